@@ -0,0 +1,215 @@
+//! Batch strategies and equity on 2-D ndarray.
+//!
+//! Each lane along `axis` is a series, e.g. for an (instruments × time)
+//! matrix use `Axis(1)` to backtest every instrument. Lanes are processed in
+//! parallel when the `rayon` feature is enabled.
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use serde_json::Value;
+use tevec::{
+    export::ndarray::{Array2, ArrayView2, Axis},
+    prelude::*,
+};
+
+use crate::{
+    equity::{calc_future_ret, FutureRetKwargs},
+    registry::{StrategyData, StrategyRegistry},
+};
+
+#[inline]
+fn lanes_to_vec<T: Clone>(arr: &ArrayView2<T>, axis: Axis) -> Vec<Vec<T>> {
+    arr.lanes(axis).into_iter().map(|l| l.to_vec()).collect()
+}
+
+/// Write the lanes back to a matrix of `shape`.
+fn vec_to_array(lanes: Vec<Vec<f64>>, shape: (usize, usize), axis: Axis) -> TResult<Array2<f64>> {
+    let mut out = Array2::from_elem(shape, f64::NAN);
+    let len = if axis.0 == 0 { shape.0 } else { shape.1 };
+    for (mut lane, v) in out.lanes_mut(axis).into_iter().zip(lanes) {
+        tensure!(
+            v.len() == len,
+            "length of output should be equal to the length of input"
+        );
+        lane.iter_mut().zip(v).for_each(|(o, v)| *o = v);
+    }
+    Ok(out)
+}
+
+/// Apply `f` to every lane along `axis`, the output has the same shape as
+/// the input.
+pub fn batch_apply<F>(arr: ArrayView2<f64>, axis: Axis, f: F) -> TResult<Array2<f64>>
+where
+    F: Fn(&Vec<f64>) -> TResult<Vec<f64>> + Sync,
+{
+    tensure!(axis.0 < 2, "axis should be 0 or 1");
+    let lanes = lanes_to_vec(&arr, axis);
+    #[cfg(feature = "rayon")]
+    let res = lanes.par_iter().map(&f).collect::<TResult<Vec<_>>>()?;
+    #[cfg(not(feature = "rayon"))]
+    let res = lanes.iter().map(&f).collect::<TResult<Vec<_>>>()?;
+    vec_to_array(res, arr.dim(), axis)
+}
+
+/// Run the strategy registered in [`StrategyRegistry`] on every lane of the
+/// factor matrix.
+pub fn batch_strategy(
+    fac: ArrayView2<f64>,
+    axis: Axis,
+    name: &str,
+    kwargs: &Value,
+) -> TResult<Array2<f64>> {
+    let registry = StrategyRegistry::default();
+    tensure!(
+        registry.contains(name),
+        "strategy {} is not registered",
+        name
+    );
+    batch_apply(fac, axis, |fac| {
+        registry.run(name, &StrategyData::new(fac.clone()), kwargs)
+    })
+}
+
+/// [`calc_future_ret`] on every lane of the position matrix, `contract_chg`
+/// is the contract change signal of each lane.
+pub fn batch_future_ret(
+    pos: ArrayView2<f64>,
+    open: ArrayView2<f64>,
+    close: ArrayView2<f64>,
+    contract_chg: Option<ArrayView2<Option<bool>>>,
+    axis: Axis,
+    kwargs: &FutureRetKwargs,
+) -> TResult<Array2<f64>> {
+    tensure!(axis.0 < 2, "axis should be 0 or 1");
+    tensure!(
+        pos.dim() == open.dim() && pos.dim() == close.dim(),
+        "shape of pos, open and close should be equal"
+    );
+    if let Some(chg) = &contract_chg {
+        tensure!(
+            chg.dim() == pos.dim(),
+            "shape of contract_chg should be equal to the shape of pos"
+        );
+    }
+    let mut chg_lanes = contract_chg.map(|chg| lanes_to_vec(&chg, axis).into_iter());
+    let lanes: Vec<_> = lanes_to_vec(&pos, axis)
+        .into_iter()
+        .zip(lanes_to_vec(&open, axis))
+        .zip(lanes_to_vec(&close, axis))
+        .map(|((pos, open), close)| {
+            let chg = chg_lanes.as_mut().and_then(|it| it.next());
+            (pos, open, close, chg)
+        })
+        .collect();
+    type Lane = (Vec<f64>, Vec<f64>, Vec<f64>, Option<Vec<Option<bool>>>);
+    let run = |(pos, open, close, chg): Lane| -> Vec<f64> {
+        calc_future_ret(&pos, &open, &close, chg, kwargs)
+    };
+    #[cfg(feature = "rayon")]
+    let res = lanes.into_par_iter().map(run).collect();
+    #[cfg(not(feature = "rayon"))]
+    let res = lanes.into_iter().map(run).collect();
+    vec_to_array(res, pos.dim(), axis)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tevec::export::ndarray::{stack, Array1};
+
+    use super::*;
+    use crate::{boll, BollKwargs, StrategyFilter};
+
+    #[test]
+    fn test_batch() -> TResult<()> {
+        let close = vec![
+            10., 11., 11.9, 10., 11., 12., 10., 11., 12., 13., 14., 10., 7., 5., 4., 3., 4., 4.,
+            3., 2.,
+        ];
+        let close2: Vec<f64> = close.iter().rev().copied().collect();
+        let kwargs = BollKwargs {
+            params: (4, 1.0, 0., None),
+            delay_open: false,
+            ..Default::default()
+        };
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let expect: Vec<f64> = boll(&close, filter.as_ref(), &kwargs);
+        let expect2: Vec<f64> = boll(&close2, filter.as_ref(), &kwargs);
+        // instruments × time
+        let fac = stack(
+            Axis(0),
+            &[
+                Array1::from(close.clone()).view(),
+                Array1::from(close2).view(),
+            ],
+        )
+        .unwrap();
+        let signal = batch_apply(fac.view(), Axis(1), |fac| {
+            Ok(boll(fac, filter.as_ref(), &kwargs))
+        })?;
+        assert_eq!(signal.row(0).to_vec(), expect);
+        assert_eq!(signal.row(1).to_vec(), expect2);
+        // time × instruments
+        let json_kwargs = json!({"params": [4, 1.0, 0.0, null], "min_periods": null,
+            "delay_open": false, "zscore": true, "long_signal": 1.0, "short_signal": -1.0,
+            "close_signal": 0.0});
+        let signal_t = batch_strategy(fac.t(), Axis(0), "boll", &json_kwargs)?;
+        assert_eq!(signal_t, signal.t());
+
+        let future_kwargs = FutureRetKwargs {
+            init_cash: 10000,
+            multiplier: 1.,
+            leverage: 1.,
+            slippage: 0.,
+            c_rate: 0.0003,
+            blowup: false,
+            commission_type: "percent".into(),
+        };
+        let equity = batch_future_ret(
+            signal.view(),
+            fac.view(),
+            fac.view(),
+            None,
+            Axis(1),
+            &future_kwargs,
+        )?;
+        let expect_equity: Vec<f64> =
+            calc_future_ret(&expect, &close, &close, None::<Vec<_>>, &future_kwargs);
+        assert_eq!(equity.row(0).to_vec(), expect_equity);
+        assert!(batch_future_ret(
+            signal.view(),
+            fac.t(),
+            fac.t(),
+            None,
+            Axis(1),
+            &future_kwargs
+        )
+        .is_err());
+        // contract changes of the first instrument
+        let mut chg = Array2::from_elem(fac.dim(), Some(false));
+        chg[[0, 5]] = Some(true);
+        chg[[0, 12]] = Some(true);
+        let equity = batch_future_ret(
+            signal.view(),
+            fac.view(),
+            fac.view(),
+            Some(chg.view()),
+            Axis(1),
+            &future_kwargs,
+        )?;
+        let chg0 = chg.row(0).to_vec();
+        let expect_chg: Vec<f64> =
+            calc_future_ret(&expect, &close, &close, Some(chg0), &future_kwargs);
+        assert_ne!(expect_chg, expect_equity);
+        assert_eq!(equity.row(0).to_vec(), expect_chg);
+        assert!(batch_future_ret(
+            signal.view(),
+            fac.view(),
+            fac.view(),
+            Some(chg.t()),
+            Axis(1),
+            &future_kwargs
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+//! Run a backtest from a TOML or JSON config file.
+//!
+//! ```text
+//! tea_strategy <config.toml|config.json> [--output <dir>]
+//! ```
+//!
+//! The config has four sections:
+//!
+//! ```toml
+//! [data]
+//! path = "bars.parquet"  # csv or parquet
+//! time = "time"
+//! fac = "fac"            # use close if not set
+//!
+//! [strategy]
+//! name = "grid"
+//! kwargs = { spacing_type = "percent", spacing = 0.01, levels = 5, per_level = 0.2, max_pos = 1.0, recenter = true }
+//!
+//! [equity]
+//! engine = "future"      # or "tick", which needs bid and ask columns
+//! init_cash = 1000000
+//! multiplier = 10.0
+//! leverage = 1.0
+//! slippage = 0.0
+//! c_rate = 0.0003
+//! blowup = false
+//! commission_type = "percent"
+//!
+//! [output]
+//! dir = "output"         # optional if --output is given
+//! annualize = 252.0
+//! ```
+//!
+//! `signal.csv`, `equity.csv`, `trades.csv` and `metrics.json` are written
+//! to the output directory.
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use tea_strategy::{
+    equity::{calc_future_ret, calc_tick_future_ret, FutureRetKwargs, TickFutureRetKwargs},
+    io::{read_table, write_equity, write_signal, write_trades},
+    metrics::Metrics,
+    registry::{StrategyConfig, StrategyData, StrategyRegistry},
+    signal_to_trades,
+    tevec::prelude::{terr, TIter},
+    PriceVec,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn default_time() -> String {
+    "time".to_string()
+}
+
+fn default_open() -> String {
+    "open".to_string()
+}
+
+fn default_close() -> String {
+    "close".to_string()
+}
+
+fn default_annualize() -> f64 {
+    252.
+}
+
+/// column mapping of the input data
+#[derive(Deserialize)]
+struct DataConfig {
+    path: PathBuf,
+    #[serde(default = "default_time")]
+    time: String,
+    fac: Option<String>,
+    #[serde(default = "default_open")]
+    open: String,
+    #[serde(default = "default_close")]
+    close: String,
+    high: Option<String>,
+    low: Option<String>,
+    bid: Option<String>,
+    ask: Option<String>,
+    contract_chg: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "engine", rename_all = "snake_case")]
+enum EquityConfig {
+    Future(FutureRetKwargs),
+    Tick(TickFutureRetKwargs),
+}
+
+#[derive(Deserialize)]
+struct OutputConfig {
+    /// overridden by `--output`
+    dir: Option<PathBuf>,
+    #[serde(default = "default_annualize")]
+    annualize: f64,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            annualize: default_annualize(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Config {
+    data: DataConfig,
+    strategy: StrategyConfig,
+    equity: EquityConfig,
+    #[serde(default)]
+    output: OutputConfig,
+}
+
+/// parse a json config if `ext` is json, otherwise a toml config
+fn parse_config(s: &str, ext: Option<&str>) -> Result<Config> {
+    let config = match ext {
+        Some("json") => serde_json::from_str(s)?,
+        _ => toml::from_str(s)?,
+    };
+    Ok(config)
+}
+
+fn read_config(path: &Path) -> Result<Config> {
+    let s = std::fs::read_to_string(path)?;
+    parse_config(&s, path.extension().and_then(|s| s.to_str()))
+}
+
+fn run(config_path: &Path, output_dir: Option<PathBuf>) -> Result<()> {
+    let config = read_config(config_path)?;
+    let Some(dir) = output_dir.or(config.output.dir) else {
+        return Err(terr!("output dir should be set by [output] or --output").into());
+    };
+    let data = &config.data;
+    let table = read_table(&data.path)?;
+    let time = table.time_column(&data.time)?;
+    let close = table.required(&data.close)?;
+    let fac = match &data.fac {
+        Some(fac) => table.required(fac)?,
+        None => close.clone(),
+    };
+    let mut strategy_data = StrategyData::new(fac)
+        .with_close(close.clone())
+        .with_time(time.clone());
+    if let (Some(high), Some(low)) = (&data.high, &data.low) {
+        strategy_data = strategy_data.with_high_low(table.required(high)?, table.required(low)?);
+    }
+    let contract_chg: Option<Vec<Option<bool>>> = match &data.contract_chg {
+        Some(name) => Some(
+            table
+                .bool_column(name)?
+                .ok_or_else(|| terr!("column {} is not found", name))?,
+        ),
+        None => None,
+    };
+    let signal = StrategyRegistry::default().run_config(&config.strategy, &strategy_data)?;
+    let (equity, trades): (Vec<f64>, _) = match &config.equity {
+        EquityConfig::Future(kwargs) => {
+            let open = table.required(&data.open)?;
+            let equity = calc_future_ret(&signal, &open, &close, contract_chg, kwargs);
+            let trades =
+                signal_to_trades(signal.titer(), PriceVec::Single(open.titer()), time.titer());
+            (equity, trades)
+        },
+        EquityConfig::Tick(kwargs) => {
+            let (Some(bid), Some(ask)) = (&data.bid, &data.ask) else {
+                return Err(terr!("bid and ask columns are required by tick engine").into());
+            };
+            let (bid, ask) = (table.required(bid)?, table.required(ask)?);
+            let equity = calc_tick_future_ret(&signal, &bid, &ask, contract_chg.as_ref(), kwargs);
+            let trades = signal_to_trades(
+                signal.titer(),
+                PriceVec::BidAsk(bid.titer(), ask.titer()),
+                time.titer(),
+            );
+            (equity, trades)
+        },
+    };
+    let metrics = Metrics::from_equity(&equity, config.output.annualize);
+
+    std::fs::create_dir_all(&dir)?;
+    write_signal(dir.join("signal.csv"), &time, &signal)?;
+    write_equity(dir.join("equity.csv"), &time, &equity)?;
+    write_trades(dir.join("trades.csv"), &trades)?;
+    let summary = serde_json::json!({
+        "strategy": config.strategy.name,
+        "total_return": metrics.total_return,
+        "annual_return": metrics.annual_return,
+        "annual_vol": metrics.annual_vol,
+        "sharpe": metrics.sharpe,
+        "max_drawdown": metrics.max_drawdown,
+        "calmar": metrics.calmar,
+        "trade_count": trades.len(),
+    });
+    std::fs::write(
+        dir.join("metrics.json"),
+        serde_json::to_string_pretty(&summary)?,
+    )?;
+    Ok(())
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut config_path = None;
+    let mut output_dir = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output_dir = args.next().map(PathBuf::from),
+            "-h" | "--help" => {
+                println!("usage: tea_strategy <config.toml|config.json> [--output <dir>]");
+                return;
+            },
+            _ => config_path = Some(PathBuf::from(arg)),
+        }
+    }
+    let Some(config_path) = config_path else {
+        eprintln!("usage: tea_strategy <config.toml|config.json> [--output <dir>]");
+        std::process::exit(2);
+    };
+    if let Err(e) = run(&config_path, output_dir) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[data]
+path = "bars.csv"
+
+[strategy]
+name = "boll"
+kwargs = { params = [4, 1.0, 0.0, 10.0], delay_open = false, zscore = true, long_signal = 1.0, short_signal = -1.0, close_signal = 0.0, control = { max_trades_per_day = 1 } }
+
+[equity]
+engine = "future"
+init_cash = 100000
+multiplier = 1.0
+leverage = 1.0
+slippage = 0.0
+c_rate = 0.0003
+blowup = false
+commission_type = "percent"
+"#;
+
+    #[test]
+    fn test_parse_config() -> Result<()> {
+        let config = parse_config(CONFIG, Some("toml"))?;
+        assert_eq!(config.data.path, PathBuf::from("bars.csv"));
+        assert_eq!(config.data.close, "close");
+        assert_eq!(config.strategy.name, "boll");
+        assert!(matches!(config.equity, EquityConfig::Future(_)));
+        assert!(config.output.dir.is_none());
+        assert_eq!(config.output.annualize, 252.);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("tea_strategy_cli_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let mut bars = String::from("time,open,close\n");
+        for i in 0..40 {
+            let close = 10. + (i as f64 * 0.5).sin();
+            bars += &format!(
+                "2024-01-{:02} {:02}:00:00,{},{}\n",
+                i / 8 + 1,
+                9 + i % 8,
+                close - 0.1,
+                close
+            );
+        }
+        std::fs::write(dir.join("bars.csv"), bars)?;
+        let config = CONFIG.replace("bars.csv", &dir.join("bars.csv").to_string_lossy());
+        std::fs::write(dir.join("config.toml"), config)?;
+        // the output dir is required by [output] or --output
+        assert!(run(&dir.join("config.toml"), None).is_err());
+        let output = dir.join("output");
+        run(&dir.join("config.toml"), Some(output.clone()))?;
+        for name in ["signal.csv", "equity.csv", "trades.csv", "metrics.json"] {
+            assert!(output.join(name).exists());
+        }
+        let metrics: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(output.join("metrics.json"))?)?;
+        assert_eq!(metrics["strategy"], "boll");
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
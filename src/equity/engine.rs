@@ -0,0 +1,813 @@
+//! Execution engine shared by the equity functions.
+//!
+//! An engine is assembled from pluggable components: a [`FillModel`] prices
+//! the fills, a [`FeeModel`] charges commission and slippage and a [`Sizer`]
+//! converts a signal to lots, while the engine itself settles the profit and
+//! handles the contract change. The equity functions are configurations of
+//! [`BarEngine`] and [`TickEngine`] driven by [`run_engine`].
+use tevec::prelude::*;
+
+use super::{result::Recorder, CommissionType};
+
+/// Slippage of a fill, in price or in cash per lot.
+#[derive(Clone, Copy)]
+pub(super) enum Slippage {
+    Price(f64),
+    Lot(f64),
+}
+
+/// Execution of a fill.
+#[derive(Clone, Copy)]
+pub(super) struct Exec {
+    /// the price traded at
+    pub price: f64,
+    /// the price the commission is charged on
+    pub fee_price: f64,
+    pub slippage: Slippage,
+}
+
+impl Exec {
+    #[inline]
+    fn new(price: f64, slippage: Slippage) -> Self {
+        Self {
+            price,
+            fee_price: price,
+            slippage,
+        }
+    }
+}
+
+/// Fill pricing of an engine.
+pub(super) trait FillModel<B> {
+    /// execution of a fill of signed `lots`
+    fn fill(&self, bar: &B, lots: f64) -> Exec;
+
+    /// execution of signed `lots` closed or reopened at a contract change
+    #[inline]
+    fn roll(&self, bar: &B, lots: f64) -> Exec {
+        self.fill(bar, lots)
+    }
+}
+
+impl<B, F: FillModel<B> + ?Sized> FillModel<B> for Box<F> {
+    #[inline]
+    fn fill(&self, bar: &B, lots: f64) -> Exec {
+        (**self).fill(bar, lots)
+    }
+
+    #[inline]
+    fn roll(&self, bar: &B, lots: f64) -> Exec {
+        (**self).roll(bar, lots)
+    }
+}
+
+/// Commission model of an engine.
+pub(super) trait FeeModel {
+    /// Return the total cost of trading `lots` (unsigned) at `price`, the
+    /// commission and the slippage are sent to `rec` separately.
+    fn charge<R: Recorder>(&self, lots: f64, price: f64, slippage: Slippage, rec: &mut R) -> f64;
+
+    /// commission of trading `lots` at `price`, slippage excluded
+    fn commission(&self, lots: f64, price: f64) -> f64;
+}
+
+/// Convert a signal to the number of lots.
+pub(super) trait Sizer {
+    /// unsigned lots of `signal` given the cash and the price
+    fn lots(&self, signal: f64, cash: f64, price: f64) -> f64;
+}
+
+impl<S: Sizer + ?Sized> Sizer for Box<S> {
+    #[inline]
+    fn lots(&self, signal: f64, cash: f64, price: f64) -> f64 {
+        (**self).lots(signal, cash, price)
+    }
+}
+
+/// Percent or absolute commission of [`CommissionType`].
+#[derive(Clone, Copy)]
+pub(super) struct Commission {
+    commission_type: CommissionType,
+    c_rate: f64,
+    multiplier: f64,
+}
+
+impl Commission {
+    #[inline]
+    pub fn new(commission_type: CommissionType, c_rate: f64, multiplier: f64) -> Self {
+        Self {
+            commission_type,
+            c_rate,
+            multiplier,
+        }
+    }
+}
+
+impl FeeModel for Commission {
+    #[inline]
+    fn charge<R: Recorder>(&self, lots: f64, price: f64, slippage: Slippage, rec: &mut R) -> f64 {
+        let (multiplier, c_rate) = (self.multiplier, self.c_rate);
+        match (self.commission_type, slippage) {
+            (CommissionType::Percent, Slippage::Price(s)) => {
+                rec.cost(lots * multiplier * price * c_rate, lots * multiplier * s);
+                lots * multiplier * (price * c_rate + s)
+            },
+            (CommissionType::Percent, Slippage::Lot(s)) => {
+                rec.cost(lots * multiplier * price * c_rate, lots * s);
+                lots * (multiplier * price * c_rate + s)
+            },
+            (CommissionType::Absolute, Slippage::Price(s)) => {
+                rec.cost(lots * c_rate, lots * s * multiplier);
+                lots * (c_rate + s * multiplier)
+            },
+            (CommissionType::Absolute, Slippage::Lot(s)) => {
+                rec.cost(lots * c_rate, lots * s);
+                lots * (c_rate + s)
+            },
+        }
+    }
+
+    #[inline]
+    fn commission(&self, lots: f64, price: f64) -> f64 {
+        if let CommissionType::Percent = self.commission_type {
+            lots * price * self.c_rate * self.multiplier
+        } else {
+            lots * self.c_rate
+        }
+    }
+}
+
+/// Size by `cash * leverage * |signal|`, the signal is a percent of equity.
+#[derive(Clone, Copy)]
+pub(super) struct LeverageSizer {
+    leverage: f64,
+    multiplier: f64,
+}
+
+impl LeverageSizer {
+    #[inline]
+    pub fn new(leverage: f64, multiplier: f64) -> Self {
+        Self {
+            leverage,
+            multiplier,
+        }
+    }
+}
+
+impl Sizer for LeverageSizer {
+    #[inline]
+    fn lots(&self, signal: f64, cash: f64, price: f64) -> f64 {
+        ((cash * self.leverage * signal.abs()) / (self.multiplier * price)).floor()
+    }
+}
+
+/// The signal is the number of lots.
+#[derive(Clone, Copy)]
+pub(super) struct FixedLots;
+
+impl Sizer for FixedLots {
+    #[inline]
+    fn lots(&self, signal: f64, _cash: f64, _price: f64) -> f64 {
+        signal.abs()
+    }
+}
+
+/// Size by board lots of `cash * |signal|`, the signal is a percent of equity.
+#[derive(Clone, Copy)]
+pub(super) struct BoardLotSizer {
+    lot_size: f64,
+}
+
+impl BoardLotSizer {
+    #[inline]
+    pub fn new(lot_size: f64) -> Self {
+        Self { lot_size }
+    }
+}
+
+impl Sizer for BoardLotSizer {
+    #[inline]
+    fn lots(&self, signal: f64, cash: f64, price: f64) -> f64 {
+        ((cash * signal.abs()) / (price * self.lot_size)).floor() * self.lot_size
+    }
+}
+
+/// When the account stops trading.
+#[derive(Clone, Copy)]
+pub(super) enum Blowup {
+    Never,
+    NonPositive,
+    Negative,
+}
+
+impl Blowup {
+    /// stop trading when the cash is not positive if `blowup` is true
+    #[inline]
+    pub fn new(blowup: bool) -> Self {
+        if blowup {
+            Blowup::NonPositive
+        } else {
+            Blowup::Never
+        }
+    }
+
+    #[inline]
+    pub fn check(self, cash: f64) -> bool {
+        match self {
+            Blowup::Never => false,
+            Blowup::NonPositive => cash <= 0.,
+            Blowup::Negative => cash < 0.,
+        }
+    }
+}
+
+/// An account updated bar by bar.
+pub(super) trait Engine {
+    type Bar;
+
+    /// equity of the account, reported for an invalid bar
+    fn equity(&self) -> f64;
+
+    /// whether the account is blown up and stops trading
+    fn is_blowup(&self) -> bool;
+
+    /// an invalid bar (or a bar after blowup) is skipped
+    #[inline]
+    fn skip(&mut self) {}
+
+    /// Update the account by a valid bar and return the equity and the cost
+    /// of the bar. `chg` is `None` when no contract change signal is given.
+    fn step<R: Recorder>(
+        &mut self,
+        bar: &Self::Bar,
+        chg: Option<Option<bool>>,
+        rec: &mut R,
+    ) -> (f64, f64);
+}
+
+/// Run `engine` over `bars` (`None` for an invalid bar) and return the
+/// equity and the cost of each bar, stop at the end of the shortest input.
+pub(super) fn run_engine<E, I, C, R>(
+    engine: &mut E,
+    bars: I,
+    chg: Option<C>,
+    rec: &mut R,
+) -> Vec<(f64, f64)>
+where
+    E: Engine,
+    I: IntoIterator<Item = Option<E::Bar>>,
+    C: IntoIterator<Item = Option<bool>>,
+    R: Recorder,
+{
+    let bars = bars.into_iter();
+    let mut chg = chg.map(IntoIterator::into_iter);
+    let mut out = Vec::with_capacity(bars.size_hint().0);
+    for bar in bars {
+        let chg = match chg.as_mut() {
+            Some(chg) => match chg.next() {
+                Some(c) => Some(c),
+                None => break,
+            },
+            None => None,
+        };
+        out.push(match bar {
+            None => {
+                engine.skip();
+                rec.finish(engine.equity(), f64::NAN);
+                (engine.equity(), 0.)
+            },
+            Some(_) if engine.is_blowup() => {
+                engine.skip();
+                rec.finish(0., f64::NAN);
+                (0., 0.)
+            },
+            Some(bar) => engine.step(&bar, chg, rec),
+        });
+    }
+    out
+}
+
+/// A bar traded at the open and settled at the close.
+pub(super) struct Bar {
+    pub signal: f64,
+    pub open: f64,
+    pub close: f64,
+    pub spread: Option<f64>,
+}
+
+impl Bar {
+    /// `None` if any of the inputs is none
+    #[inline]
+    pub fn new<T: IsNone>(signal: T, open: T, close: T) -> Option<Self>
+    where
+        T::Inner: Number,
+    {
+        if signal.is_none() || open.is_none() || close.is_none() {
+            return None;
+        }
+        Some(Self {
+            signal: signal.unwrap().f64(),
+            open: open.unwrap().f64(),
+            close: close.unwrap().f64(),
+            spread: None,
+        })
+    }
+
+    #[inline]
+    pub fn with_spread<T: IsNone>(mut self, spread: T) -> Self
+    where
+        T::Inner: Number,
+    {
+        self.spread = spread.to_opt().map(|s| s.f64());
+        self
+    }
+}
+
+/// Fill at the open with a constant slippage in price.
+#[derive(Clone, Copy)]
+pub(super) struct FixedSlippage(pub f64);
+
+impl FillModel<Bar> for FixedSlippage {
+    #[inline]
+    fn fill(&self, bar: &Bar, _lots: f64) -> Exec {
+        Exec::new(bar.open, Slippage::Price(self.0))
+    }
+}
+
+/// Fill at the open with the spread of the bar as slippage, the commission
+/// rate is used when the spread is missing.
+#[derive(Clone, Copy)]
+pub(super) struct SpreadSlippage {
+    commission_type: CommissionType,
+    c_rate: f64,
+}
+
+impl SpreadSlippage {
+    #[inline]
+    pub fn new(commission_type: CommissionType, c_rate: f64) -> Self {
+        Self {
+            commission_type,
+            c_rate,
+        }
+    }
+}
+
+impl FillModel<Bar> for SpreadSlippage {
+    #[inline]
+    fn fill(&self, bar: &Bar, _lots: f64) -> Exec {
+        let slippage = match (bar.spread, self.commission_type) {
+            (Some(spread), _) => Slippage::Price(spread),
+            (None, CommissionType::Percent) => Slippage::Price(bar.open * self.c_rate),
+            (None, CommissionType::Absolute) => Slippage::Lot(self.c_rate),
+        };
+        Exec::new(bar.open, slippage)
+    }
+}
+
+/// Position of a bar engine, rebalanced at the open.
+pub(super) struct BarPosition {
+    signal: f64,
+    lot_num: f64,
+    last_close: Option<f64>,
+}
+
+impl BarPosition {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            signal: 0.,
+            lot_num: 0.,
+            last_close: None,
+        }
+    }
+
+    /// signed lots held
+    #[inline]
+    pub fn lots(&self) -> f64 {
+        self.lot_num * self.signal.signum()
+    }
+
+    /// Add the profit of the jump from the last close to the open, the jump
+    /// is ignored when there is a contract change.
+    #[inline]
+    pub fn settle_open(&mut self, cash: &mut f64, open: f64, multiplier: f64, chg: bool) {
+        if self.last_close.is_none() {
+            self.last_close = Some(open)
+        }
+        if (self.lot_num != 0.) && (!chg) {
+            *cash += self.lot_num
+                * (open - self.last_close.unwrap())
+                * multiplier
+                * self.signal.signum();
+        }
+    }
+
+    /// Change the position to the signal of the bar sized by `cash`, return
+    /// the cost. The old contract is closed at the last close and the new one
+    /// is opened at the open when there is a contract change.
+    #[inline]
+    #[allow(clippy::too_many_arguments)]
+    pub fn rebalance<F, E, S, R>(
+        &mut self,
+        bar: &Bar,
+        cash: f64,
+        chg: bool,
+        fill: &F,
+        fee: &E,
+        sizer: &S,
+        rec: &mut R,
+    ) -> f64
+    where
+        F: FillModel<Bar>,
+        E: FeeModel,
+        S: Sizer,
+        R: Recorder,
+    {
+        // we use the signal to determine the position change, so leverage must be a constant
+        if (bar.signal == self.signal) && !chg {
+            return 0.;
+        }
+        let lot_num = sizer.lots(bar.signal, cash, bar.open);
+        let last_lots = self.lots();
+        let cost = if !chg {
+            let change = lot_num * bar.signal.signum() - last_lots;
+            let exec = fill.fill(bar, change);
+            rec.fill(bar.open, exec.price, change);
+            fee.charge(change.abs(), exec.fee_price, exec.slippage, rec)
+        } else {
+            let last_close = self.last_close.unwrap();
+            rec.roll(last_close, last_close, -last_lots);
+            let lots = lot_num * bar.signal.signum();
+            let exec = fill.roll(bar, lots);
+            rec.fill(bar.open, exec.price, lots);
+            fee.charge(lot_num.abs() * 2., exec.fee_price, exec.slippage, rec)
+        };
+        self.lot_num = lot_num;
+        self.signal = bar.signal;
+        cost
+    }
+
+    /// add the profit from the open to the close
+    #[inline]
+    pub fn settle_close(&mut self, cash: &mut f64, open: f64, close: f64, multiplier: f64) {
+        if self.lot_num != 0. {
+            *cash += self.lot_num * self.signal.signum() * (close - open) * multiplier;
+        }
+        self.last_close = Some(close);
+    }
+}
+
+/// A single future account sized at the open of each bar.
+pub(super) struct BarEngine<F, E, S> {
+    fill: F,
+    fee: E,
+    sizer: S,
+    multiplier: f64,
+    blowup: Blowup,
+    cash: f64,
+    position: BarPosition,
+}
+
+impl<F, E, S> BarEngine<F, E, S> {
+    #[inline]
+    pub fn new(fill: F, fee: E, sizer: S, multiplier: f64, blowup: Blowup, init_cash: f64) -> Self {
+        Self {
+            fill,
+            fee,
+            sizer,
+            multiplier,
+            blowup,
+            cash: init_cash,
+            position: BarPosition::new(),
+        }
+    }
+}
+
+impl<F: FillModel<Bar>, E: FeeModel, S: Sizer> Engine for BarEngine<F, E, S> {
+    type Bar = Bar;
+
+    #[inline]
+    fn equity(&self) -> f64 {
+        self.cash
+    }
+
+    #[inline]
+    fn is_blowup(&self) -> bool {
+        self.blowup.check(self.cash)
+    }
+
+    fn step<R: Recorder>(
+        &mut self,
+        bar: &Bar,
+        chg: Option<Option<bool>>,
+        rec: &mut R,
+    ) -> (f64, f64) {
+        // a None contract change signal is not allowed when the signal is given
+        let chg = chg.map(|c| c.unwrap()).unwrap_or(false);
+        self.position
+            .settle_open(&mut self.cash, bar.open, self.multiplier, chg);
+        rec.settle(self.cash);
+        let cost =
+            self.position
+                .rebalance(bar, self.cash, chg, &self.fill, &self.fee, &self.sizer, rec);
+        self.cash -= cost;
+        self.position
+            .settle_close(&mut self.cash, bar.open, bar.close, self.multiplier);
+        rec.finish(self.cash, bar.close);
+        (self.cash, cost)
+    }
+}
+
+/// A tick with bid and ask price.
+pub(super) struct Quote {
+    pub signal: f64,
+    pub bid: f64,
+    pub ask: f64,
+}
+
+impl Quote {
+    /// `None` if any of the inputs is none
+    #[inline]
+    pub fn new<T: IsNone>(signal: T, bid: T, ask: T) -> Option<Self>
+    where
+        T::Inner: Number,
+    {
+        if signal.is_none() || bid.is_none() || ask.is_none() {
+            return None;
+        }
+        Some(Self {
+            signal: signal.unwrap().f64(),
+            bid: bid.unwrap().f64(),
+            ask: ask.unwrap().f64(),
+        })
+    }
+
+    #[inline]
+    pub fn mid(&self) -> f64 {
+        (self.bid + self.ask) * 0.5
+    }
+}
+
+/// Buy at the ask and sell at the bid, the half spread is the slippage.
+///
+/// At a contract change the commission is charged at the mid price with
+/// half of the quoted spread, since the quote of the next contract is unknown.
+#[derive(Clone, Copy)]
+pub(super) struct BidAsk;
+
+impl FillModel<Quote> for BidAsk {
+    #[inline]
+    fn fill(&self, bar: &Quote, lots: f64) -> Exec {
+        let mid = bar.mid();
+        if lots > 0. {
+            Exec::new(bar.ask, Slippage::Price(bar.ask - mid))
+        } else {
+            Exec::new(bar.bid, Slippage::Price(mid - bar.bid))
+        }
+    }
+
+    #[inline]
+    fn roll(&self, bar: &Quote, lots: f64) -> Exec {
+        Exec {
+            price: if lots < 0. { bar.bid } else { bar.ask },
+            fee_price: bar.mid(),
+            slippage: Slippage::Price((bar.ask - bar.bid) * 0.5),
+        }
+    }
+}
+
+/// Post orders as a maker, buy at the bid and sell at the ask.
+#[derive(Clone, Copy)]
+pub(super) struct Passive;
+
+impl FillModel<Quote> for Passive {
+    #[inline]
+    fn fill(&self, bar: &Quote, lots: f64) -> Exec {
+        let mid = bar.mid();
+        if lots > 0. {
+            Exec::new(bar.bid, Slippage::Price(bar.bid - mid))
+        } else {
+            Exec::new(bar.ask, Slippage::Price(mid - bar.ask))
+        }
+    }
+}
+
+/// Lots charged when the position closed at a contract change is reopened.
+#[derive(Clone, Copy)]
+pub(super) enum Reopen {
+    /// resize the lots by the price of the new contract
+    Resize,
+    /// charge the lots of the current signal
+    Signal,
+    /// charge the lots held
+    Held,
+}
+
+/// A future account marked to the mid price of each tick.
+///
+/// The position is closed at the tick of a contract change and reopened at
+/// the next tick, the profit of the first tick of the new contract is ignored.
+pub(super) struct TickEngine<F, E, S> {
+    fill: F,
+    fee: E,
+    sizer: S,
+    multiplier: f64,
+    blowup: Blowup,
+    reopen: Reopen,
+    /// return the equity after the commission of the tick when no contract
+    /// change signal is given, otherwise the equity is before commission
+    after_fee: bool,
+    cash: f64,
+    signal: f64,
+    lot_num: f64,
+    last_chg: bool,
+    last_mid: f64,
+}
+
+impl<F, E, S> TickEngine<F, E, S> {
+    #[inline]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        fill: F,
+        fee: E,
+        sizer: S,
+        multiplier: f64,
+        blowup: Blowup,
+        reopen: Reopen,
+        after_fee: bool,
+        init_cash: f64,
+    ) -> Self {
+        Self {
+            fill,
+            fee,
+            sizer,
+            multiplier,
+            blowup,
+            reopen,
+            after_fee,
+            cash: init_cash,
+            signal: 0.,
+            lot_num: 0.,
+            last_chg: false,
+            last_mid: f64::NAN,
+        }
+    }
+
+    /// signed lots held
+    #[inline]
+    fn lots(&self) -> f64 {
+        self.lot_num * self.signal.signum()
+    }
+}
+
+impl<F: FillModel<Quote>, E: FeeModel, S: Sizer> Engine for TickEngine<F, E, S> {
+    type Bar = Quote;
+
+    #[inline]
+    fn equity(&self) -> f64 {
+        self.cash
+    }
+
+    #[inline]
+    fn is_blowup(&self) -> bool {
+        self.blowup.check(self.cash)
+    }
+
+    fn step<R: Recorder>(
+        &mut self,
+        bar: &Quote,
+        chg: Option<Option<bool>>,
+        rec: &mut R,
+    ) -> (f64, f64) {
+        let has_chg = chg.is_some();
+        let chg = chg.flatten().unwrap_or(false);
+        let mid = bar.mid();
+        let mut cost = 0.;
+        if self.last_chg && self.lot_num != 0. {
+            // the position is reopened after a contract change
+            let lot_num = match self.reopen {
+                Reopen::Resize => {
+                    self.lot_num = ((self.lot_num * self.last_mid) / mid).floor();
+                    self.lot_num
+                },
+                Reopen::Signal => bar.signal.abs(),
+                Reopen::Held => self.lot_num,
+            };
+            let lots = self.lots();
+            let exec = self.fill.roll(bar, lots);
+            let c = self.fee.charge(lot_num, exec.fee_price, exec.slippage, rec);
+            self.cash -= c;
+            cost += c;
+            rec.fill(mid, exec.price, lots);
+        }
+        // calculate the profit and loss of the current period
+        // we should not calculate the profit in this way if the contract has changed
+        if (self.lot_num != 0.) && self.last_mid.not_none() && (!self.last_chg) {
+            self.cash += self.lots() * (mid - self.last_mid) * self.multiplier;
+        }
+        let out = self.cash;
+        rec.settle(out);
+        // TODO(Teamon): how to handle the case when the contract has changed
+        // should we pass daily open price as another input?
+        // currently we just ignore the profit and loss in the first tick when there is a contract change
+        if (bar.signal != self.signal) || chg {
+            // the position has changed, calculate the new theoretical number of lots
+            let lot_num = self.sizer.lots(bar.signal, self.cash, mid);
+            let last_lots = self.lots();
+            let c = if !chg {
+                let change = lot_num * bar.signal.signum() - last_lots;
+                let exec = self.fill.fill(bar, change);
+                rec.fill(mid, exec.price, change);
+                self.fee
+                    .charge(change.abs(), exec.fee_price, exec.slippage, rec)
+            } else {
+                let exec = self.fill.roll(bar, -last_lots);
+                rec.roll(mid, exec.price, -last_lots);
+                self.fee
+                    .charge(self.lot_num, exec.fee_price, exec.slippage, rec)
+            };
+            self.cash -= c;
+            cost += c;
+            self.lot_num = lot_num;
+            self.signal = bar.signal;
+        }
+        self.last_mid = mid;
+        self.last_chg = chg;
+        let out = if self.after_fee && !has_chg {
+            self.cash
+        } else {
+            out
+        };
+        rec.finish(out, mid);
+        (out, cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equity::{calc_future_ret, FutureRetKwargs};
+
+    /// a flat commission per fill
+    struct PerFill(f64);
+
+    impl FeeModel for PerFill {
+        fn charge<R: Recorder>(&self, lots: f64, _price: f64, _: Slippage, rec: &mut R) -> f64 {
+            let fee = if lots > 0. { self.0 } else { 0. };
+            rec.cost(fee, 0.);
+            fee
+        }
+
+        fn commission(&self, lots: f64, _price: f64) -> f64 {
+            if lots > 0. {
+                self.0
+            } else {
+                0.
+            }
+        }
+    }
+
+    #[test]
+    fn test_engine_components() {
+        let kwargs = FutureRetKwargs {
+            init_cash: 10000,
+            multiplier: 1.,
+            leverage: 1.,
+            slippage: 0.,
+            c_rate: 0.,
+            blowup: false,
+            commission_type: CommissionType::Percent,
+        };
+        let pos = vec![0., 1., 1., f64::NAN, -1., 0.];
+        let open = vec![10., 10., 11., 12., 11., 10.];
+        let close = vec![10., 11., 12., 11., 10., 10.];
+        let bars = || {
+            pos.iter()
+                .zip(&open)
+                .zip(&close)
+                .map(|((&p, &o), &c)| Bar::new(p, o, c))
+        };
+        let expect: Vec<f64> = calc_future_ret(&pos, &open, &close, None::<Vec<_>>, &kwargs);
+        let res = run_engine(&mut kwargs.engine(), bars(), None::<Vec<_>>, &mut ());
+        assert_eq!(res.iter().map(|r| r.0).collect::<Vec<_>>(), expect);
+        // a new fee model plugged into the same engine
+        let mut engine = BarEngine::new(
+            FixedSlippage(0.),
+            PerFill(5.),
+            LeverageSizer::new(1., 1.),
+            1.,
+            Blowup::new(false),
+            10000.,
+        );
+        let res = run_engine(&mut engine, bars(), None::<Vec<_>>, &mut ());
+        let fee: Vec<f64> = res.iter().map(|r| r.1).collect();
+        assert_eq!(fee, vec![0., 5., 0., 0., 5., 5.]);
+        assert!(res[5].0 < expect[5]);
+        // stop at the end of the shortest input
+        let chg = vec![Some(false); 4];
+        assert_eq!(
+            run_engine(&mut kwargs.engine(), bars(), Some(chg), &mut ()).len(),
+            4
+        );
+    }
+}
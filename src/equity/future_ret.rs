@@ -1,10 +1,14 @@
 use itertools::izip;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tevec::prelude::*;
 
-use super::CommissionType;
+use super::{
+    engine::{run_engine, Bar, BarEngine, Blowup, Commission, FixedSlippage, LeverageSizer},
+    result::{Recorder, ResultBuilder},
+    BacktestResult, CommissionType,
+};
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct FutureRetKwargs {
     pub init_cash: usize,
     pub multiplier: f64,
@@ -15,6 +19,47 @@ pub struct FutureRetKwargs {
     pub commission_type: CommissionType,
 }
 
+impl FutureRetKwargs {
+    /// engine of [`calc_future_ret`]
+    #[inline]
+    pub(super) fn engine(&self) -> BarEngine<FixedSlippage, Commission, LeverageSizer> {
+        BarEngine::new(
+            FixedSlippage(self.slippage),
+            Commission::new(self.commission_type, self.c_rate, self.multiplier),
+            LeverageSizer::new(self.leverage, self.multiplier),
+            self.multiplier,
+            Blowup::new(self.blowup),
+            self.init_cash as f64,
+        )
+    }
+}
+
+/// run the engine and return the equity and fee of each bar
+fn run_future_ret<T, V, VMask, R>(
+    pos_vec: &V,
+    open_vec: &V,
+    close_vec: &V,
+    contract_chg_signal_vec: Option<VMask>,
+    kwargs: &FutureRetKwargs,
+    rec: &mut R,
+) -> Vec<(f64, f64)>
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+    R: Recorder,
+{
+    let bars = izip!(pos_vec.titer(), open_vec.titer(), close_vec.titer())
+        .map(|(pos, open, close)| Bar::new(pos, open, close));
+    run_engine(
+        &mut kwargs.engine(),
+        bars,
+        contract_chg_signal_vec.as_ref().map(|c| c.titer()),
+        rec,
+    )
+}
+
 pub fn calc_future_ret<O, T, V, VMask>(
     pos_vec: &V,
     open_vec: &V,
@@ -29,122 +74,99 @@ where
     VMask: Vec1View<Option<bool>>,
     O: Vec1<T::Cast<f64>>,
 {
-    let mut cash = kwargs.init_cash as f64;
-    let mut last_pos = 0_f64; // pos_arr[0];
-    let mut last_lot_num = 0.;
     if pos_vec.is_empty() {
         return O::empty();
     }
-    let mut last_close = None;
-    let blowup = kwargs.blowup;
-    let multiplier = kwargs.multiplier;
-    let commission_type = kwargs.commission_type;
-    let slippage = kwargs.slippage;
-    let leverage = kwargs.leverage;
-    let c_rate = kwargs.c_rate;
-    if let Some(contract_chg_signal_vec) = contract_chg_signal_vec {
-        izip!(
-            pos_vec.titer(),
-            open_vec.titer(),
-            close_vec.titer(),
-            contract_chg_signal_vec.titer(),
-        )
-        .map(|(pos, open, close, chg)| {
-            if pos.is_none() || open.is_none() || close.is_none() {
-                return cash.into_cast::<T>();
-            } else if blowup && cash <= 0. {
-                return 0_f64.into_cast::<T>();
-            }
-            let pos = pos.unwrap().f64();
-            let open = open.unwrap().f64();
-            let close = close.unwrap().f64();
-            let chg = chg.unwrap();
-            if last_close.is_none() {
-                last_close = Some(open)
-            }
-            if (last_lot_num != 0.) && (!chg) {
-                // do not calculate the profit and loss of the jump open when there is a contract change
-                cash +=
-                    last_lot_num * (open - last_close.unwrap()) * multiplier * last_pos.signum();
-            }
-            // we use pos to determine the position change, so leverage must be a constant
-            if (pos != last_pos) || chg {
-                // the position has changed, calculate the new theoretical number of lots
-                let l = ((cash * leverage * pos.abs()) / (multiplier * open)).floor();
-                let (lot_num, lot_num_change) = if !chg {
-                    (
-                        l,
-                        (l * pos.signum() - last_lot_num * last_pos.signum()).abs(),
-                    )
-                } else {
-                    (l, l.abs() * 2.)
-                };
-                // addup the commision fee
-                if let CommissionType::Percent = commission_type {
-                    cash -= lot_num_change * multiplier * (open * c_rate + slippage);
-                } else {
-                    cash -= lot_num_change * (c_rate + multiplier * slippage);
-                };
-                // update last lot num and last pos
-                last_lot_num = lot_num;
-                last_pos = pos;
-            }
-            // calculate the profit and loss of the current period
-            if last_lot_num != 0. {
-                cash += last_lot_num * last_pos.signum() * (close - open) * multiplier;
-            }
-            last_close = Some(close); // update last close
-            cash.into_cast::<T>()
-        })
-        .collect_trusted_vec1()
-    } else {
-        // ignore contract chg signal
-        // this should be faster than the above
-        izip!(pos_vec.titer(), open_vec.titer(), close_vec.titer(),)
-            .map(|(pos, open, close)| {
-                if pos.is_none() || open.is_none() || close.is_none() {
-                    return cash.into_cast::<T>();
-                } else if blowup && cash <= 0. {
-                    return 0_f64.into_cast::<T>();
-                }
-                let pos = pos.unwrap().f64();
-                let open = open.unwrap().f64();
-                let close = close.unwrap().f64();
-                if last_close.is_none() {
-                    last_close = Some(open)
-                }
-                if last_lot_num != 0. {
-                    cash += last_lot_num
-                        * (open - last_close.unwrap())
-                        * multiplier
-                        * last_pos.signum();
-                }
-                // we use pos to determine the position change, so leverage must be a constant
-                if pos != last_pos {
-                    // the position has changed, calculate the new theoretical number of lots
-                    let l = ((cash * leverage * pos.abs()) / (multiplier * open)).floor();
-                    let (lot_num, lot_num_change) = (
-                        l,
-                        (l * pos.signum() - last_lot_num * last_pos.signum()).abs(),
-                    );
+    run_future_ret(
+        pos_vec,
+        open_vec,
+        close_vec,
+        contract_chg_signal_vec,
+        kwargs,
+        &mut (),
+    )
+    .into_iter()
+    .map(|(equity, _)| equity.into_cast::<T>())
+    .collect_trusted_vec1()
+}
+
+/// The same as [`calc_future_ret`], but also return the commission fee
+/// (including slippage) charged at each bar.
+pub fn calc_future_ret_with_fee<T, V, VMask>(
+    pos_vec: &V,
+    open_vec: &V,
+    close_vec: &V,
+    contract_chg_signal_vec: Option<VMask>,
+    kwargs: &FutureRetKwargs,
+) -> (Vec<f64>, Vec<f64>)
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+{
+    run_future_ret(
+        pos_vec,
+        open_vec,
+        close_vec,
+        contract_chg_signal_vec,
+        kwargs,
+        &mut (),
+    )
+    .into_iter()
+    .unzip()
+}
+
+/// The same as [`calc_future_ret`], but return a [`BacktestResult`].
+pub fn calc_future_ret_result<T, V, VMask>(
+    pos_vec: &V,
+    open_vec: &V,
+    close_vec: &V,
+    contract_chg_signal_vec: Option<VMask>,
+    kwargs: &FutureRetKwargs,
+) -> BacktestResult
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+{
+    let mut rec = ResultBuilder::new(pos_vec.len(), kwargs.multiplier);
+    run_future_ret(
+        pos_vec,
+        open_vec,
+        close_vec,
+        contract_chg_signal_vec,
+        kwargs,
+        &mut rec,
+    );
+    rec.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                    // addup the commision fee
-                    if let CommissionType::Percent = commission_type {
-                        cash -= lot_num_change * multiplier * (open * c_rate + slippage);
-                    } else {
-                        cash -= lot_num_change * (c_rate + multiplier * slippage);
-                    };
-                    // update last lot num and last pos
-                    last_lot_num = lot_num;
-                    last_pos = pos;
-                }
-                // calculate the profit and loss of the current period
-                if last_lot_num != 0. {
-                    cash += last_lot_num * last_pos.signum() * (close - open) * multiplier;
-                }
-                last_close = Some(close); // update last close
-                cash.into_cast::<T>()
-            })
-            .collect_trusted_vec1()
+    #[test]
+    fn test_future_ret_with_fee() {
+        let kwargs = FutureRetKwargs {
+            init_cash: 10000,
+            multiplier: 1.,
+            leverage: 1.,
+            slippage: 0.,
+            c_rate: 0.001,
+            blowup: false,
+            commission_type: CommissionType::Percent,
+        };
+        let pos = vec![0., 1., 1., -1., 0.];
+        let open = vec![10., 10., 11., 12., 11.];
+        let close = vec![10., 11., 12., 11., 10.];
+        let equity: Vec<f64> = calc_future_ret(&pos, &open, &close, None::<Vec<_>>, &kwargs);
+        let (equity2, fee) = calc_future_ret_with_fee(&pos, &open, &close, None::<Vec<_>>, &kwargs);
+        assert_eq!(equity, equity2);
+        assert_eq!(fee[0], 0.);
+        assert!((fee[1] - 1000. * 10. * 0.001).abs() < 1e-10);
+        assert_eq!(fee[2], 0.);
+        assert!(fee[3] > 0. && fee[4] > 0.);
     }
 }
@@ -1,17 +1,40 @@
+mod engine;
 mod future_ret;
 mod future_ret_spread;
+mod option_ret;
+mod pairs_future_ret;
+mod perp_ret;
+mod result;
+mod stock_ret;
 mod tick_future_ret;
 mod tick_future_ret_full;
 
-pub use future_ret::{calc_future_ret, FutureRetKwargs};
-pub use future_ret_spread::{calc_future_ret_with_spread, FutureRetSpreadKwargs};
-use serde::{Deserialize, Deserializer};
+pub use future_ret::{
+    calc_future_ret, calc_future_ret_result, calc_future_ret_with_fee, FutureRetKwargs,
+};
+pub use future_ret_spread::{
+    calc_future_ret_with_spread, calc_future_ret_with_spread_result, FutureRetSpreadKwargs,
+};
+pub use option_ret::{calc_option_ret, OptionLeg, OptionRetKwargs, OptionRetResult};
+pub use pairs_future_ret::{
+    calc_pairs_future_ret, calc_pairs_future_ret_result, PairsFutureRetKwargs,
+};
+#[cfg(feature = "time")]
+pub use perp_ret::perp_funding_mask;
+pub use perp_ret::{
+    calc_perp_ret, calc_perp_ret_result, perp_liquidation_price, ContractType, MarginMode,
+    PerpRetKwargs,
+};
+pub use result::{BacktestResult, Fill};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+pub use stock_ret::{calc_stock_ret, calc_stock_ret_result, StockRetKwargs};
 use tevec::prelude::{tbail, TResult};
-pub use tick_future_ret::{calc_tick_future_ret, TickFutureRetKwargs};
+pub use tick_future_ret::{calc_tick_future_ret, calc_tick_future_ret_result, TickFutureRetKwargs};
 #[cfg(feature = "polars")]
 pub use tick_future_ret_full::profit_vec_to_series;
 pub use tick_future_ret_full::{
-    calc_tick_future_ret_full, OpenPriceMethod, Profit, TickFutureRetFullKwargs,
+    calc_tick_future_ret_full, calc_tick_future_ret_full_result, OpenPriceMethod, Profit,
+    TickFutureRetFullKwargs,
 };
 #[derive(Clone, Copy)]
 pub enum CommissionType {
@@ -28,6 +51,14 @@ impl CommissionType {
             _ => tbail!("invalid commission type"),
         }
     }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommissionType::Percent => "percent",
+            CommissionType::Absolute => "absolute",
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for CommissionType {
@@ -41,6 +72,16 @@ impl<'de> Deserialize<'de> for CommissionType {
     }
 }
 
+impl Serialize for CommissionType {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 impl<A: AsRef<str>> From<A> for CommissionType {
     #[inline]
     fn from(s: A) -> Self {
@@ -63,6 +104,14 @@ impl SignalType {
             _ => tbail!("invalid signal type"),
         }
     }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignalType::Percent => "percent",
+            SignalType::Absolute => "absolute",
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for SignalType {
@@ -75,3 +124,13 @@ impl<'de> Deserialize<'de> for SignalType {
         SignalType::parse(s.as_str()).map_err(serde::de::Error::custom)
     }
 }
+
+impl Serialize for SignalType {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
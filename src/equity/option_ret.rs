@@ -0,0 +1,376 @@
+use serde::{Deserialize, Serialize};
+use tevec::prelude::*;
+
+use super::{
+    calc_future_ret_result,
+    engine::{Commission, FeeModel, Slippage},
+    CommissionType, FutureRetKwargs,
+};
+use crate::options::{black76_greeks, black76_implied_vol, Greeks, OptionType};
+
+/// Option lots held on a strike and expiry of the underlying future.
+pub struct OptionLeg<'a, V> {
+    pub option_type: OptionType,
+    pub strike: f64,
+    /// expiry in years, on the same axis as `time_vec`
+    pub expiry: f64,
+    /// signed lots held after the close of each bar, a none keeps the lots
+    pub pos: &'a V,
+    /// market price of each bar, the option is marked to model if the price
+    /// is not given or none
+    pub price: Option<&'a V>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OptionRetKwargs {
+    /// kwargs of the futures position, `init_cash` is the cash of the account,
+    /// `blowup` only applies to the equity of the futures position
+    pub future: FutureRetKwargs,
+    /// multiplier of an option lot
+    pub multiplier: f64,
+    /// continuously compounded discount rate of the option price
+    pub rate: f64,
+    /// slippage of an option fill in price
+    pub slippage: f64,
+    pub c_rate: f64,
+    pub commission_type: CommissionType,
+}
+
+/// Output of [`calc_option_ret`], the greeks are the sum of the options held
+/// at the end of each bar in money, the pnl is the profit of each bar.
+///
+/// `hedged_pnl == future_pnl + delta_pnl + gamma_pnl + vega_pnl + theta_pnl
+/// + residual_pnl - option_cost`, and it sums up to the change of the equity.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OptionRetResult {
+    pub equity: Vec<f64>,
+    /// mark value of the options held
+    pub option_value: Vec<f64>,
+    /// net delta of the futures and options in future lots
+    pub delta: Vec<f64>,
+    pub gamma: Vec<f64>,
+    pub vega: Vec<f64>,
+    pub theta: Vec<f64>,
+    /// profit of the futures position, costs included
+    pub future_pnl: Vec<f64>,
+    pub delta_pnl: Vec<f64>,
+    pub gamma_pnl: Vec<f64>,
+    pub vega_pnl: Vec<f64>,
+    pub theta_pnl: Vec<f64>,
+    /// option profit not explained by the greeks
+    pub residual_pnl: Vec<f64>,
+    /// commission and slippage of the option fills
+    pub option_cost: Vec<f64>,
+    pub hedged_pnl: Vec<f64>,
+}
+
+/// State of an option leg at its last valid mark.
+struct LegState {
+    lots: f64,
+    mark: f64,
+    greeks: Greeks,
+    forward: f64,
+    time: f64,
+    vol: f64,
+    expired: bool,
+}
+
+impl Default for LegState {
+    #[inline]
+    fn default() -> Self {
+        LegState {
+            lots: 0.,
+            mark: f64::NAN,
+            greeks: Greeks::default(),
+            forward: f64::NAN,
+            time: f64::NAN,
+            vol: f64::NAN,
+            expired: false,
+        }
+    }
+}
+
+#[inline]
+fn to_f64_vec<T, V>(vec: &V) -> Vec<f64>
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+{
+    vec.titer()
+        .map(|v| v.to_opt().map_or(f64::NAN, |v| v.f64()))
+        .collect()
+}
+
+/// Calculate the equity of a futures position hedged (or overlaid) by options
+/// on the same future.
+///
+/// The futures position is the same as [`calc_future_ret`](super::calc_future_ret).
+/// Options are traded at the mark of the close, and marked to the market
+/// price if given, otherwise to the Black-76 price of the close with
+/// `vol_vec`. `time_vec` is the time of each bar in years, an option is
+/// settled at the intrinsic value once the time reaches its expiry. The option
+/// profit of each bar is attributed to the greeks of the last mark.
+///
+/// The option legs have no margin: premiums are paid from (or added to) the
+/// cash without a check, so a short option is never margin called and the
+/// equity can be negative.
+pub fn calc_option_ret<T, V>(
+    pos_vec: &V,
+    open_vec: &V,
+    close_vec: &V,
+    time_vec: &V,
+    vol_vec: &V,
+    legs: &[OptionLeg<V>],
+    kwargs: &OptionRetKwargs,
+) -> OptionRetResult
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+{
+    let future = calc_future_ret_result(
+        pos_vec,
+        open_vec,
+        close_vec,
+        None::<Vec<Option<bool>>>,
+        &kwargs.future,
+    );
+    let len = future.len();
+    let m = kwargs.multiplier;
+    let fee = Commission::new(kwargs.commission_type, kwargs.c_rate, m);
+    let (close, time, vol) = (
+        to_f64_vec(close_vec),
+        to_f64_vec(time_vec),
+        to_f64_vec(vol_vec),
+    );
+    let leg_pos: Vec<_> = legs.iter().map(|leg| to_f64_vec(leg.pos)).collect();
+    let leg_price: Vec<_> = legs
+        .iter()
+        .map(|leg| leg.price.map(|p| to_f64_vec(p)))
+        .collect();
+    let mut states: Vec<LegState> = legs.iter().map(|_| LegState::default()).collect();
+    let mut res = OptionRetResult::default();
+    let mut cash = 0.;
+    let mut last_future = kwargs.future.init_cash as f64;
+    for i in 0..len {
+        let (forward, t) = (close[i], time[i]);
+        let (mut value, mut cost) = (0., 0.);
+        let mut greeks = Greeks::default();
+        let mut pnl = Greeks::default();
+        let mut residual = 0.;
+        for (j, (leg, state)) in legs.iter().zip(states.iter_mut()).enumerate() {
+            if state.expired {
+                continue;
+            }
+            let tte = leg.expiry - t;
+            let price = leg_price[j].as_ref().map_or(f64::NAN, |p| p[i]);
+            let leg_vol = if tte > 0. && price.not_none() {
+                let iv = black76_implied_vol(
+                    leg.option_type,
+                    price,
+                    forward,
+                    leg.strike,
+                    tte,
+                    kwargs.rate,
+                );
+                if iv.not_none() {
+                    iv
+                } else {
+                    vol[i]
+                }
+            } else {
+                vol[i]
+            };
+            let g = black76_greeks(
+                leg.option_type,
+                forward,
+                leg.strike,
+                tte,
+                kwargs.rate,
+                leg_vol,
+            );
+            let mark = if tte <= 0. {
+                g.price
+            } else if price.not_none() {
+                price
+            } else if leg_vol.not_none() {
+                g.price
+            } else {
+                f64::NAN
+            };
+            // a leg without a valid mark keeps the last mark
+            if forward.not_none() && t.not_none() && mark.not_none() {
+                if state.lots != 0. {
+                    let size = state.lots * m;
+                    let last = &state.greeks;
+                    let df = forward - state.forward;
+                    let dvol = if leg_vol.not_none() && state.vol.not_none() {
+                        leg_vol - state.vol
+                    } else {
+                        0.
+                    };
+                    let leg_pnl = [
+                        size * last.delta * df,
+                        size * 0.5 * last.gamma * df * df,
+                        size * last.vega * dvol,
+                        size * last.theta * (t - state.time),
+                    ];
+                    pnl.delta += leg_pnl[0];
+                    pnl.gamma += leg_pnl[1];
+                    pnl.vega += leg_pnl[2];
+                    pnl.theta += leg_pnl[3];
+                    residual += leg_pnl
+                        .iter()
+                        .fold(size * (mark - state.mark), |acc, p| acc - p);
+                }
+                state.mark = mark;
+                state.greeks = g;
+                state.forward = forward;
+                state.time = t;
+                state.vol = leg_vol;
+                if tte <= 0. {
+                    // cash settled at the intrinsic value
+                    cash += state.lots * m * mark;
+                    state.lots = 0.;
+                    state.expired = true;
+                    continue;
+                }
+                let target = leg_pos[j][i];
+                if target.not_none() && target != state.lots {
+                    let change = target - state.lots;
+                    let c = fee.charge(
+                        change.abs(),
+                        mark,
+                        Slippage::Price(kwargs.slippage),
+                        &mut (),
+                    );
+                    cash -= change * m * mark + c;
+                    cost += c;
+                    state.lots = target;
+                }
+            }
+            if state.lots != 0. {
+                let size = state.lots * m;
+                value += size * state.mark;
+                greeks.delta += size * state.greeks.delta;
+                greeks.gamma += size * state.greeks.gamma;
+                greeks.vega += size * state.greeks.vega;
+                greeks.theta += size * state.greeks.theta;
+            }
+        }
+        let future_pnl = future.equity[i] - last_future;
+        last_future = future.equity[i];
+        res.equity.push(future.equity[i] + cash + value);
+        res.option_value.push(value);
+        res.delta
+            .push(future.lots[i] + greeks.delta / kwargs.future.multiplier);
+        res.gamma.push(greeks.gamma);
+        res.vega.push(greeks.vega);
+        res.theta.push(greeks.theta);
+        res.future_pnl.push(future_pnl);
+        res.delta_pnl.push(pnl.delta);
+        res.gamma_pnl.push(pnl.gamma);
+        res.vega_pnl.push(pnl.vega);
+        res.theta_pnl.push(pnl.theta);
+        res.residual_pnl.push(residual);
+        res.option_cost.push(cost);
+        res.hedged_pnl
+            .push(future_pnl + pnl.delta + pnl.gamma + pnl.vega + pnl.theta + residual - cost);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{equity::calc_future_ret, options::black76_price};
+
+    fn kwargs() -> OptionRetKwargs {
+        OptionRetKwargs {
+            future: FutureRetKwargs {
+                init_cash: 100000,
+                multiplier: 10.,
+                leverage: 1.,
+                slippage: 0.,
+                c_rate: 0.,
+                blowup: false,
+                commission_type: CommissionType::Percent,
+            },
+            multiplier: 10.,
+            rate: 0.02,
+            slippage: 0.1,
+            c_rate: 0.,
+            commission_type: CommissionType::Percent,
+        }
+    }
+
+    fn check_identity(res: &OptionRetResult, init_cash: f64) {
+        let mut equity = init_cash;
+        for i in 0..res.equity.len() {
+            equity += res.hedged_pnl[i];
+            assert!((res.equity[i] - equity).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_option_ret_model() {
+        let kwargs = kwargs();
+        let pos = vec![0., -0.05, -0.05, -0.05, -0.05, 0.];
+        let open = vec![100., 100., 101., 100.5, 102., 103.];
+        let close = vec![100., 101., 100.5, 102., 103., 104.];
+        let time = vec![0., 0.01, 0.02, 0.03, 0.04, 0.05];
+        let vol = vec![0.2, 0.2, 0.21, 0.2, 0.19, 0.2];
+        let call_pos = vec![10., 10., 10., 10., 10., 10.];
+        let legs = [OptionLeg {
+            option_type: OptionType::Call,
+            strike: 100.,
+            expiry: 0.05,
+            pos: &call_pos,
+            price: None,
+        }];
+        let res = calc_option_ret(&pos, &open, &close, &time, &vol, &legs, &kwargs);
+        let future: Vec<f64> = calc_future_ret(&pos, &open, &close, None::<Vec<_>>, &kwargs.future);
+        let call = black76_price(OptionType::Call, 100., 100., 0.05, 0.02, 0.2);
+        assert!((res.option_value[0] - 100. * call).abs() < 1e-10);
+        assert!((res.option_cost[0] - 100. * 0.1).abs() < 1e-10);
+        // the option is settled at expiry
+        assert_eq!(res.option_value[5], 0.);
+        assert_eq!(res.delta[5], 0.);
+        check_identity(&res, 100000.);
+        for i in 1..6 {
+            assert!((res.future_pnl[i] - (future[i] - future[i - 1])).abs() < 1e-10);
+            let explained =
+                res.delta_pnl[i] + res.gamma_pnl[i] + res.vega_pnl[i] + res.theta_pnl[i];
+            assert!(res.residual_pnl[i].abs() < explained.abs().max(10.));
+        }
+        assert!(res.vega_pnl[2] > 0. && res.vega_pnl[4] < 0.);
+        // the short future hedges the delta of the call
+        assert!(res.delta[1].abs() < 100. * 0.6 / 10.);
+    }
+
+    #[test]
+    fn test_option_ret_market() {
+        let kwargs = kwargs();
+        let pos = vec![0.; 4];
+        let close = vec![100., 100., 100., 100.];
+        let time = vec![0., 0.01, 0.02, 0.03];
+        let vol = vec![f64::NAN; 4];
+        let put_pos = vec![-1., -1., f64::NAN, 0.];
+        let put_price = vec![3., 3.5, f64::NAN, 3.2];
+        let legs = [OptionLeg {
+            option_type: OptionType::Put,
+            strike: 100.,
+            expiry: 0.5,
+            pos: &put_pos,
+            price: Some(&put_price),
+        }];
+        let res = calc_option_ret(&pos, &close, &close, &time, &vol, &legs, &kwargs);
+        assert_eq!(res.option_value, vec![-30., -35., -35., 0.]);
+        // the implied volatility rises and the short put loses on vega
+        assert!(res.vega_pnl[1] < 0.);
+        assert_eq!(res.hedged_pnl[2], 0.);
+        assert!((res.hedged_pnl[3] - (3.5 - 3.2 - 0.1) * 10.).abs() < 1e-10);
+        check_identity(&res, 100000.);
+    }
+}
@@ -0,0 +1,219 @@
+use itertools::izip;
+use serde::{Deserialize, Serialize};
+use tevec::prelude::*;
+
+use super::{
+    engine::{Bar, BarPosition, Blowup, Commission, FixedSlippage, LeverageSizer},
+    result::{Recorder, ResultBuilder},
+    BacktestResult, CommissionType,
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct PairsFutureRetKwargs {
+    pub init_cash: usize,
+    /// multiplier of leg a and leg b
+    pub multiplier: (f64, f64),
+    pub leverage: f64,
+    /// slippage of leg a and leg b
+    pub slippage: (f64, f64),
+    pub c_rate: f64,
+    pub blowup: bool,
+    pub commission_type: CommissionType,
+}
+
+/// run the engine and return the equity of each bar
+#[allow(clippy::too_many_arguments)]
+fn run_pairs_future_ret<T, V, R>(
+    pos_a_vec: &V,
+    pos_b_vec: &V,
+    open_a_vec: &V,
+    close_a_vec: &V,
+    open_b_vec: &V,
+    close_b_vec: &V,
+    kwargs: &PairsFutureRetKwargs,
+    rec_a: &mut R,
+    rec_b: &mut R,
+) -> Vec<f64>
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    R: Recorder,
+{
+    let mut cash = kwargs.init_cash as f64;
+    let blowup = Blowup::new(kwargs.blowup);
+    let (multiplier_a, multiplier_b) = kwargs.multiplier;
+    let (slippage_a, slippage_b) = kwargs.slippage;
+    let (fill_a, fill_b) = (FixedSlippage(slippage_a), FixedSlippage(slippage_b));
+    let fee_a = Commission::new(kwargs.commission_type, kwargs.c_rate, multiplier_a);
+    let fee_b = Commission::new(kwargs.commission_type, kwargs.c_rate, multiplier_b);
+    let sizer_a = LeverageSizer::new(kwargs.leverage, multiplier_a);
+    let sizer_b = LeverageSizer::new(kwargs.leverage, multiplier_b);
+    let mut leg_a = BarPosition::new();
+    let mut leg_b = BarPosition::new();
+    izip!(
+        pos_a_vec.titer(),
+        pos_b_vec.titer(),
+        open_a_vec.titer(),
+        close_a_vec.titer(),
+        open_b_vec.titer(),
+        close_b_vec.titer(),
+    )
+    .map(|(pos_a, pos_b, open_a, close_a, open_b, close_b)| {
+        let (bar_a, bar_b) = match (
+            Bar::new(pos_a, open_a, close_a),
+            Bar::new(pos_b, open_b, close_b),
+        ) {
+            (Some(bar_a), Some(bar_b)) => (bar_a, bar_b),
+            _ => {
+                rec_a.finish(cash, f64::NAN);
+                rec_b.finish(cash, f64::NAN);
+                return cash;
+            },
+        };
+        if blowup.check(cash) {
+            rec_a.finish(0., f64::NAN);
+            rec_b.finish(0., f64::NAN);
+            return 0.;
+        }
+        leg_a.settle_open(&mut cash, bar_a.open, multiplier_a, false);
+        leg_b.settle_open(&mut cash, bar_b.open, multiplier_b, false);
+        // both legs are sized by the cash before commission
+        let sizing_cash = cash;
+        cash -= leg_a.rebalance(&bar_a, sizing_cash, false, &fill_a, &fee_a, &sizer_a, rec_a);
+        cash -= leg_b.rebalance(&bar_b, sizing_cash, false, &fill_b, &fee_b, &sizer_b, rec_b);
+        leg_a.settle_close(&mut cash, bar_a.open, bar_a.close, multiplier_a);
+        leg_b.settle_close(&mut cash, bar_b.open, bar_b.close, multiplier_b);
+        rec_a.finish(cash, bar_a.close);
+        rec_b.finish(cash, bar_b.close);
+        cash
+    })
+    .collect()
+}
+
+/// Calculate the equity of a two leg spread position with a single cash
+/// account, each leg is sized and charged in the same way as `calc_future_ret`.
+#[allow(clippy::too_many_arguments)]
+pub fn calc_pairs_future_ret<O, T, V>(
+    pos_a_vec: &V,
+    pos_b_vec: &V,
+    open_a_vec: &V,
+    close_a_vec: &V,
+    open_b_vec: &V,
+    close_b_vec: &V,
+    kwargs: &PairsFutureRetKwargs,
+) -> O
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    O: Vec1<T::Cast<f64>>,
+{
+    if pos_a_vec.is_empty() {
+        return O::empty();
+    }
+    run_pairs_future_ret(
+        pos_a_vec,
+        pos_b_vec,
+        open_a_vec,
+        close_a_vec,
+        open_b_vec,
+        close_b_vec,
+        kwargs,
+        &mut (),
+        &mut (),
+    )
+    .into_iter()
+    .map(|v| v.into_cast::<T>())
+    .collect_trusted_vec1()
+}
+
+/// The same as [`calc_pairs_future_ret`], but return a [`BacktestResult`] of
+/// each leg. The equity of both results is the equity of the whole account.
+#[allow(clippy::too_many_arguments)]
+pub fn calc_pairs_future_ret_result<T, V>(
+    pos_a_vec: &V,
+    pos_b_vec: &V,
+    open_a_vec: &V,
+    close_a_vec: &V,
+    open_b_vec: &V,
+    close_b_vec: &V,
+    kwargs: &PairsFutureRetKwargs,
+) -> (BacktestResult, BacktestResult)
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+{
+    let len = pos_a_vec.len();
+    let mut rec_a = ResultBuilder::new(len, kwargs.multiplier.0);
+    let mut rec_b = ResultBuilder::new(len, kwargs.multiplier.1);
+    run_pairs_future_ret(
+        pos_a_vec,
+        pos_b_vec,
+        open_a_vec,
+        close_a_vec,
+        open_b_vec,
+        close_b_vec,
+        kwargs,
+        &mut rec_a,
+        &mut rec_b,
+    );
+    (rec_a.build(), rec_b.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use tevec::core::testing::assert_vec1d_equal_numeric;
+
+    use super::*;
+    use crate::equity::{calc_future_ret, FutureRetKwargs};
+
+    #[test]
+    fn test_pairs_future_ret() {
+        let pos_a = vec![0., 1., 1., 0.5, 0.5, -1., -1., 0.];
+        let pos_b = vec![0.; 8];
+        let open_a = vec![10., 10.5, 11., 10.8, 11.2, 11.5, 11., 10.6];
+        let close_a = vec![10.2, 10.9, 10.7, 11.1, 11.6, 11.2, 10.7, 10.5];
+        let open_b = vec![5.; 8];
+        let close_b = vec![5.5; 8];
+        let kwargs = PairsFutureRetKwargs {
+            init_cash: 10000,
+            multiplier: (10., 5.),
+            leverage: 1.,
+            slippage: (0.1, 0.),
+            c_rate: 0.0003,
+            blowup: false,
+            commission_type: CommissionType::Percent,
+        };
+        let res: Vec<f64> = calc_pairs_future_ret(
+            &pos_a, &pos_b, &open_a, &close_a, &open_b, &close_b, &kwargs,
+        );
+        // a spread with empty leg b should be the same as a single leg
+        let single_kwargs = FutureRetKwargs {
+            init_cash: 10000,
+            multiplier: 10.,
+            leverage: 1.,
+            slippage: 0.1,
+            c_rate: 0.0003,
+            blowup: false,
+            commission_type: CommissionType::Percent,
+        };
+        let expect: Vec<f64> = calc_future_ret(
+            &pos_a,
+            &open_a,
+            &close_a,
+            None::<Vec<Option<bool>>>,
+            &single_kwargs,
+        );
+        assert_vec1d_equal_numeric(&res, &expect, Some(1e-7));
+        // hedged leg b reduces the profit of leg a
+        let pos_b = pos_a.iter().map(|p| -p).collect::<Vec<_>>();
+        let close_b = close_a.iter().map(|c| c / 2.).collect::<Vec<_>>();
+        let open_b = open_a.iter().map(|o| o / 2.).collect::<Vec<_>>();
+        let hedged: Vec<f64> = calc_pairs_future_ret(
+            &pos_a, &pos_b, &open_a, &close_a, &open_b, &close_b, &kwargs,
+        );
+        assert!((hedged[7] - 10000.).abs() < (expect[7] - 10000.).abs());
+    }
+}
@@ -0,0 +1,740 @@
+use itertools::izip;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tevec::prelude::*;
+
+use super::{
+    engine::{
+        run_engine, BidAsk, Blowup, Commission, Engine, FeeModel, FillModel, FixedLots,
+        LeverageSizer, Passive, Quote, Sizer, Slippage,
+    },
+    result::{Recorder, ResultBuilder},
+    BacktestResult, CommissionType, SignalType,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContractType {
+    /// margined and settled in the quote currency (USDT-margined)
+    Linear,
+    /// margined and settled in the coin (coin-margined)
+    Inverse,
+}
+
+impl ContractType {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "linear" | "usdt" => Ok(ContractType::Linear),
+            "inverse" | "coin" => Ok(ContractType::Inverse),
+            _ => tbail!("invalid contract type"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContractType::Linear => "linear",
+            ContractType::Inverse => "inverse",
+        }
+    }
+
+    /// the profit of a contract is linear in the value of the price
+    #[inline]
+    fn value(&self, price: f64) -> f64 {
+        match self {
+            ContractType::Linear => price,
+            ContractType::Inverse => -1. / price,
+        }
+    }
+
+    /// inverse of [`ContractType::value`]
+    #[inline]
+    fn price(&self, value: f64) -> f64 {
+        match self {
+            ContractType::Linear => value,
+            ContractType::Inverse => -1. / value,
+        }
+    }
+
+    /// notional of a unit contract size in the margin currency
+    #[inline]
+    fn unit(&self, price: f64) -> f64 {
+        match self {
+            ContractType::Linear => price,
+            ContractType::Inverse => 1. / price,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ContractType {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        ContractType::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for ContractType {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarginMode {
+    /// the whole wallet balance is the margin of the position
+    Cross,
+    /// only the margin put in the position can be lost
+    Isolated,
+}
+
+impl MarginMode {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "cross" => Ok(MarginMode::Cross),
+            "isolated" => Ok(MarginMode::Isolated),
+            _ => tbail!("invalid margin mode"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarginMode::Cross => "cross",
+            MarginMode::Isolated => "isolated",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MarginMode {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        MarginMode::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for MarginMode {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PerpRetKwargs {
+    /// initial wallet balance in the margin currency, the coin for inverse
+    /// contracts
+    pub init_cash: f64,
+    /// coins of a linear contract, or quote currency of an inverse contract
+    pub contract_size: f64,
+    pub contract_type: ContractType,
+    pub margin_mode: MarginMode,
+    /// leverage of the initial margin in isolated margin mode, it does not
+    /// size the position of a percent signal
+    pub leverage: f64,
+    /// maintenance margin rate of the notional
+    pub maintenance_margin: f64,
+    pub maker_fee: f64,
+    pub taker_fee: f64,
+    /// post orders at the best price as a maker instead of taking the quote,
+    /// liquidations are always charged as a taker
+    pub maker: bool,
+    pub blowup: bool,
+    /// a percent signal is the notional as a fraction of equity at 1x, e.g.
+    /// a signal of 3 holds 3 times the equity
+    pub signal_type: SignalType,
+}
+
+impl Default for PerpRetKwargs {
+    #[inline]
+    fn default() -> Self {
+        PerpRetKwargs {
+            init_cash: 10000.,
+            contract_size: 1.,
+            contract_type: ContractType::Linear,
+            margin_mode: MarginMode::Cross,
+            leverage: 10.,
+            maintenance_margin: 0.005,
+            maker_fee: 0.0002,
+            taker_fee: 0.0005,
+            maker: false,
+            blowup: false,
+            signal_type: SignalType::Percent,
+        }
+    }
+}
+
+impl PerpRetKwargs {
+    /// Engine of [`calc_perp_ret`].
+    ///
+    /// A percent signal is sized by the equity at 1x regardless of
+    /// `leverage`, an absolute signal is the number of contracts.
+    #[inline]
+    fn engine(&self) -> PerpEngine<Box<dyn FillModel<Quote>>, Commission, Box<dyn Sizer>> {
+        let (fill, fee_rate): (Box<dyn FillModel<Quote>>, _) = if self.maker {
+            (Box::new(Passive), self.maker_fee)
+        } else {
+            (Box::new(BidAsk), self.taker_fee)
+        };
+        let sizer: Box<dyn Sizer> = match self.signal_type {
+            SignalType::Percent => Box::new(LeverageSizer::new(1., self.contract_size)),
+            SignalType::Absolute => Box::new(FixedLots),
+        };
+        let fee = |rate| Commission::new(CommissionType::Percent, rate, self.contract_size);
+        PerpEngine {
+            fill,
+            fee: fee(fee_rate),
+            liquidation_fee: fee(self.taker_fee),
+            sizer,
+            contract_type: self.contract_type,
+            contract_size: self.contract_size,
+            margin_mode: self.margin_mode,
+            leverage: self.leverage,
+            maintenance_margin: self.maintenance_margin,
+            blowup: Blowup::new(self.blowup),
+            wallet: self.init_cash,
+            lots: 0.,
+            entry: f64::NAN,
+            margin: 0.,
+            signal: 0.,
+            last_value: f64::NAN,
+            liquidation: Vec::new(),
+        }
+    }
+}
+
+/// A tick of a perpetual swap.
+struct PerpQuote {
+    quote: Quote,
+    /// funding rate paid at this tick
+    funding_rate: Option<f64>,
+}
+
+/// Liquidation price of `q` coins (or quote currency for inverse contracts)
+/// opened at `entry` value, `NaN` if the position can not be liquidated.
+fn liquidation_price(
+    contract_type: ContractType,
+    q: f64,
+    entry: f64,
+    collateral: f64,
+    maintenance_margin: f64,
+) -> f64 {
+    if q == 0. {
+        return f64::NAN;
+    }
+    // collateral + q * (value(p) - entry) == |q| * unit(p) * maintenance_margin
+    let price = match contract_type {
+        ContractType::Linear => (q * entry - collateral) / (q - q.abs() * maintenance_margin),
+        ContractType::Inverse => (q + q.abs() * maintenance_margin) / (collateral - q * entry),
+    };
+    if price.is_finite() && price > 0. {
+        price
+    } else {
+        f64::NAN
+    }
+}
+
+/// Liquidation price of signed `lots` contracts opened at `entry_price`,
+/// `collateral` is the wallet balance in cross margin mode or the margin of
+/// the position in isolated margin mode. Return `NaN` if the position can not
+/// be liquidated.
+pub fn perp_liquidation_price(
+    lots: f64,
+    entry_price: f64,
+    collateral: f64,
+    kwargs: &PerpRetKwargs,
+) -> f64 {
+    liquidation_price(
+        kwargs.contract_type,
+        lots * kwargs.contract_size,
+        kwargs.contract_type.value(entry_price),
+        collateral,
+        kwargs.maintenance_margin,
+    )
+}
+
+/// A perpetual swap account marked to the mid price of each tick.
+///
+/// Prices are converted to values (`-1 / price` for inverse contracts) so
+/// that the profit of a contract is `contract_size * (value - entry)`.
+struct PerpEngine<F, E, S> {
+    fill: F,
+    fee: E,
+    liquidation_fee: E,
+    sizer: S,
+    contract_type: ContractType,
+    contract_size: f64,
+    margin_mode: MarginMode,
+    leverage: f64,
+    maintenance_margin: f64,
+    blowup: Blowup,
+    /// balance with realized profit, costs and funding
+    wallet: f64,
+    lots: f64,
+    /// average entry value of the lots held
+    entry: f64,
+    /// margin of the position in isolated margin mode
+    margin: f64,
+    signal: f64,
+    last_value: f64,
+    /// liquidation price at the end of each tick
+    liquidation: Vec<f64>,
+}
+
+impl<F, E, S> PerpEngine<F, E, S> {
+    #[inline]
+    fn equity_at(&self, value: f64) -> f64 {
+        if self.lots != 0. {
+            self.wallet + self.lots * self.contract_size * (value - self.entry)
+        } else {
+            self.wallet
+        }
+    }
+
+    #[inline]
+    fn liquidation_price(&self) -> f64 {
+        let collateral = match self.margin_mode {
+            MarginMode::Cross => self.wallet,
+            MarginMode::Isolated => self.margin,
+        };
+        liquidation_price(
+            self.contract_type,
+            self.lots * self.contract_size,
+            self.entry,
+            collateral,
+            self.maintenance_margin,
+        )
+    }
+
+    /// Trade `change` lots at `value`, the profit of the closed lots is
+    /// realized to the wallet.
+    fn trade(&mut self, change: f64, value: f64, unit: f64) {
+        let cs = self.contract_size;
+        let lots = self.lots + change;
+        if self.lots == 0. || self.lots.signum() == change.signum() {
+            self.entry = if self.lots == 0. {
+                value
+            } else {
+                (self.entry * self.lots.abs() + value * change.abs()) / lots.abs()
+            };
+            self.margin += change.abs() * cs * unit / self.leverage;
+        } else {
+            let closed = change.abs().min(self.lots.abs());
+            self.wallet += closed * self.lots.signum() * cs * (value - self.entry);
+            if lots == 0. {
+                self.entry = f64::NAN;
+                self.margin = 0.;
+            } else if lots.signum() == self.lots.signum() {
+                self.margin *= lots.abs() / self.lots.abs();
+            } else {
+                self.entry = value;
+                self.margin = lots.abs() * cs * unit / self.leverage;
+            }
+        }
+        self.lots = lots;
+    }
+}
+
+impl<F: FillModel<Quote>, E: FeeModel, S: Sizer> Engine for PerpEngine<F, E, S> {
+    type Bar = PerpQuote;
+
+    #[inline]
+    fn equity(&self) -> f64 {
+        self.equity_at(self.last_value)
+    }
+
+    #[inline]
+    fn is_blowup(&self) -> bool {
+        self.blowup.check(self.equity())
+    }
+
+    #[inline]
+    fn skip(&mut self) {
+        self.liquidation.push(self.liquidation_price());
+    }
+
+    fn step<R: Recorder>(
+        &mut self,
+        bar: &PerpQuote,
+        _chg: Option<Option<bool>>,
+        rec: &mut R,
+    ) -> (f64, f64) {
+        let (ct, cs) = (self.contract_type, self.contract_size);
+        let quote = &bar.quote;
+        let mid = quote.mid();
+        let value = ct.value(mid);
+        let mut cost = 0.;
+        // the position is closed at the liquidation price once the mark crosses it
+        let liq = self.liquidation_price();
+        if (self.lots > 0. && mid <= liq) || (self.lots < 0. && mid >= liq) {
+            let liq_value = ct.value(liq);
+            let lots = self.lots;
+            rec.fill(liq_value, liq, -lots);
+            let c = self
+                .liquidation_fee
+                .charge(lots.abs(), ct.unit(liq), Slippage::Price(0.), rec);
+            self.trade(-lots, liq_value, ct.unit(liq));
+            self.wallet -= c;
+            cost += c;
+        }
+        if let Some(rate) = bar.funding_rate {
+            if self.lots != 0. {
+                // longs pay shorts for a positive funding rate
+                let paid = self.lots * cs * ct.unit(mid) * rate;
+                self.wallet -= paid;
+                if let MarginMode::Isolated = self.margin_mode {
+                    self.margin -= paid;
+                }
+                rec.funding(paid);
+            }
+        }
+        let equity = self.equity_at(value);
+        rec.settle(equity);
+        if quote.signal != self.signal {
+            let lot_num = self.sizer.lots(quote.signal, equity, ct.unit(mid));
+            let change = lot_num * quote.signal.signum() - self.lots;
+            if change != 0. {
+                let exec = self.fill.fill(quote, change);
+                let slippage = (ct.value(exec.price) - value) * change.signum();
+                rec.fill(value, exec.price, change);
+                let c = self.fee.charge(
+                    change.abs(),
+                    ct.unit(exec.price),
+                    Slippage::Price(slippage),
+                    rec,
+                );
+                self.trade(change, value, ct.unit(mid));
+                self.wallet -= c;
+                cost += c;
+            }
+            self.signal = quote.signal;
+        }
+        self.last_value = value;
+        let equity = self.equity_at(value);
+        rec.finish(equity, value);
+        self.liquidation.push(self.liquidation_price());
+        (equity, cost)
+    }
+}
+
+/// Funding events of ticks with a funding every `interval` of wall-clock
+/// time (e.g. `8h`), intervals are aligned to the unix epoch and the first
+/// tick of each interval is an event. The flag of an invalid time is None.
+///
+/// The first valid tick is never an event, as it is unknown whether the
+/// ticks before it are in the same interval. Set its flag to true if the
+/// data starts right after a funding time.
+#[cfg(feature = "time")]
+pub fn perp_funding_mask<V: Vec1View<DateTime>>(
+    time_vec: &V,
+    interval: &str,
+) -> TResult<Vec<Option<bool>>> {
+    let interval = TimeDelta::parse(interval)?;
+    tensure!(
+        interval.months == 0 && interval.inner.num_nanoseconds().unwrap_or(0) > 0,
+        "funding interval should be a positive duration without months"
+    );
+    let mut last: Option<DateTime> = None;
+    Ok(time_vec
+        .titer()
+        .map(|dt| {
+            if dt.is_nat() {
+                return None;
+            }
+            let period = dt.duration_trunc(interval);
+            let event = last.is_some_and(|last| last != period);
+            last = Some(period);
+            Some(event)
+        })
+        .collect())
+}
+
+/// run the engine and return the equity and liquidation price of each tick
+fn run_perp_ret<T, V, VMask, R>(
+    signal_vec: &V,
+    bid_vec: &V,
+    ask_vec: &V,
+    funding_rate_vec: Option<&V>,
+    funding_mask: Option<&VMask>,
+    kwargs: &PerpRetKwargs,
+    rec: &mut R,
+) -> (Vec<f64>, Vec<f64>)
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+    R: Recorder,
+{
+    let mut funding_iter = funding_rate_vec.map(|v| v.titer());
+    let mut mask_iter = funding_mask.map(|v| v.titer());
+    let mut pending: Option<f64> = None;
+    let bars =
+        izip!(signal_vec.titer(), bid_vec.titer(), ask_vec.titer()).map(|(signal, bid, ask)| {
+            let rate = funding_iter
+                .as_mut()
+                .and_then(|it| it.next())
+                .and_then(|r| r.to_opt())
+                .map(|r| r.f64());
+            let event = mask_iter
+                .as_mut()
+                .map(|it| it.next().flatten().unwrap_or(false))
+                .unwrap_or(true);
+            // the funding of invalid ticks is paid at the next valid tick
+            if let (true, Some(rate)) = (event, rate) {
+                pending = Some(pending.unwrap_or(0.) + rate);
+            }
+            Quote::new(signal, bid, ask).map(|quote| PerpQuote {
+                quote,
+                funding_rate: pending.take(),
+            })
+        });
+    let mut engine = kwargs.engine();
+    let equity = run_engine(&mut engine, bars, None::<Vec<Option<bool>>>, rec)
+        .into_iter()
+        .map(|(equity, _)| equity)
+        .collect();
+    (equity, engine.liquidation)
+}
+
+/// Calculate the equity of a perpetual swap account by tick data.
+///
+/// The signal is the same as [`calc_tick_future_ret`](super::calc_tick_future_ret),
+/// the equity is in the margin currency (the coin for inverse contracts).
+/// `funding_rate_vec` is the funding rate of each tick, a positive rate is
+/// paid by longs to shorts on the notional at the mid price. Funding is
+/// paid at the ticks where `funding_mask` is true (see [`perp_funding_mask`]),
+/// or at every tick with a valid rate without a mask.
+pub fn calc_perp_ret<O, T, V, VMask>(
+    signal_vec: &V,
+    bid_vec: &V,
+    ask_vec: &V,
+    funding_rate_vec: Option<&V>,
+    funding_mask: Option<&VMask>,
+    kwargs: &PerpRetKwargs,
+) -> O
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+    O: Vec1<T::Cast<f64>>,
+{
+    if signal_vec.is_empty() {
+        return O::empty();
+    }
+    run_perp_ret(
+        signal_vec,
+        bid_vec,
+        ask_vec,
+        funding_rate_vec,
+        funding_mask,
+        kwargs,
+        &mut (),
+    )
+    .0
+    .into_iter()
+    .map(|v| v.into_cast::<T>())
+    .collect_trusted_vec1()
+}
+
+/// The same as [`calc_perp_ret`], but return a [`BacktestResult`] and the
+/// liquidation price at the end of each tick (`NaN` without a position).
+///
+/// The profit of the result is in the margin currency, fill prices are real
+/// prices.
+pub fn calc_perp_ret_result<T, V, VMask>(
+    signal_vec: &V,
+    bid_vec: &V,
+    ask_vec: &V,
+    funding_rate_vec: Option<&V>,
+    funding_mask: Option<&VMask>,
+    kwargs: &PerpRetKwargs,
+) -> (BacktestResult, Vec<f64>)
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+{
+    let mut rec = ResultBuilder::new(signal_vec.len(), kwargs.contract_size);
+    let (_, liquidation) = run_perp_ret(
+        signal_vec,
+        bid_vec,
+        ask_vec,
+        funding_rate_vec,
+        funding_mask,
+        kwargs,
+        &mut rec,
+    );
+    let mut res = rec.build();
+    for fill in res.fills.iter_mut() {
+        fill.price = kwargs.contract_type.price(fill.price);
+    }
+    (res, liquidation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_identity(res: &BacktestResult, init_cash: f64) {
+        let mut cost = 0.;
+        for i in 0..res.len() {
+            cost += res.fee[i] + res.slippage[i] + res.funding[i];
+            let pnl = res.realized[i] + res.unrealized[i] - cost;
+            assert!((res.equity[i] - init_cash - pnl).abs() < 1e-8);
+        }
+    }
+
+    const NO_MASK: Option<&Vec<Option<bool>>> = None;
+
+    #[test]
+    fn test_perp_ret_funding() {
+        let kwargs = PerpRetKwargs {
+            signal_type: SignalType::Absolute,
+            ..Default::default()
+        };
+        let signal = vec![0., 1., 1., 1., 1., -1., 0.];
+        let bid = vec![99., 99., 100., f64::NAN, 101., 99., 99.];
+        let ask = vec![101., 101., 102., 103., 103., 101., 101.];
+        let rate = vec![0.01; 7];
+        let mask = vec![
+            Some(false),
+            Some(true),
+            Some(false),
+            Some(true),
+            None,
+            Some(true),
+            None,
+        ];
+        let (res, liq) =
+            calc_perp_ret_result(&signal, &bid, &ask, Some(&rate), Some(&mask), &kwargs);
+        assert_eq!(res.lots, vec![0., 1., 1., 1., 1., -1., 0.]);
+        // no lots at tick 1, the funding of the invalid tick 3 is paid at tick 4
+        // and the funding of tick 5 is paid before the position is reversed
+        assert_eq!(res.funding, vec![0., 0., 0., 0., 1.02, 1., 0.]);
+        assert_eq!(res.fills[0].price, 100.);
+        assert!((res.fee[1] - 101. * 0.0005).abs() < 1e-10);
+        assert!((res.slippage[1] - 1.).abs() < 1e-10);
+        check_identity(&res, 10000.);
+        // cross margin, the whole wallet backs one contract
+        assert!(liq[0].is_nan() && liq[1].is_nan() && liq[6].is_nan());
+        let equity: Vec<f64> =
+            calc_perp_ret(&signal, &bid, &ask, Some(&rate), Some(&mask), &kwargs);
+        assert_eq!(equity, res.equity);
+        // without a mask every valid rate is paid, the rates of invalid ticks
+        // are summed
+        let bid = vec![99., 99., f64::NAN, f64::NAN, 101., 99., 99.];
+        let rate = vec![f64::NAN, 0.01, 0.01, 0.02, f64::NAN, f64::NAN, f64::NAN];
+        let (res, _) = calc_perp_ret_result(&signal, &bid, &ask, Some(&rate), NO_MASK, &kwargs);
+        assert_eq!(res.funding[..4], [0.; 4]);
+        assert!((res.funding[4] - 0.03 * 102.).abs() < 1e-10);
+        assert_eq!(res.funding[5], 0.);
+        // a maker buys at the bid
+        let kwargs = PerpRetKwargs {
+            maker: true,
+            ..kwargs
+        };
+        let (res, _) = calc_perp_ret_result(&signal, &bid, &ask, None, NO_MASK, &kwargs);
+        assert!((res.fee[1] - 99. * 0.0002).abs() < 1e-10);
+        assert!((res.slippage[1] + 1.).abs() < 1e-10);
+        check_identity(&res, 10000.);
+    }
+
+    #[test]
+    fn test_perp_ret_liquidation() {
+        let kwargs = PerpRetKwargs {
+            init_cash: 1000.,
+            margin_mode: MarginMode::Isolated,
+            leverage: 10.,
+            maintenance_margin: 0.,
+            signal_type: SignalType::Absolute,
+            ..Default::default()
+        };
+        assert_eq!(perp_liquidation_price(1., 100., 10., &kwargs), 90.);
+        assert_eq!(perp_liquidation_price(-1., 100., 10., &kwargs), 110.);
+        let signal = vec![1., 1., 1., 1.];
+        let mid = vec![100., 95., 89., 100.];
+        let (res, liq) = calc_perp_ret_result(&signal, &mid, &mid, None, NO_MASK, &kwargs);
+        // the margin of 10 is lost at 90, the signal is not reopened
+        assert!((liq[0] - 90.).abs() < 1e-8);
+        assert!((liq[1] - 90.).abs() < 1e-8);
+        assert!(liq[2].is_nan());
+        assert_eq!(res.lots, vec![1., 1., 0., 0.]);
+        assert_eq!(res.fills[1].price, 90.);
+        let fee = (100. + 90.) * 0.0005;
+        assert!((res.equity[3] - (1000. - 10. - fee)).abs() < 1e-8);
+        check_identity(&res, 1000.);
+    }
+
+    #[test]
+    fn test_perp_ret_inverse() {
+        let kwargs = PerpRetKwargs {
+            init_cash: 1.,
+            contract_size: 100.,
+            contract_type: ContractType::Inverse,
+            maintenance_margin: 0.,
+            taker_fee: 0.,
+            signal_type: SignalType::Absolute,
+            ..Default::default()
+        };
+        // a long of 1 coin is liquidated at half of the entry price
+        assert!((perp_liquidation_price(1., 100., 1., &kwargs) - 50.).abs() < 1e-10);
+        assert!(perp_liquidation_price(-1., 100., 1., &kwargs).is_nan());
+        let signal = vec![1., 1., 1.];
+        let mid = vec![100., 200., 80.];
+        let (res, _) = calc_perp_ret_result(&signal, &mid, &mid, None, NO_MASK, &kwargs);
+        assert_eq!(res.lots, vec![1., 1., 1.]);
+        assert!((res.equity[1] - 1.5).abs() < 1e-10);
+        assert!((res.equity[2] - 0.75).abs() < 1e-10);
+        check_identity(&res, 1.);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn test_perp_funding_mask() -> TResult<()> {
+        let time_vec: Vec<DateTime> = [
+            "2024-01-01 07:59:00",
+            "2024-01-01 08:00:03",
+            "2024-01-01 08:01:00",
+            "2024-01-01 17:30:00",
+            "2024-01-01 17:31:00",
+        ]
+        .into_iter()
+        .map(|s| DateTime::parse(s, None).unwrap())
+        .collect();
+        let mask = perp_funding_mask(&time_vec, "8h")?;
+        assert_eq!(
+            mask,
+            vec![
+                Some(false),
+                Some(true),
+                Some(false),
+                Some(true),
+                Some(false)
+            ]
+        );
+        // the first valid tick is not an event even right after a funding time
+        assert_eq!(
+            perp_funding_mask(&time_vec[1..].to_vec(), "8h")?[0],
+            Some(false)
+        );
+        assert!(perp_funding_mask(&time_vec, "1mo").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "time")]
+use tevec::prelude::{tensure, DateTime, TResult};
+
+#[cfg(feature = "time")]
+use crate::{Trade, TradeSide};
+
+/// A fill of the engine, `lots` is positive to buy and negative to sell.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    /// index of the bar (or tick)
+    pub idx: usize,
+    /// reference price of the fill, costs are recorded separately
+    pub price: f64,
+    pub lots: f64,
+}
+
+/// Detailed output of an equity engine, every vector has the same length as
+/// the input.
+///
+/// Fills are priced at the reference price of the engine (open for bar
+/// engines and mid price for tick engines), so that
+/// `equity - init_cash == realized + unrealized - cumsum(fee + slippage + funding)`
+/// as long as the engine does not skip the profit of a contract change.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BacktestResult {
+    pub equity: Vec<f64>,
+    /// signed lots held at the end of each bar
+    pub lots: Vec<f64>,
+    /// commission charged at each bar
+    pub fee: Vec<f64>,
+    /// slippage (or spread) cost charged at each bar
+    pub slippage: Vec<f64>,
+    /// cumulative realized profit by the average open price, costs excluded
+    pub realized: Vec<f64>,
+    /// unrealized profit of the lots held at the end of each bar
+    pub unrealized: Vec<f64>,
+    /// funding paid at each bar for perpetual swaps, dividends received are
+    /// negative funding for stocks
+    #[serde(default)]
+    pub funding: Vec<f64>,
+    pub fills: Vec<Fill>,
+}
+
+impl BacktestResult {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.equity.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.equity.is_empty()
+    }
+
+    /// total commission and slippage cost
+    #[inline]
+    pub fn total_cost(&self) -> f64 {
+        self.fee
+            .iter()
+            .zip(&self.slippage)
+            .fold(0., |acc, (f, s)| acc + f + s)
+    }
+
+    /// total traded lots
+    #[inline]
+    pub fn turnover(&self) -> f64 {
+        self.fills.iter().fold(0., |acc, f| acc + f.lots.abs())
+    }
+
+    /// Convert fills to trades with the time of each bar, `time` should have
+    /// the same length as the result.
+    #[cfg(feature = "time")]
+    pub fn trades(&self, time: &[DateTime]) -> TResult<Vec<Trade>> {
+        tensure!(
+            time.len() == self.len(),
+            "length of time should be equal to the length of result"
+        );
+        Ok(self
+            .fills
+            .iter()
+            .map(|f| {
+                let side = if f.lots > 0. {
+                    TradeSide::Buy
+                } else {
+                    TradeSide::Sell
+                };
+                Trade::new(time[f.idx], side, f.price, f.lots.abs())
+            })
+            .collect())
+    }
+}
+
+/// Receive the details of an engine bar by bar, `()` ignores them.
+pub(super) trait Recorder {
+    /// a fill of signed lots at the reference `price`, traded at `exec_price`
+    #[inline]
+    fn fill(&mut self, _price: f64, _exec_price: f64, _lots: f64) {}
+
+    /// signed lots closed at a contract change
+    #[inline]
+    fn roll(&mut self, price: f64, exec_price: f64, lots: f64) {
+        self.fill(price, exec_price, lots)
+    }
+
+    /// commission and slippage charged
+    #[inline]
+    fn cost(&mut self, _fee: f64, _slippage: f64) {}
+
+    /// funding paid for the lots held
+    #[inline]
+    fn funding(&mut self, _amount: f64) {}
+
+    /// the lots held are multiplied by `ratio` by a split
+    #[inline]
+    fn adjust(&mut self, _ratio: f64) {}
+
+    /// the profit of the bar is settled, before the position is changed
+    #[inline]
+    fn settle(&mut self, _equity: f64) {}
+
+    /// end of a bar, `mark` is `NaN` for an invalid bar
+    #[inline]
+    fn finish(&mut self, _equity: f64, _mark: f64) {}
+}
+
+impl Recorder for () {}
+
+/// Build a [`BacktestResult`] with average open price accounting.
+pub(super) struct ResultBuilder {
+    multiplier: f64,
+    res: BacktestResult,
+    lots: f64,
+    open_price: f64,
+    realized: f64,
+    last_mark: f64,
+    fee: f64,
+    slippage: f64,
+    funding: f64,
+}
+
+impl ResultBuilder {
+    #[inline]
+    pub fn new(len: usize, multiplier: f64) -> Self {
+        Self {
+            multiplier,
+            res: BacktestResult {
+                equity: Vec::with_capacity(len),
+                lots: Vec::with_capacity(len),
+                fee: Vec::with_capacity(len),
+                slippage: Vec::with_capacity(len),
+                realized: Vec::with_capacity(len),
+                unrealized: Vec::with_capacity(len),
+                funding: Vec::with_capacity(len),
+                fills: Vec::new(),
+            },
+            lots: 0.,
+            open_price: f64::NAN,
+            realized: 0.,
+            last_mark: f64::NAN,
+            fee: 0.,
+            slippage: 0.,
+            funding: 0.,
+        }
+    }
+
+    #[inline]
+    pub fn build(self) -> BacktestResult {
+        self.res
+    }
+}
+
+impl Recorder for ResultBuilder {
+    fn fill(&mut self, price: f64, _exec_price: f64, lots: f64) {
+        if lots == 0. {
+            return;
+        }
+        let new_lots = self.lots + lots;
+        if self.lots == 0. {
+            self.open_price = price;
+        } else if self.lots.signum() == lots.signum() {
+            self.open_price =
+                (self.open_price * self.lots.abs() + price * lots.abs()) / new_lots.abs();
+        } else {
+            let closed = lots.abs().min(self.lots.abs());
+            self.realized +=
+                (price - self.open_price) * closed * self.lots.signum() * self.multiplier;
+            if new_lots == 0. {
+                self.open_price = f64::NAN;
+            } else if new_lots.signum() != self.lots.signum() {
+                self.open_price = price;
+            }
+        }
+        self.lots = new_lots;
+        self.res.fills.push(Fill {
+            idx: self.res.equity.len(),
+            price,
+            lots,
+        });
+    }
+
+    #[inline]
+    fn funding(&mut self, amount: f64) {
+        self.funding += amount;
+    }
+
+    #[inline]
+    fn adjust(&mut self, ratio: f64) {
+        // the cost of the position is unchanged
+        self.lots *= ratio;
+        self.open_price /= ratio;
+    }
+
+    #[inline]
+    fn cost(&mut self, fee: f64, slippage: f64) {
+        self.fee += fee;
+        self.slippage += slippage;
+    }
+
+    fn finish(&mut self, equity: f64, mark: f64) {
+        if !mark.is_nan() {
+            self.last_mark = mark;
+        }
+        let unrealized = if self.lots != 0. {
+            (self.last_mark - self.open_price) * self.lots * self.multiplier
+        } else {
+            0.
+        };
+        self.res.equity.push(equity);
+        self.res.lots.push(self.lots);
+        self.res.fee.push(self.fee);
+        self.res.slippage.push(self.slippage);
+        self.res.realized.push(self.realized);
+        self.res.unrealized.push(unrealized);
+        self.res.funding.push(self.funding);
+        self.fee = 0.;
+        self.slippage = 0.;
+        self.funding = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::equity::{calc_future_ret, calc_future_ret_result, CommissionType, FutureRetKwargs};
+
+    #[test]
+    fn test_backtest_result() {
+        let kwargs = FutureRetKwargs {
+            init_cash: 10000,
+            multiplier: 1.,
+            leverage: 1.,
+            slippage: 0.01,
+            c_rate: 0.001,
+            blowup: false,
+            commission_type: CommissionType::Percent,
+        };
+        let pos = vec![0., 1., 1., 0.5, -1., 0.];
+        let open = vec![10., 10., 11., 12., 11., 10.];
+        let close = vec![10., 11., 12., 11., 10., 10.];
+        let equity: Vec<f64> = calc_future_ret(&pos, &open, &close, None::<Vec<_>>, &kwargs);
+        let res = calc_future_ret_result(&pos, &open, &close, None::<Vec<_>>, &kwargs);
+        assert_eq!(res.equity, equity);
+        assert_eq!(res.lots, vec![0., 1000., 1000., 499., -1042., 0.]);
+        assert_eq!(res.fills.len(), 4);
+        assert_eq!(res.turnover(), 1000. + 501. + 1541. + 1042.);
+        assert!((res.fee[1] - 10.).abs() < 1e-10);
+        assert!((res.slippage[1] - 10.).abs() < 1e-10);
+        // close 501 lots opened at 10 at 12, then reverse at 11
+        assert!((res.realized[3] - 501. * 2.).abs() < 1e-8);
+        assert!((res.realized[4] - (501. * 2. + 499.)).abs() < 1e-8);
+        assert!((res.unrealized[4] - 1042.).abs() < 1e-8);
+        assert!((res.realized[5] - (501. * 2. + 499. + 1042.)).abs() < 1e-8);
+        let mut cost = 0.;
+        for i in 0..res.len() {
+            cost += res.fee[i] + res.slippage[i];
+            let pnl = res.realized[i] + res.unrealized[i] - cost;
+            assert!((res.equity[i] - 10000. - pnl).abs() < 1e-8);
+        }
+        assert!((res.total_cost() - cost).abs() < 1e-10);
+        #[cfg(feature = "time")]
+        {
+            use tevec::prelude::DateTime;
+
+            use crate::TradeSide;
+            let time: Vec<DateTime> = (0..6).map(|i| DateTime::new(i * 60_000_000_000)).collect();
+            let trades = res.trades(&time).unwrap();
+            assert_eq!(trades.len(), 4);
+            assert_eq!(trades[0].time, time[1]);
+            assert!(matches!(trades[3].side, TradeSide::Buy));
+            assert_eq!(trades[3].num, 1042.);
+            // a short time axis is an error instead of a panic
+            assert!(res.trades(&time[..3]).is_err());
+        }
+    }
+}
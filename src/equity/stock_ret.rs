@@ -0,0 +1,476 @@
+use itertools::izip;
+use serde::{Deserialize, Serialize};
+use tevec::prelude::*;
+
+use super::{
+    engine::{
+        run_engine, Bar, BoardLotSizer, Commission, Engine, FeeModel, FillModel, FixedSlippage,
+        Sizer,
+    },
+    result::{Recorder, ResultBuilder},
+    BacktestResult, CommissionType,
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StockRetKwargs {
+    pub init_cash: usize,
+    /// shares of a board lot, buys are rounded down to board lots
+    pub lot_size: f64,
+    /// slippage in price
+    pub slippage: f64,
+    /// commission rate for percent commission, or commission per share for
+    /// absolute commission
+    pub c_rate: f64,
+    pub commission_type: CommissionType,
+    /// stamp duty rate on the amount of sells
+    pub stamp_duty: f64,
+    /// transfer fee rate on the amount of buys and sells
+    pub transfer_fee: f64,
+    /// a negative signal is treated as 0 unless short is allowed, a short is
+    /// frictionless: no borrow fee or margin, the proceeds are added to cash
+    /// and it is not limited by T+1
+    pub allow_short: bool,
+}
+
+impl Default for StockRetKwargs {
+    #[inline]
+    fn default() -> Self {
+        StockRetKwargs {
+            init_cash: 1000000,
+            lot_size: 100.,
+            slippage: 0.,
+            c_rate: 0.0003,
+            commission_type: CommissionType::Percent,
+            stamp_duty: 0.0005,
+            transfer_fee: 0.00001,
+            allow_short: false,
+        }
+    }
+}
+
+impl StockRetKwargs {
+    /// engine of [`calc_stock_ret`]
+    #[inline]
+    fn engine(&self) -> StockEngine<FixedSlippage, Commission, BoardLotSizer> {
+        StockEngine {
+            fill: FixedSlippage(self.slippage),
+            fee: Commission::new(self.commission_type, self.c_rate, 1.),
+            sizer: BoardLotSizer::new(self.lot_size),
+            lot_size: self.lot_size,
+            stamp_duty: self.stamp_duty,
+            transfer_fee: self.transfer_fee,
+            allow_short: self.allow_short,
+            cash: self.init_cash as f64,
+            shares: 0.,
+            sellable: 0.,
+            signal: 0.,
+            pending: false,
+            last_split: None,
+            last_close: f64::NAN,
+        }
+    }
+}
+
+/// A bar of a stock.
+struct StockBar {
+    bar: Bar,
+    /// cumulative split factor
+    split: Option<f64>,
+    /// cash dividend per share
+    dividend: f64,
+    /// the first bar of a trading day
+    new_day: bool,
+}
+
+/// A cash account of a stock, the shares bought are not sellable until the
+/// next trading day.
+struct StockEngine<F, E, S> {
+    fill: F,
+    fee: E,
+    sizer: S,
+    lot_size: f64,
+    stamp_duty: f64,
+    transfer_fee: f64,
+    allow_short: bool,
+    cash: f64,
+    shares: f64,
+    /// long shares held before the current trading day
+    sellable: f64,
+    signal: f64,
+    /// a sell is limited by T+1 and should be finished later
+    pending: bool,
+    last_split: Option<f64>,
+    last_close: f64,
+}
+
+/// Whole shares, the error of float multiplication is ignored.
+#[inline]
+fn whole_shares(shares: f64) -> f64 {
+    let round = shares.round();
+    if (shares - round).abs() < 1e-6 {
+        round
+    } else {
+        shares.trunc()
+    }
+}
+
+impl<F, E, S> StockEngine<F, E, S> {
+    /// Shares to trade towards `target` under the board lot and T+1 rules,
+    /// `unit_cost` is the price and cost of buying a share.
+    fn order(&mut self, target: f64, unit_cost: f64) -> f64 {
+        let change = target - self.shares;
+        self.pending = false;
+        if change < 0. {
+            // odd lots can only be sold, a short is opened after the long is closed
+            let long = (-change).min(self.shares.max(0.));
+            let sell = long.min(self.sellable);
+            self.pending = sell < long;
+            let short = if self.pending { 0. } else { -change - long };
+            -(sell + short)
+        } else if change > 0. {
+            let affordable = (self.cash / unit_cost / self.lot_size).floor() * self.lot_size;
+            ((change / self.lot_size).floor() * self.lot_size).min(affordable.max(0.))
+        } else {
+            0.
+        }
+    }
+}
+
+impl<F: FillModel<Bar>, E: FeeModel, S: Sizer> Engine for StockEngine<F, E, S> {
+    type Bar = StockBar;
+
+    #[inline]
+    fn equity(&self) -> f64 {
+        if self.shares != 0. {
+            self.cash + self.shares * self.last_close
+        } else {
+            self.cash
+        }
+    }
+
+    #[inline]
+    fn is_blowup(&self) -> bool {
+        false
+    }
+
+    fn step<R: Recorder>(
+        &mut self,
+        bar: &StockBar,
+        _chg: Option<Option<bool>>,
+        rec: &mut R,
+    ) -> (f64, f64) {
+        let (open, close) = (bar.bar.open, bar.bar.close);
+        if let Some(split) = bar.split {
+            if let Some(last_split) = self.last_split {
+                let ratio = split / last_split;
+                if ratio != 1. && self.shares != 0. {
+                    // the fraction of a share is sold at the open
+                    let shares = self.shares * ratio;
+                    let whole = whole_shares(shares);
+                    rec.adjust(ratio);
+                    if (shares - whole).abs() >= 1e-6 {
+                        rec.fill(open, open, whole - shares);
+                        self.cash += (shares - whole) * open;
+                    }
+                    self.shares = whole;
+                    self.sellable = whole_shares(self.sellable * ratio).min(whole.max(0.));
+                }
+            }
+            self.last_split = Some(split);
+        }
+        if bar.dividend != 0. && self.shares != 0. {
+            // a short pays the dividend
+            let amount = self.shares * bar.dividend;
+            self.cash += amount;
+            rec.funding(-amount);
+        }
+        if bar.new_day {
+            self.sellable = self.shares.max(0.);
+        }
+        let signal = if self.allow_short {
+            bar.bar.signal
+        } else {
+            bar.bar.signal.max(0.)
+        };
+        rec.settle(self.cash + self.shares * open);
+        let mut cost = 0.;
+        if (signal != self.signal) || self.pending {
+            let equity = self.cash + self.shares * open;
+            let target = self.sizer.lots(signal, equity, open) * signal.signum();
+            let exec = self.fill.fill(&bar.bar, 1.);
+            let unit_cost = exec.price
+                + self.fee.charge(1., exec.fee_price, exec.slippage, &mut ())
+                + exec.price * self.transfer_fee;
+            let change = self.order(target, unit_cost);
+            if change != 0. {
+                let exec = self.fill.fill(&bar.bar, change);
+                rec.fill(open, exec.price, change);
+                let amount = change.abs() * exec.price;
+                let tax = if change < 0. {
+                    amount * (self.stamp_duty + self.transfer_fee)
+                } else {
+                    amount * self.transfer_fee
+                };
+                rec.cost(tax, 0.);
+                cost = self
+                    .fee
+                    .charge(change.abs(), exec.fee_price, exec.slippage, rec)
+                    + tax;
+                self.cash -= change * exec.price + cost;
+                if change < 0. {
+                    self.sellable -= (-change).min(self.shares.max(0.));
+                }
+                self.shares += change;
+            }
+            self.signal = signal;
+        }
+        self.last_close = close;
+        let equity = self.cash + self.shares * close;
+        rec.finish(equity, close);
+        (equity, cost)
+    }
+}
+
+/// run the engine and return the equity and cost of each bar
+#[allow(clippy::too_many_arguments)]
+fn run_stock_ret<T, V, VMask, R>(
+    pos_vec: &V,
+    open_vec: &V,
+    close_vec: &V,
+    split_vec: Option<&V>,
+    dividend_vec: Option<&V>,
+    new_day_vec: Option<&VMask>,
+    kwargs: &StockRetKwargs,
+    rec: &mut R,
+) -> Vec<(f64, f64)>
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+    R: Recorder,
+{
+    let mut split_iter = split_vec.map(|v| v.titer());
+    let mut dividend_iter = dividend_vec.map(|v| v.titer());
+    let mut new_day_iter = new_day_vec.map(|v| v.titer());
+    let mut dividend = 0.;
+    let mut new_day = false;
+    let bars =
+        izip!(pos_vec.titer(), open_vec.titer(), close_vec.titer()).map(|(pos, open, close)| {
+            let split = split_iter
+                .as_mut()
+                .and_then(|it| it.next())
+                .and_then(|a| a.to_opt())
+                .map(|a| a.f64());
+            // keep the dividend and new day of an invalid bar
+            dividend += dividend_iter
+                .as_mut()
+                .and_then(|it| it.next())
+                .and_then(|a| a.to_opt())
+                .map_or(0., |a| a.f64());
+            // every bar is a trading day if not given
+            new_day |= match new_day_iter.as_mut() {
+                Some(it) => it.next().flatten().unwrap_or(false),
+                None => true,
+            };
+            Bar::new(pos, open, close).map(|bar| StockBar {
+                bar,
+                split,
+                dividend: std::mem::take(&mut dividend),
+                new_day: std::mem::take(&mut new_day),
+            })
+        });
+    run_engine(&mut kwargs.engine(), bars, None::<Vec<Option<bool>>>, rec)
+}
+
+/// Calculate the equity of a stock (or ETF) cash account.
+///
+/// `pos_vec` is the percent of equity held, filled at the open in board lots.
+/// Prices are raw prices. `split_vec` is the cumulative split factor
+/// (including bonus shares), the shares held are multiplied by the change of
+/// the factor and the fraction of a share is sold at the open.
+/// `dividend_vec` is the cash dividend per share paid at the open of the
+/// ex-date, it is recorded as negative funding in [`BacktestResult`].
+/// `new_day_vec` marks the first bar of each trading day for intraday bars,
+/// every bar is a trading day if it is not given.
+pub fn calc_stock_ret<O, T, V, VMask>(
+    pos_vec: &V,
+    open_vec: &V,
+    close_vec: &V,
+    split_vec: Option<&V>,
+    dividend_vec: Option<&V>,
+    new_day_vec: Option<&VMask>,
+    kwargs: &StockRetKwargs,
+) -> O
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+    O: Vec1<T::Cast<f64>>,
+{
+    if pos_vec.is_empty() {
+        return O::empty();
+    }
+    run_stock_ret(
+        pos_vec,
+        open_vec,
+        close_vec,
+        split_vec,
+        dividend_vec,
+        new_day_vec,
+        kwargs,
+        &mut (),
+    )
+    .into_iter()
+    .map(|(equity, _)| equity.into_cast::<T>())
+    .collect_trusted_vec1()
+}
+
+/// The same as [`calc_stock_ret`], but return a [`BacktestResult`], the lots
+/// are shares.
+pub fn calc_stock_ret_result<T, V, VMask>(
+    pos_vec: &V,
+    open_vec: &V,
+    close_vec: &V,
+    split_vec: Option<&V>,
+    dividend_vec: Option<&V>,
+    new_day_vec: Option<&VMask>,
+    kwargs: &StockRetKwargs,
+) -> BacktestResult
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+{
+    let mut rec = ResultBuilder::new(pos_vec.len(), 1.);
+    run_stock_ret(
+        pos_vec,
+        open_vec,
+        close_vec,
+        split_vec,
+        dividend_vec,
+        new_day_vec,
+        kwargs,
+        &mut rec,
+    );
+    rec.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stock_ret() {
+        let kwargs = StockRetKwargs {
+            init_cash: 100000,
+            ..Default::default()
+        };
+        let pos = vec![0., 1., 1., -1., 0.5, 0.5];
+        let open = vec![10., 10., 11., 12., 12., 12.];
+        let close = vec![10., 10.5, 11.5, 12., 12., 12.];
+        let res = calc_stock_ret_result(&pos, &open, &close, None, None, None::<&Vec<_>>, &kwargs);
+        // the cost of a share is 10.0031, and the short signal closes the long
+        assert_eq!(res.lots, vec![0., 9900., 9900., 0., 4900., 4900.]);
+        let sell = 9900. * 12.;
+        assert!((res.fee[3] - sell * (0.0003 + 0.0005 + 0.00001)).abs() < 1e-8);
+        let mut cost = 0.;
+        for i in 0..res.len() {
+            cost += res.fee[i] + res.slippage[i];
+            let pnl = res.realized[i] + res.unrealized[i] - cost;
+            assert!((res.equity[i] - 100000. - pnl).abs() < 1e-8);
+        }
+        let equity: Vec<f64> =
+            calc_stock_ret(&pos, &open, &close, None, None, None::<&Vec<_>>, &kwargs);
+        assert_eq!(equity, res.equity);
+    }
+
+    #[test]
+    fn test_stock_ret_t1_and_adjust() {
+        let kwargs = StockRetKwargs {
+            init_cash: 100000,
+            c_rate: 0.,
+            stamp_duty: 0.,
+            transfer_fee: 0.,
+            ..Default::default()
+        };
+        // intraday bars, shares bought on the first day are sold on the second day
+        let pos = vec![1., 0., 0., 0., 0.];
+        let open = vec![10., 10., 10., 5., 5.];
+        let close = vec![10., 10., 10., 5., 5.5];
+        let new_day = vec![true, false, false, true, false];
+        let new_day = new_day.opt();
+        let res = calc_stock_ret_result(&pos, &open, &close, None, None, Some(&new_day), &kwargs);
+        assert_eq!(res.lots, vec![10000., 10000., 10000., 0., 0.]);
+        // a 10 for 10 split, the shares are doubled at the ex-date
+        let pos = vec![1.; 5];
+        let split = vec![1., 1., 1., 2., 2.];
+        let res = calc_stock_ret_result(
+            &pos,
+            &open,
+            &close,
+            Some(&split),
+            None,
+            None::<&Vec<_>>,
+            &kwargs,
+        );
+        assert_eq!(res.lots, vec![10000., 10000., 10000., 20000., 20000.]);
+        assert_eq!(
+            res.equity,
+            vec![100000., 100000., 100000., 100000., 110000.]
+        );
+    }
+
+    #[test]
+    fn test_stock_ret_dividend_and_odd_split() {
+        let kwargs = StockRetKwargs {
+            init_cash: 100000,
+            c_rate: 0.,
+            stamp_duty: 0.,
+            transfer_fee: 0.,
+            ..Default::default()
+        };
+        // a cash dividend of 0.5 per share is paid at the ex-date, the shares
+        // are unchanged
+        let pos = vec![1.; 4];
+        let open = vec![10., 10., 9.5, 9.5];
+        let close = vec![10., 10., 9.5, 9.5];
+        let dividend = vec![0., 0., 0.5, 0.];
+        let res = calc_stock_ret_result(
+            &pos,
+            &open,
+            &close,
+            None,
+            Some(&dividend),
+            None::<&Vec<_>>,
+            &kwargs,
+        );
+        assert_eq!(res.lots, vec![10000.; 4]);
+        assert_eq!(res.equity, vec![100000.; 4]);
+        assert_eq!(res.funding, vec![0., 0., -5000., 0.]);
+        // 5 bonus shares for 10 shares, the fraction of 3001.5 shares is sold
+        // at the open
+        let pos = vec![0.3002; 3];
+        let open = vec![15., 10., 10.];
+        let close = vec![15., 10., 10.];
+        let split = vec![1., 1.5, 1.5];
+        let kwargs = StockRetKwargs {
+            lot_size: 1.,
+            ..kwargs
+        };
+        let res = calc_stock_ret_result(
+            &pos,
+            &open,
+            &close,
+            Some(&split),
+            None,
+            None::<&Vec<_>>,
+            &kwargs,
+        );
+        assert_eq!(res.lots, vec![2001., 3001., 3001.]);
+        assert_eq!(res.fills[1].lots, -0.5);
+        assert_eq!(res.equity, vec![100000.; 3]);
+    }
+}
@@ -1,10 +1,17 @@
 use itertools::izip;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tevec::prelude::*;
 
-use super::{CommissionType, SignalType};
+use super::{
+    engine::{
+        run_engine, BidAsk, Blowup, Commission, FixedLots, LeverageSizer, Quote, Reopen, Sizer,
+        TickEngine,
+    },
+    result::{Recorder, ResultBuilder},
+    BacktestResult, CommissionType, SignalType,
+};
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct TickFutureRetKwargs {
     pub init_cash: usize,
     pub multiplier: f64,
@@ -27,6 +34,60 @@ impl Default for TickFutureRetKwargs {
     }
 }
 
+impl TickFutureRetKwargs {
+    /// Engine of [`calc_tick_future_ret`].
+    ///
+    /// A percent signal is sized by the cash and resized by price at a contract
+    /// change, an absolute signal is the number of lots.
+    #[inline]
+    pub(super) fn engine(&self) -> TickEngine<BidAsk, Commission, Box<dyn Sizer>> {
+        let (sizer, reopen, after_fee): (Box<dyn Sizer>, _, _) = match self.signal_type {
+            SignalType::Percent => (
+                Box::new(LeverageSizer::new(1., self.multiplier)),
+                Reopen::Resize,
+                true,
+            ),
+            SignalType::Absolute => (Box::new(FixedLots), Reopen::Signal, false),
+        };
+        TickEngine::new(
+            BidAsk,
+            Commission::new(self.commission_type, self.c_rate, self.multiplier),
+            sizer,
+            self.multiplier,
+            Blowup::new(self.blowup),
+            reopen,
+            after_fee,
+            self.init_cash as f64,
+        )
+    }
+}
+
+/// run the engine and return the equity of each tick
+fn run_tick_future_ret<T, V, VMask, R>(
+    signal_vec: &V,
+    bid_vec: &V,
+    ask_vec: &V,
+    contract_chg_signal_vec: Option<&VMask>,
+    kwargs: &TickFutureRetKwargs,
+    rec: &mut R,
+) -> Vec<(f64, f64)>
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+    R: Recorder,
+{
+    let quotes = izip!(signal_vec.titer(), bid_vec.titer(), ask_vec.titer())
+        .map(|(signal, bid, ask)| Quote::new(signal, bid, ask));
+    run_engine(
+        &mut kwargs.engine(),
+        quotes,
+        contract_chg_signal_vec.map(|c| c.titer()),
+        rec,
+    )
+}
+
 pub fn calc_tick_future_ret<O, T, V, VMask>(
     signal_vec: &V,
     bid_vec: &V,
@@ -44,262 +105,43 @@ where
     if signal_vec.is_empty() {
         return O::empty();
     }
-    let mut cash = kwargs.init_cash as f64;
-    let mut last_lot_num = 0.;
-    let mut last_chg = false;
-    let mut last_mid = f64::NAN;
-    let blowup = kwargs.blowup;
-    let multiplier = kwargs.multiplier;
-    let commission_type = kwargs.commission_type;
-    let c_rate = kwargs.c_rate;
-    if let SignalType::Percent = kwargs.signal_type {
-        let mut last_signal = 0_f64;
-        if let Some(contract_chg_signal_vec) = contract_chg_signal_vec {
-            izip!(
-                signal_vec.titer(),
-                bid_vec.titer(),
-                ask_vec.titer(),
-                contract_chg_signal_vec.titer(),
-            )
-            .map(|(signal, bid, ask, chg)| {
-                if signal.is_none() || bid.is_none() || ask.is_none() {
-                    return cash.into_cast::<T>();
-                } else if blowup && cash <= 0. {
-                    return 0_f64.into_cast::<T>();
-                }
-                let signal = signal.unwrap().f64();
-                let bid = bid.unwrap().f64();
-                let ask = ask.unwrap().f64();
-                let chg = chg.unwrap_or(false);
-                let mid = (bid + ask) * 0.5;
-
-                if last_chg && last_lot_num != 0. {
-                    // update last_lot_num if contract has changed
-                    last_lot_num = ((last_lot_num * last_mid) / mid).floor();
-                    if let CommissionType::Percent = commission_type {
-                        cash -=
-                            last_lot_num.abs() * multiplier * (mid * c_rate + (ask - bid) * 0.5);
-                    } else {
-                        cash -= last_lot_num.abs() * (c_rate + (ask - bid) * 0.5 * multiplier);
-                    }
-                }
-
-                // calculate the profit and loss of the current period
-                // we should not calculate the profit in this way if the contract has changed
-                if (last_lot_num != 0.) && last_mid.not_none() && (!last_chg) {
-                    cash += last_lot_num * last_signal.signum() * (mid - last_mid) * multiplier;
-                }
-                let out = cash;
-                // TODO(Teamon): how to handle the case when the contract has changed
-                // should we pass daily open price as another input?
-                // currently we just ignore the profit and loss in the first tick when there is a contract change
-
-                // addup the commision fee
-                if (signal != last_signal) || chg {
-                    // the position has changed, calculate the new theoretical number of lots
-                    let lot_num = ((cash * signal.abs()) / (multiplier * mid)).floor();
-                    if !chg {
-                        let lot_num_change =
-                            lot_num * signal.signum() - last_lot_num * last_signal.signum();
-                        let (open_price, spread) = if lot_num_change > 0. {
-                            (ask, ask - mid)
-                        } else {
-                            (bid, mid - bid)
-                        };
-                        if let CommissionType::Percent = commission_type {
-                            cash -=
-                                lot_num_change.abs() * multiplier * (open_price * c_rate + spread);
-                        } else {
-                            cash -= lot_num_change.abs() * (c_rate + spread * multiplier);
-                        }
-                    } else {
-                        // for simple, assume spread is (bid - ask) / 2
-                        // otherwise we need the bid and ask of next hot future
-                        if let CommissionType::Percent = commission_type {
-                            cash -= last_lot_num.abs()
-                                * multiplier
-                                * (mid * c_rate + (ask - bid) * 0.5);
-                        } else {
-                            cash -= last_lot_num.abs() * (c_rate + (ask - bid) * 0.5 * multiplier);
-                        }
-                    };
-
-                    // update last lot num and last pos
-                    last_lot_num = lot_num;
-                    last_signal = signal;
-                }
-
-                last_mid = mid; // update last close
-                last_chg = chg;
-                out.into_cast::<T>()
-            })
-            .collect_trusted_vec1()
-        } else {
-            // ignore contract chg signal
-            // this should be faster than the above
-            izip!(signal_vec.titer(), bid_vec.titer(), ask_vec.titer(),)
-                .map(|(signal, bid, ask)| {
-                    if signal.is_none() || bid.is_none() || ask.is_none() {
-                        return cash.into_cast::<T>();
-                    } else if blowup && cash <= 0. {
-                        return 0_f64.into_cast::<T>();
-                    }
-                    let signal = signal.unwrap().f64();
-                    let bid = bid.unwrap().f64();
-                    let ask = ask.unwrap().f64();
-                    let mid = (bid + ask) * 0.5;
-
-                    // calculate the profit and loss of the current period
-                    if (last_lot_num != 0.) && last_mid.not_none() {
-                        cash += last_lot_num * last_signal.signum() * (mid - last_mid) * multiplier;
-                    }
-
-                    // addup the commision fee
-                    if signal != last_signal {
-                        // the position has changed, calculate the new theoretical number of lots
-                        let lot_num = ((cash * signal.abs()) / (multiplier * mid)).floor();
-                        let lot_num_change =
-                            lot_num * signal.signum() - last_lot_num * last_signal.signum();
-                        let (open_price, spread) = if lot_num_change > 0. {
-                            (ask, ask - mid)
-                        } else {
-                            (bid, mid - bid)
-                        };
-                        if let CommissionType::Percent = commission_type {
-                            cash -=
-                                lot_num_change.abs() * multiplier * (open_price * c_rate + spread);
-                        } else {
-                            cash -= lot_num_change.abs() * (c_rate + spread * multiplier);
-                        };
-                        // update last lot num and last pos
-                        last_lot_num = lot_num;
-                        last_signal = signal;
-                    }
-
-                    last_mid = mid; // update last close
-                    cash.into_cast::<T>()
-                })
-                .collect_trusted_vec1()
-        }
-    } else {
-        // absolute signal type
-        if let Some(contract_chg_signal_vec) = contract_chg_signal_vec {
-            izip!(
-                signal_vec.titer(),
-                bid_vec.titer(),
-                ask_vec.titer(),
-                contract_chg_signal_vec.titer(),
-            )
-            .map(|(lot_num, bid, ask, chg)| {
-                if lot_num.is_none() || bid.is_none() || ask.is_none() {
-                    return cash.into_cast::<T>();
-                } else if blowup && cash <= 0. {
-                    return 0_f64.into_cast::<T>();
-                }
-                let lot_num = lot_num.unwrap().f64();
-                let bid = bid.unwrap().f64();
-                let ask = ask.unwrap().f64();
-                let chg = chg.unwrap_or(false);
-                let mid = (bid + ask) * 0.5;
-
-                if last_chg && last_lot_num != 0. {
-                    if let CommissionType::Percent = commission_type {
-                        cash -= lot_num.abs() * multiplier * (mid * c_rate + (ask - bid) * 0.5);
-                    } else {
-                        cash -= lot_num.abs() * (c_rate + (ask - bid) * 0.5 * multiplier);
-                    }
-                }
-
-                // calculate the profit and loss of the current period
-                // we should not calculate the profit in this way if the contract has changed
-                if (last_lot_num != 0.) && last_mid.not_none() && (!last_chg) {
-                    cash += last_lot_num * (mid - last_mid) * multiplier;
-                }
-                let out = cash;
-                // TODO(Teamon): how to handle the case when the contract has changed
-                // should we pass daily open price as another input?
-                // currently we just ignore the profit and loss in the first tick when there is a contract change
-
-                // addup the commision fee
-                if (lot_num != last_lot_num) || chg {
-                    if !chg {
-                        let lot_num_change = lot_num - last_lot_num;
-                        let (open_price, spread) = if lot_num_change > 0. {
-                            (ask, ask - mid)
-                        } else {
-                            (bid, mid - bid)
-                        };
-                        if let CommissionType::Percent = commission_type {
-                            cash -=
-                                lot_num_change.abs() * multiplier * (open_price * c_rate + spread);
-                        } else {
-                            cash -= lot_num_change.abs() * (c_rate + spread * multiplier);
-                        }
-                    } else {
-                        // for simple, assume spread is (bid - ask) / 2
-                        // otherwise we need the bid and ask of next hot future
-                        if let CommissionType::Percent = commission_type {
-                            cash -= last_lot_num.abs()
-                                * multiplier
-                                * (mid * c_rate + (ask - bid) * 0.5);
-                        } else {
-                            cash -= last_lot_num.abs() * (c_rate + (ask - bid) * 0.5 * multiplier);
-                        }
-                    };
-
-                    // update last lot num and last pos
-                    last_lot_num = lot_num;
-                }
-
-                last_mid = mid; // update last close
-                last_chg = chg;
-                out.into_cast::<T>()
-            })
-            .collect_trusted_vec1()
-        } else {
-            // ignore contract chg signal
-            // this should be faster than the above
-            izip!(signal_vec.titer(), bid_vec.titer(), ask_vec.titer(),)
-                .map(|(lot_num, bid, ask)| {
-                    if lot_num.is_none() || bid.is_none() || ask.is_none() {
-                        return cash.into_cast::<T>();
-                    } else if blowup && cash <= 0. {
-                        return 0_f64.into_cast::<T>();
-                    }
-                    let lot_num = lot_num.unwrap().f64();
-                    let bid = bid.unwrap().f64();
-                    let ask = ask.unwrap().f64();
-                    let mid = (bid + ask) * 0.5;
-
-                    // calculate the profit and loss of the current period
-                    if (last_lot_num != 0.) && last_mid.not_none() {
-                        cash += last_lot_num * (mid - last_mid) * multiplier;
-                    }
-                    let out = cash;
-                    // addup the commision fee
-                    if lot_num != last_lot_num {
-                        let lot_num_change = lot_num - last_lot_num;
-                        let (open_price, spread) = if lot_num_change > 0. {
-                            (ask, ask - mid)
-                        } else {
-                            (bid, mid - bid)
-                        };
-                        if let CommissionType::Percent = commission_type {
-                            cash -=
-                                lot_num_change.abs() * multiplier * (open_price * c_rate + spread);
-                        } else {
-                            cash -= lot_num_change.abs() * (c_rate + spread * multiplier);
-                        };
-                        // update last lot num and last pos
-                        last_lot_num = lot_num;
-                    }
+    run_tick_future_ret(
+        signal_vec,
+        bid_vec,
+        ask_vec,
+        contract_chg_signal_vec,
+        kwargs,
+        &mut (),
+    )
+    .into_iter()
+    .map(|(equity, _)| equity.into_cast::<T>())
+    .collect_trusted_vec1()
+}
 
-                    last_mid = mid; // update last close
-                    out.into_cast::<T>()
-                })
-                .collect_trusted_vec1()
-        }
-    }
+/// The same as [`calc_tick_future_ret`], but return a [`BacktestResult`].
+pub fn calc_tick_future_ret_result<T, V, VMask>(
+    signal_vec: &V,
+    bid_vec: &V,
+    ask_vec: &V,
+    contract_chg_signal_vec: Option<&VMask>,
+    kwargs: &TickFutureRetKwargs,
+) -> BacktestResult
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+{
+    let mut rec = ResultBuilder::new(signal_vec.len(), kwargs.multiplier);
+    run_tick_future_ret(
+        signal_vec,
+        bid_vec,
+        ask_vec,
+        contract_chg_signal_vec,
+        kwargs,
+        &mut rec,
+    );
+    rec.build()
 }
 
 #[cfg(test)]
@@ -367,5 +209,22 @@ mod tests {
             9990.91785, 9994.39705, 9996.39705, 9992.83615,
         ];
         assert_vec1d_equal_numeric(&res, &expect, Some(1e-7));
+        let detail = calc_tick_future_ret_result(
+            &signal_vec,
+            &bid_vec,
+            &ask_vec,
+            Some(&contract_chg_vec.opt()),
+            &kwargs,
+        );
+        assert_eq!(detail.equity, res);
+        // the position is closed at the contract change and reopened at the next tick
+        assert_eq!(
+            detail.lots,
+            vec![0., 1., 1., 2., 2., 0., 1., 0., -1., -1., 2., 2.]
+        );
+        assert_eq!(
+            detail.fills.iter().map(|f| f.idx).collect::<Vec<_>>(),
+            vec![1, 3, 5, 6, 7, 8, 10]
+        );
     }
 }
@@ -1,12 +1,18 @@
 use std::str::FromStr;
 
 use itertools::izip;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tevec::prelude::*;
 
-use super::{CommissionType, SignalType};
+use super::{
+    engine::{
+        run_engine, BidAsk, Blowup, Commission, FeeModel, FixedLots, Quote, Reopen, TickEngine,
+    },
+    result::{Recorder, ResultBuilder},
+    BacktestResult, CommissionType, SignalType,
+};
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct TickFutureRetFullKwargs {
     pub init_cash: usize,
     pub multiplier: f64,
@@ -32,10 +38,13 @@ impl Default for TickFutureRetFullKwargs {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Profit {
+    #[serde(with = "crate::serde_utils::nan_as_null")]
     pub unrealize: f64,
+    #[serde(with = "crate::serde_utils::nan_as_null")]
     pub realize: f64,
+    #[serde(with = "crate::serde_utils::nan_as_null")]
     pub open_price: f64,
 }
 
@@ -58,6 +67,17 @@ pub enum OpenPriceMethod {
     Last,
 }
 
+impl OpenPriceMethod {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OpenPriceMethod::Average => "average",
+            OpenPriceMethod::First => "first",
+            OpenPriceMethod::Last => "last",
+        }
+    }
+}
+
 impl FromStr for OpenPriceMethod {
     type Err = String;
 
@@ -83,6 +103,160 @@ impl<'de> Deserialize<'de> for OpenPriceMethod {
     }
 }
 
+impl Serialize for OpenPriceMethod {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl TickFutureRetFullKwargs {
+    /// engine of [`calc_tick_future_ret_full`], the signal is the number of lots
+    #[inline]
+    fn engine(&self) -> TickEngine<BidAsk, Commission, FixedLots> {
+        let blowup = if self.blowup {
+            Blowup::Negative
+        } else {
+            Blowup::Never
+        };
+        TickEngine::new(
+            BidAsk,
+            self.commission(),
+            FixedLots,
+            self.multiplier,
+            blowup,
+            Reopen::Held,
+            false,
+            self.init_cash as f64,
+        )
+    }
+
+    #[inline]
+    fn commission(&self) -> Commission {
+        Commission::new(self.commission_type, self.c_rate, self.multiplier)
+    }
+}
+
+/// Record the profit of each tick, the realized profit is charged with
+/// commission at the traded price.
+struct ProfitRecorder<'a> {
+    kwargs: &'a TickFutureRetFullKwargs,
+    fee: Commission,
+    lot_num: f64,
+    average_open_price: f64,
+    realize_profit: f64,
+    settled: Option<Profit>,
+    out: Vec<Profit>,
+}
+
+impl<'a> ProfitRecorder<'a> {
+    #[inline]
+    fn new(kwargs: &'a TickFutureRetFullKwargs, len: usize) -> Self {
+        Self {
+            kwargs,
+            fee: kwargs.commission(),
+            lot_num: 0.,
+            average_open_price: f64::NAN,
+            realize_profit: 0.,
+            settled: None,
+            out: Vec::with_capacity(len),
+        }
+    }
+}
+
+impl Recorder for ProfitRecorder<'_> {
+    fn fill(&mut self, _price: f64, open_price: f64, lot_num_change: f64) {
+        let multiplier = self.kwargs.multiplier;
+        let last_lot_num = self.lot_num;
+        let lot_num = last_lot_num + lot_num_change;
+        if last_lot_num == 0. {
+            self.average_open_price = open_price;
+        } else if lot_num == 0. {
+            self.realize_profit +=
+                (open_price - self.average_open_price) * last_lot_num * multiplier;
+            self.average_open_price = f64::NAN;
+        } else if lot_num.signum() != last_lot_num.signum() {
+            self.realize_profit +=
+                last_lot_num * (open_price - self.average_open_price) * multiplier;
+            self.average_open_price = open_price;
+        } else if last_lot_num.abs() > lot_num.abs() {
+            self.realize_profit += (open_price - self.average_open_price)
+                * (last_lot_num.abs() - lot_num.abs())
+                * multiplier;
+        } else if last_lot_num.abs() < lot_num.abs() {
+            self.average_open_price = match self.kwargs.open_price_method {
+                OpenPriceMethod::First => self.average_open_price,
+                OpenPriceMethod::Last => open_price,
+                OpenPriceMethod::Average => {
+                    (self.average_open_price * last_lot_num.abs()
+                        + open_price * lot_num_change.abs())
+                        / lot_num.abs()
+                },
+            }
+        } else {
+            panic!("implemention error");
+        };
+        self.realize_profit -= self.fee.commission(lot_num_change.abs(), open_price);
+        self.lot_num = lot_num;
+    }
+
+    fn roll(&mut self, _price: f64, open_price: f64, lots: f64) {
+        let last_lot_num = self.lot_num;
+        self.realize_profit +=
+            (open_price - self.average_open_price) * last_lot_num * self.kwargs.multiplier;
+        self.average_open_price = f64::NAN;
+        self.realize_profit -= self.fee.commission(last_lot_num.abs(), open_price);
+        self.lot_num = last_lot_num + lots;
+    }
+
+    #[inline]
+    fn settle(&mut self, equity: f64) {
+        let unrealize = equity - self.kwargs.init_cash as f64;
+        self.settled = Some((unrealize, self.realize_profit, self.average_open_price).into());
+    }
+
+    #[inline]
+    fn finish(&mut self, equity: f64, _mark: f64) {
+        // an invalid tick returns the cash itself
+        let profit = self
+            .settled
+            .take()
+            .unwrap_or_else(|| (equity, self.realize_profit, self.average_open_price).into());
+        self.out.push(profit);
+    }
+}
+
+/// run the engine with the recorder, the signal is the number of lots
+fn run_tick_future_ret_full<T, V, VMask, R>(
+    signal_vec: &V,
+    bid_vec: &V,
+    ask_vec: &V,
+    contract_chg_signal_vec: Option<&VMask>,
+    kwargs: &TickFutureRetFullKwargs,
+    rec: &mut R,
+) where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+    R: Recorder,
+{
+    if let SignalType::Percent = kwargs.signal_type {
+        todo!()
+    }
+    let quotes = izip!(signal_vec.titer(), bid_vec.titer(), ask_vec.titer())
+        .map(|(lot_num, bid, ask)| Quote::new(lot_num, bid, ask));
+    run_engine(
+        &mut kwargs.engine(),
+        quotes,
+        contract_chg_signal_vec.map(|c| c.titer()),
+        rec,
+    );
+}
+
 pub fn calc_tick_future_ret_full<T, V, VMask>(
     signal_vec: &V,
     bid_vec: &V,
@@ -99,208 +273,80 @@ where
     if signal_vec.is_empty() {
         return Vec::empty();
     }
-    let init_cash = kwargs.init_cash as f64;
-    let mut cash = init_cash;
-    let mut last_lot_num = 0.;
-    let mut last_chg = false;
-    let mut last_mid = f64::NAN;
-    let mut average_open_price = f64::NAN;
-    let mut realize_profit = 0.;
-    let blowup = kwargs.blowup;
-    let multiplier = kwargs.multiplier;
-    let commission_type = kwargs.commission_type;
-    let c_rate = kwargs.c_rate;
-    if let SignalType::Absolute = kwargs.signal_type {
-        // absolute signal type
-        if let Some(contract_chg_signal_vec) = contract_chg_signal_vec {
-            izip!(
-                signal_vec.titer(),
-                bid_vec.titer(),
-                ask_vec.titer(),
-                contract_chg_signal_vec.titer(),
-            )
-            .map(|(lot_num, bid, ask, chg)| {
-                if lot_num.is_none() || bid.is_none() || ask.is_none() {
-                    return (cash, realize_profit, average_open_price).into();
-                } else if blowup && cash < 0. {
-                    return (0., realize_profit, average_open_price).into();
-                }
-                let lot_num = lot_num.unwrap().f64();
-                let bid = bid.unwrap().f64();
-                let ask = ask.unwrap().f64();
-                let chg = chg.unwrap_or(false);
-                let mid = (bid + ask) * 0.5;
-
-                if last_chg && last_lot_num != 0. {
-                    average_open_price = if last_lot_num > 0. { ask } else { bid };
-                    if let CommissionType::Percent = commission_type {
-                        cash -=
-                            last_lot_num.abs() * multiplier * (mid * c_rate + (ask - bid) * 0.5);
-                        realize_profit -=
-                            last_lot_num.abs() * average_open_price * c_rate * multiplier;
-                    } else {
-                        cash -= last_lot_num.abs() * (c_rate + (ask - bid) * 0.5 * multiplier);
-                        realize_profit -= last_lot_num.abs() * c_rate;
-                    }
-                }
-                // calculate the profit and loss of the current period
-                // we should not calculate the profit in this way if the contract has changed
-                if (last_lot_num != 0.) && last_mid.not_none() && (!last_chg) {
-                    cash += last_lot_num * (mid - last_mid) * multiplier;
-                }
-                let out = (cash - init_cash, realize_profit, average_open_price).into();
-                // TODO(Teamon): how to handle the case when the contract has changed
-                // should we pass daily open price as another input?
-                // currently we just ignore the profit and loss in the first tick when there is a contract change
-
-                // addup the commision fee
-                if (lot_num != last_lot_num) || chg {
-                    if !chg {
-                        let lot_num_change = lot_num - last_lot_num;
-                        let (open_price, spread) = if lot_num_change > 0. {
-                            (ask, ask - mid)
-                        } else {
-                            (bid, mid - bid)
-                        };
-                        if last_lot_num == 0. {
-                            average_open_price = open_price;
-                        } else if lot_num == 0. {
-                            realize_profit +=
-                                (open_price - average_open_price) * last_lot_num * multiplier;
-                            average_open_price = f64::NAN;
-                        } else if lot_num.signum() != last_lot_num.signum() {
-                            realize_profit +=
-                                last_lot_num * (open_price - average_open_price) * multiplier;
-                            average_open_price = open_price;
-                        } else if last_lot_num.abs() > lot_num.abs() {
-                            realize_profit += (open_price - average_open_price)
-                                * (last_lot_num.abs() - lot_num.abs())
-                                * multiplier;
-                        } else if last_lot_num.abs() < lot_num.abs() {
-                            average_open_price = match kwargs.open_price_method {
-                                OpenPriceMethod::First => average_open_price,
-                                OpenPriceMethod::Last => open_price,
-                                OpenPriceMethod::Average => {
-                                    (average_open_price * last_lot_num.abs()
-                                        + open_price * lot_num_change.abs())
-                                        / lot_num.abs()
-                                },
-                            }
-                        } else {
-                            panic!("implemention error");
-                        };
-                        if let CommissionType::Percent = commission_type {
-                            cash -=
-                                lot_num_change.abs() * multiplier * (open_price * c_rate + spread);
-                            realize_profit -=
-                                lot_num_change.abs() * open_price * c_rate * multiplier;
-                        } else {
-                            cash -= lot_num_change.abs() * (c_rate + spread * multiplier);
-                            realize_profit -= lot_num_change.abs() * c_rate;
-                        }
-                    } else {
-                        let open_price = if last_lot_num > 0. { bid } else { ask };
-                        realize_profit +=
-                            (open_price - average_open_price) * last_lot_num * multiplier;
-                        average_open_price = f64::NAN;
-                        // for simple, assume spread is (bid - ask) / 2
-                        // otherwise we need the bid and ask of next hot future
-                        if let CommissionType::Percent = commission_type {
-                            cash -= last_lot_num.abs()
-                                * multiplier
-                                * (mid * c_rate + (ask - bid) * 0.5);
-                            realize_profit -= last_lot_num.abs() * open_price * c_rate * multiplier;
-                        } else {
-                            cash -= last_lot_num.abs() * (c_rate + (ask - bid) * 0.5 * multiplier);
-                            realize_profit -= last_lot_num.abs() * c_rate;
-                        }
-                    };
-
-                    // update last lot num and last pos
-                    last_lot_num = lot_num;
-                }
-
-                last_mid = mid; // update last close
-                last_chg = chg;
-                out
-            })
-            .collect_trusted_vec1()
-        } else {
-            // ignore contract chg signal
-            // this should be faster than the above
-            izip!(signal_vec.titer(), bid_vec.titer(), ask_vec.titer(),)
-                .map(|(lot_num, bid, ask)| {
-                    if lot_num.is_none() || bid.is_none() || ask.is_none() {
-                        return (cash, realize_profit, average_open_price).into();
-                    } else if blowup && cash < 0. {
-                        return (0., realize_profit, average_open_price).into();
-                    }
-                    let lot_num = lot_num.unwrap().f64();
-                    let bid = bid.unwrap().f64();
-                    let ask = ask.unwrap().f64();
-                    let mid = (bid + ask) * 0.5;
+    let mut rec = ProfitRecorder::new(kwargs, signal_vec.len());
+    run_tick_future_ret_full(
+        signal_vec,
+        bid_vec,
+        ask_vec,
+        contract_chg_signal_vec,
+        kwargs,
+        &mut rec,
+    );
+    rec.out
+}
 
-                    // calculate the profit and loss of the current period
-                    if (last_lot_num != 0.) && last_mid.not_none() {
-                        cash += last_lot_num * (mid - last_mid) * multiplier;
-                    }
-                    let out = (cash - init_cash, realize_profit, average_open_price).into();
-                    // addup the commision fee
-                    if lot_num != last_lot_num {
-                        let lot_num_change = lot_num - last_lot_num;
-                        let (open_price, spread) = if lot_num_change > 0. {
-                            (ask, ask - mid)
-                        } else {
-                            (bid, mid - bid)
-                        };
-                        if last_lot_num == 0. {
-                            average_open_price = open_price;
-                        } else if lot_num == 0. {
-                            realize_profit +=
-                                (open_price - average_open_price) * last_lot_num * multiplier;
-                            average_open_price = f64::NAN;
-                        } else if lot_num.signum() != last_lot_num.signum() {
-                            realize_profit +=
-                                last_lot_num * (open_price - average_open_price) * multiplier;
-                            average_open_price = open_price;
-                        } else if last_lot_num.abs() > lot_num.abs() {
-                            realize_profit += (open_price - average_open_price)
-                                * (last_lot_num.abs() - lot_num.abs())
-                                * multiplier;
-                        } else if last_lot_num.abs() < lot_num.abs() {
-                            average_open_price = match kwargs.open_price_method {
-                                OpenPriceMethod::First => average_open_price,
-                                OpenPriceMethod::Last => open_price,
-                                OpenPriceMethod::Average => {
-                                    (average_open_price * last_lot_num.abs()
-                                        + open_price * lot_num_change.abs())
-                                        / lot_num.abs()
-                                },
-                            }
-                        } else {
-                            panic!("implemention error");
-                        };
-                        if let CommissionType::Percent = commission_type {
-                            cash -=
-                                lot_num_change.abs() * multiplier * (open_price * c_rate + spread);
-                            realize_profit -=
-                                lot_num_change.abs() * open_price * c_rate * multiplier;
-                        } else {
-                            cash -= lot_num_change.abs() * (c_rate + spread * multiplier);
-                            realize_profit -= lot_num_change.abs() * c_rate;
-                        }
-                        // update last lot num and last pos
-                        last_lot_num = lot_num;
-                    }
+/// The same as [`calc_tick_future_ret_full`], but return a [`BacktestResult`]
+/// with the lots, costs and fills of each tick instead of the profits.
+pub fn calc_tick_future_ret_full_result<T, V, VMask>(
+    signal_vec: &V,
+    bid_vec: &V,
+    ask_vec: &V,
+    contract_chg_signal_vec: Option<&VMask>,
+    kwargs: &TickFutureRetFullKwargs,
+) -> BacktestResult
+where
+    T: IsNone,
+    T::Inner: Number,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+{
+    let mut rec = ResultBuilder::new(signal_vec.len(), kwargs.multiplier);
+    run_tick_future_ret_full(
+        signal_vec,
+        bid_vec,
+        ask_vec,
+        contract_chg_signal_vec,
+        kwargs,
+        &mut rec,
+    );
+    rec.build()
+}
 
-                    last_mid = mid; // update last close
-                    out
-                })
-                .collect_trusted_vec1()
-        }
-    } else {
-        todo!()
-    }
+#[cfg(feature = "polars")]
+#[allow(clippy::useless_conversion)] // needed for support polars version below 0.43
+pub fn profit_vec_to_series(trades: &[Profit]) -> tevec::export::polars::prelude::Series {
+    use tevec::{
+        export::polars::prelude::*,
+        prelude::{IsNone, Vec1Collect},
+    };
+    let unrealized_profit: Float64Chunked = trades
+        .iter()
+        .map(|t| t.unrealize.to_opt())
+        .collect_trusted_vec1();
+    let realized_profit: Float64Chunked = trades
+        .iter()
+        .map(|t| t.realize.to_opt())
+        .collect_trusted_vec1();
+    let open_price: Float64Chunked = trades
+        .iter()
+        .map(|t| t.open_price.to_opt())
+        .collect_trusted_vec1();
+    let res: StructChunked = StructChunked::from_series(
+        "profit".into(),
+        open_price.len(),
+        [
+            unrealized_profit
+                .into_series()
+                .with_name("unrealized_profit".into()),
+            realized_profit
+                .into_series()
+                .with_name("realized_profit".into()),
+            open_price.into_series().with_name("open_price".into()),
+        ]
+        .iter(),
+    )
+    .unwrap();
+    res.into_series()
 }
 
 #[cfg(test)]
@@ -368,42 +414,28 @@ mod tests {
             &expect_unrealize_profit.collect::<Vec<_>>(),
             Some(1e-7),
         );
+        let detail = calc_tick_future_ret_full_result(
+            &signal_vec,
+            &bid_vec,
+            &ask_vec,
+            Some(&contract_chg_vec.opt()),
+            &kwargs,
+        );
+        // init_cash is 0, so the equity is the unrealize of the profits
+        assert_eq!(
+            detail.equity,
+            res.iter().map(|p| p.unrealize).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            detail.lots,
+            vec![0., 1., 1., 2., 2., 0., 1., 0., -1., -1., 2., 2.]
+        );
+        assert_eq!(
+            detail.fills.iter().map(|f| f.idx).collect::<Vec<_>>(),
+            vec![1, 3, 5, 6, 7, 8, 10]
+        );
+        // commission of the first fill at the ask price
+        assert!((detail.fee[1] - 0.0103).abs() < 1e-10);
+        assert!((detail.slippage[1] - 0.5).abs() < 1e-10);
     }
 }
-
-#[cfg(feature = "polars")]
-#[allow(clippy::useless_conversion)] // needed for support polars version below 0.43
-pub fn profit_vec_to_series(trades: &[Profit]) -> tevec::export::polars::prelude::Series {
-    use tevec::{
-        export::polars::prelude::*,
-        prelude::{IsNone, Vec1Collect},
-    };
-    let unrealized_profit: Float64Chunked = trades
-        .iter()
-        .map(|t| t.unrealize.to_opt())
-        .collect_trusted_vec1();
-    let realized_profit: Float64Chunked = trades
-        .iter()
-        .map(|t| t.realize.to_opt())
-        .collect_trusted_vec1();
-    let open_price: Float64Chunked = trades
-        .iter()
-        .map(|t| t.open_price.to_opt())
-        .collect_trusted_vec1();
-    let res: StructChunked = StructChunked::from_series(
-        "profit".into(),
-        open_price.len(),
-        [
-            unrealized_profit
-                .into_series()
-                .with_name("unrealized_profit".into()),
-            realized_profit
-                .into_series()
-                .with_name("realized_profit".into()),
-            open_price.into_series().with_name("open_price".into()),
-        ]
-        .iter(),
-    )
-    .unwrap();
-    res.into_series()
-}
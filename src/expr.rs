@@ -0,0 +1,168 @@
+//! Strategies as polars expressions.
+//!
+//! The expressions are evaluated group-wise, so they can be used in
+//! `group_by().agg()` or with `.over("symbol")` to run a strategy for each
+//! symbol separately. Filters are not supported in expressions.
+//!
+//! These are Rust-side expressions built by [`Expr::apply`] for lazy queries
+//! in Rust. In python the same strategies are polars expression plugins
+//! exported by the extension module of the `python` feature.
+use std::sync::Arc;
+
+use serde_json::Value;
+use tevec::{
+    export::polars::prelude::*,
+    prelude::{terr, TResult},
+};
+
+use crate::{
+    registry::{StrategyData, StrategyRegistry},
+    *,
+};
+
+#[inline]
+pub(crate) fn column_to_vec(col: &Column) -> PolarsResult<Vec<f64>> {
+    Ok(col
+        .cast(&DataType::Float64)?
+        .f64()?
+        .into_iter()
+        .map(|v| v.unwrap_or(f64::NAN))
+        .collect())
+}
+
+#[inline]
+pub(crate) fn to_polars_err(e: tevec::prelude::TError) -> PolarsError {
+    PolarsError::ComputeError(e.to_string().into())
+}
+
+/// Apply a strategy on the factor expression group-wise.
+fn strategy_apply<F>(fac: Expr, f: F) -> Expr
+where
+    F: Fn(&Vec<f64>) -> TResult<Vec<f64>> + Send + Sync + 'static,
+{
+    fac.apply(
+        move |col: Column| {
+            let out = f(&column_to_vec(&col)?).map_err(to_polars_err)?;
+            Ok(Some(
+                Float64Chunked::from_vec(col.name().clone(), out).into_column(),
+            ))
+        },
+        GetOutput::from_type(DataType::Float64),
+    )
+}
+
+/// Strategy expression by the name in [`StrategyRegistry`] and kwargs in
+/// json, the factor is also used as close price by strategies trading on
+/// price, e.g. `martingale` and `grid`.
+pub fn strategy_expr(fac: Expr, name: &str, kwargs: Value) -> TResult<Expr> {
+    let registry = Arc::new(StrategyRegistry::default());
+    if !registry.contains(name) {
+        return Err(terr!("strategy {} is not registered", name));
+    }
+    let name = name.to_string();
+    Ok(strategy_apply(fac, move |fac| {
+        registry.run(&name, &StrategyData::new(fac.clone()), &kwargs)
+    }))
+}
+
+/// Strategy expression with kwargs in a json string.
+#[inline]
+pub fn strategy_expr_json(fac: Expr, name: &str, kwargs: &str) -> TResult<Expr> {
+    let kwargs: Value =
+        serde_json::from_str(kwargs).map_err(|e| terr!("invalid json kwargs: {}", e))?;
+    strategy_expr(fac, name, kwargs)
+}
+
+macro_rules! strategy_expr_impl {
+    ($(#[$meta: meta])* $name: ident, $func: ident, $kwargs: ty) => {
+        $(#[$meta])*
+        pub fn $name(fac: Expr, kwargs: &$kwargs) -> Expr {
+            let kwargs = kwargs.clone();
+            strategy_apply(fac, move |fac| {
+                let filter: Option<&StrategyFilter<Vec<Option<bool>>>> = None;
+                $func(fac, filter, &kwargs)
+            })
+        }
+    };
+    // strategies that never fail
+    ($(#[$meta: meta])* $name: ident, $func: ident, $kwargs: ty, infallible) => {
+        $(#[$meta])*
+        pub fn $name(fac: Expr, kwargs: &$kwargs) -> Expr {
+            let kwargs = kwargs.clone();
+            strategy_apply(fac, move |fac| {
+                let filter: Option<&StrategyFilter<Vec<Option<bool>>>> = None;
+                Ok($func(fac, filter, &kwargs))
+            })
+        }
+    };
+}
+
+strategy_expr_impl!(
+    /// bollinger band strategy, see [`boll`]
+    boll_expr,
+    boll,
+    BollKwargs,
+    infallible
+);
+
+strategy_expr_impl!(auto_boll_expr, auto_boll, AutoBollKwargs);
+strategy_expr_impl!(delay_boll_expr, delay_boll, DelayBollKwargs);
+strategy_expr_impl!(fix_time_expr, fix_time, FixTimeKwargs);
+strategy_expr_impl!(prob_threshold_expr, prob_threshold, ProbThresholdKwargs);
+strategy_expr_impl!(
+    /// martingale on close price
+    martingale_expr,
+    martingale,
+    MartingaleKwargs
+);
+strategy_expr_impl!(auto_tangqian_expr, auto_tangqian, AutoTangQiAnKwargs);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strategy_expr() -> TResult<()> {
+        let fac = vec![
+            10., 11., 11.9, 10., 11., 12., 10., 11., 12., 13., 14., 10., 7., 5., 4., 3., 4., 4.,
+            3., 2.,
+        ];
+        let n = fac.len();
+        let kwargs = BollKwargs {
+            params: (4, 1.0, 0., None),
+            delay_open: false,
+            ..Default::default()
+        };
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let expect: Vec<f64> = boll(&fac, filter.as_ref(), &kwargs);
+        // two symbols with the same factor
+        let symbol: Vec<&str> = vec!["a"; n].into_iter().chain(vec!["b"; n]).collect();
+        let all_fac: Vec<f64> = fac.iter().chain(fac.iter()).copied().collect();
+        let df = df!("symbol" => symbol, "fac" => all_fac).unwrap();
+        let out = df
+            .lazy()
+            .select([
+                boll_expr(col("fac"), &kwargs)
+                    .over([col("symbol")])
+                    .alias("typed"),
+                strategy_expr_json(
+                    col("fac"),
+                    "boll",
+                    r#"{"params": [4, 1.0, 0.0, null], "min_periods": null, "delay_open": false,
+                        "zscore": true, "long_signal": 1.0, "short_signal": -1.0,
+                        "close_signal": 0.0}"#,
+                )?
+                .over([col("symbol")])
+                .alias("json"),
+            ])
+            .collect()
+            .unwrap();
+        let typed: Vec<f64> = out["typed"].f64().unwrap().into_no_null_iter().collect();
+        let json: Vec<f64> = out["json"].f64().unwrap().into_no_null_iter().collect();
+        assert_eq!(&typed[..n], &expect[..]);
+        assert_eq!(&typed[n..], &expect[..]);
+        assert_eq!(typed, json);
+        assert!(strategy_expr(col("fac"), "unknown", Value::Null).is_err());
+        Ok(())
+    }
+}
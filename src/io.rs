@@ -0,0 +1,633 @@
+//! Read backtest inputs and write backtest outputs.
+//!
+//! Csv is always supported and doesn't need polars, parquet requires the
+//! `parquet` feature. The file format is decided by the extension of the
+//! path. Missing values are written as empty fields in csv and read back as
+//! `NaN` (or `NaT` for time). Csv fields with commas, quotes or line breaks
+//! are quoted.
+//!
+//! Column schemas:
+//!
+//! * bars: `time, open, high, low, close[, volume]`
+//! * ticks: `time, bid, ask`
+//! * depth: `time, ask_price1..5, bid_price1..5, ask_volume1..5, bid_volume1..5`
+//! * trades: `time, side, price, num`
+//! * profits: `time, unrealize, realize, open_price`
+//! * signal: `time, signal`
+//! * equity: `time, equity`
+use std::{fs, path::Path};
+
+use itertools::Itertools;
+use tevec::prelude::*;
+
+use crate::{equity::Profit, OrderBook, OrderBookLevel, Trade};
+
+/// Ohlc bars.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bars {
+    pub time: Vec<DateTime>,
+    pub open: Vec<f64>,
+    pub high: Vec<f64>,
+    pub low: Vec<f64>,
+    pub close: Vec<f64>,
+    /// `NaN` if there is no volume column
+    pub volume: Vec<f64>,
+}
+
+/// Best bid and ask prices.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ticks {
+    pub time: Vec<DateTime>,
+    pub bid: Vec<f64>,
+    pub ask: Vec<f64>,
+}
+
+/// Five levels of depth.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Depth {
+    pub time: Vec<DateTime>,
+    pub books: Vec<OrderBook>,
+}
+
+enum Format {
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> TResult<Self> {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("csv") => Ok(Format::Csv),
+            #[cfg(feature = "parquet")]
+            Some("parquet") | Some("pq") => Ok(Format::Parquet),
+            _ => tbail!("unsupported file format: {}", path.display()),
+        }
+    }
+}
+
+/// A column to write.
+enum ColumnData<'a> {
+    Time(&'a [DateTime]),
+    F64(Vec<f64>),
+    Str(Vec<&'a str>),
+}
+
+impl ColumnData<'_> {
+    #[inline]
+    fn len(&self) -> usize {
+        match self {
+            ColumnData::Time(v) => v.len(),
+            ColumnData::F64(v) => v.len(),
+            ColumnData::Str(v) => v.len(),
+        }
+    }
+
+    #[inline]
+    fn csv_field(&self, i: usize) -> String {
+        let s = match self {
+            ColumnData::Time(v) => {
+                if v[i].is_nat() {
+                    String::new()
+                } else {
+                    v[i].strftime(None)
+                }
+            },
+            ColumnData::F64(v) => {
+                if v[i].is_nan() {
+                    String::new()
+                } else {
+                    v[i].to_string()
+                }
+            },
+            ColumnData::Str(v) => v[i].to_string(),
+        };
+        quote_csv_field(s)
+    }
+}
+
+/// quote a csv field if it has a comma, a quote or a line break
+fn quote_csv_field(s: String) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s
+    }
+}
+
+/// Split csv text into rows of fields. Quoted fields may contain commas,
+/// line breaks and quotes escaped as `""`, unquoted fields are trimmed and
+/// blank lines are skipped.
+fn parse_csv(s: &str) -> TResult<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let (mut in_quote, mut quoted) = (false, false);
+    let end_field = |field: &mut String, quoted: &mut bool| -> String {
+        let f = std::mem::take(field);
+        if std::mem::take(quoted) {
+            f
+        } else {
+            f.trim().to_string()
+        }
+    };
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quote {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                },
+                '"' => in_quote = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => (in_quote, quoted) = (true, true),
+            ',' => row.push(end_field(&mut field, &mut quoted)),
+            '\n' => {
+                row.push(end_field(&mut field, &mut quoted));
+                rows.push(std::mem::take(&mut row));
+            },
+            '\r' => {},
+            _ => field.push(c),
+        }
+    }
+    tensure!(!in_quote, "unterminated quoted csv field");
+    if !row.is_empty() || !field.is_empty() || quoted {
+        row.push(end_field(&mut field, &mut quoted));
+        rows.push(row);
+    }
+    rows.retain(|r| !(r.len() == 1 && r[0].is_empty()));
+    Ok(rows)
+}
+
+/// A table read by [`read_table`].
+pub trait Table {
+    /// None if the column does not exist, missing values are `NaN`
+    fn f64_column(&self, name: &str) -> TResult<Option<Vec<f64>>>;
+
+    /// None if the column does not exist, missing values are None
+    fn bool_column(&self, name: &str) -> TResult<Option<Vec<Option<bool>>>>;
+
+    /// datetime strings or integer timestamps in nanoseconds
+    fn time_column(&self, name: &str) -> TResult<Vec<DateTime>>;
+
+    #[inline]
+    fn required(&self, name: &str) -> TResult<Vec<f64>> {
+        self.f64_column(name)?
+            .ok_or_else(|| terr!("column {} is not found", name))
+    }
+}
+
+struct CsvTable {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl CsvTable {
+    fn read(path: &Path) -> TResult<Self> {
+        let s = fs::read_to_string(path)
+            .map_err(|e| terr!("failed to read {}: {}", path.display(), e))?;
+        let mut rows = parse_csv(&s)
+            .map_err(|e| terr!("failed to parse {}: {}", path.display(), e))?
+            .into_iter();
+        let header = rows
+            .next()
+            .ok_or_else(|| terr!("{} is empty", path.display()))?;
+        let rows = rows.collect::<Vec<_>>();
+        for (i, row) in rows.iter().enumerate() {
+            tensure!(
+                row.len() == header.len(),
+                "row {} of {} has {} fields, expect {}",
+                i + 1,
+                path.display(),
+                row.len(),
+                header.len()
+            );
+        }
+        Ok(Self { header, rows })
+    }
+
+    #[inline]
+    fn index(&self, name: &str) -> Option<usize> {
+        self.header.iter().position(|h| h == name)
+    }
+}
+
+impl Table for CsvTable {
+    fn f64_column(&self, name: &str) -> TResult<Option<Vec<f64>>> {
+        let Some(idx) = self.index(name) else {
+            return Ok(None);
+        };
+        self.rows
+            .iter()
+            .map(|row| {
+                let s = row[idx].as_str();
+                if s.is_empty() || s.eq_ignore_ascii_case("null") {
+                    Ok(f64::NAN)
+                } else {
+                    s.parse::<f64>()
+                        .map_err(|_| terr!("invalid value {} in column {}", s, name))
+                }
+            })
+            .collect::<TResult<Vec<_>>>()
+            .map(Some)
+    }
+
+    fn bool_column(&self, name: &str) -> TResult<Option<Vec<Option<bool>>>> {
+        let Some(idx) = self.index(name) else {
+            return Ok(None);
+        };
+        self.rows
+            .iter()
+            .map(|row| match row[idx].to_ascii_lowercase().as_str() {
+                "" | "null" => Ok(None),
+                "true" | "1" => Ok(Some(true)),
+                "false" | "0" => Ok(Some(false)),
+                s => Err(terr!("invalid bool {} in column {}", s, name)),
+            })
+            .collect::<TResult<Vec<_>>>()
+            .map(Some)
+    }
+
+    fn time_column(&self, name: &str) -> TResult<Vec<DateTime>> {
+        let idx = self
+            .index(name)
+            .ok_or_else(|| terr!("column {} is not found", name))?;
+        self.rows
+            .iter()
+            .map(|row| {
+                let s = row[idx].as_str();
+                if s.is_empty() || s == "NaT" {
+                    Ok(DateTime::nat())
+                } else if let Ok(v) = s.parse::<i64>() {
+                    // integer timestamps are in nanoseconds
+                    Ok(DateTime::new(v))
+                } else {
+                    DateTime::parse(s, None)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet {
+    use std::{fs::File, path::Path};
+
+    use polars::prelude::*;
+    use tevec::prelude::{terr, DateTime, TResult};
+
+    use super::{ColumnData, Table};
+
+    #[inline]
+    fn to_terr(e: PolarsError) -> tevec::prelude::TError {
+        terr!("{}", e)
+    }
+
+    pub(super) fn read(path: &Path) -> TResult<DataFrame> {
+        let file =
+            File::open(path).map_err(|e| terr!("failed to read {}: {}", path.display(), e))?;
+        ParquetReader::new(file).finish().map_err(to_terr)
+    }
+
+    pub(super) fn write(path: &Path, columns: &[(&str, ColumnData)]) -> TResult<()> {
+        let columns = columns
+            .iter()
+            .map(|(name, data)| match data {
+                ColumnData::Time(v) => v
+                    .iter()
+                    .map(|t| t.into_opt_i64())
+                    .collect::<Int64Chunked>()
+                    .into_datetime(TimeUnit::Nanoseconds, None)
+                    .into_column()
+                    .with_name((*name).into()),
+                ColumnData::F64(v) => Column::new((*name).into(), v),
+                ColumnData::Str(v) => Column::new((*name).into(), v),
+            })
+            .collect::<Vec<_>>();
+        let mut df = DataFrame::new(columns).map_err(to_terr)?;
+        let file =
+            File::create(path).map_err(|e| terr!("failed to write {}: {}", path.display(), e))?;
+        ParquetWriter::new(file).finish(&mut df).map_err(to_terr)?;
+        Ok(())
+    }
+
+    impl Table for DataFrame {
+        fn f64_column(&self, name: &str) -> TResult<Option<Vec<f64>>> {
+            let Ok(col) = self.column(name) else {
+                return Ok(None);
+            };
+            let col = col.cast(&DataType::Float64).map_err(to_terr)?;
+            Ok(Some(
+                col.f64()
+                    .map_err(to_terr)?
+                    .into_iter()
+                    .map(|v| v.unwrap_or(f64::NAN))
+                    .collect(),
+            ))
+        }
+
+        fn bool_column(&self, name: &str) -> TResult<Option<Vec<Option<bool>>>> {
+            let Ok(col) = self.column(name) else {
+                return Ok(None);
+            };
+            let col = col.cast(&DataType::Boolean).map_err(to_terr)?;
+            Ok(Some(col.bool().map_err(to_terr)?.into_iter().collect()))
+        }
+
+        fn time_column(&self, name: &str) -> TResult<Vec<DateTime>> {
+            let col = self.column(name).map_err(to_terr)?;
+            if let DataType::String = col.dtype() {
+                return col
+                    .str()
+                    .map_err(to_terr)?
+                    .into_iter()
+                    .map(|s| s.map_or(Ok(DateTime::nat()), |s| DateTime::parse(s, None)))
+                    .collect();
+            }
+            let col = col
+                .cast(&DataType::Datetime(TimeUnit::Nanoseconds, None))
+                .and_then(|c| c.cast(&DataType::Int64))
+                .map_err(to_terr)?;
+            Ok(col
+                .i64()
+                .map_err(to_terr)?
+                .into_iter()
+                .map(DateTime::from_opt_i64)
+                .collect())
+        }
+    }
+}
+
+/// Read a csv or parquet table to get columns that are not covered by the
+/// readers of bars, ticks and depth.
+pub fn read_table<P: AsRef<Path>>(path: P) -> TResult<Box<dyn Table>> {
+    let path = path.as_ref();
+    match Format::from_path(path)? {
+        Format::Csv => Ok(Box::new(CsvTable::read(path)?)),
+        #[cfg(feature = "parquet")]
+        Format::Parquet => Ok(Box::new(parquet::read(path)?)),
+    }
+}
+
+fn write_table(path: &Path, columns: &[(&str, ColumnData)]) -> TResult<()> {
+    let len = columns.first().map(|(_, c)| c.len()).unwrap_or(0);
+    for (name, c) in columns {
+        tensure!(
+            c.len() == len,
+            "length of column {} should be equal to {}",
+            name,
+            len
+        );
+    }
+    match Format::from_path(path)? {
+        Format::Csv => {
+            let mut s = columns
+                .iter()
+                .map(|(name, _)| quote_csv_field(name.to_string()))
+                .join(",");
+            s.push('\n');
+            for i in 0..len {
+                s.push_str(&columns.iter().map(|(_, c)| c.csv_field(i)).join(","));
+                s.push('\n');
+            }
+            fs::write(path, s).map_err(|e| terr!("failed to write {}: {}", path.display(), e))
+        },
+        #[cfg(feature = "parquet")]
+        Format::Parquet => parquet::write(path, columns),
+    }
+}
+
+/// Read ohlc bars, the `volume` column is optional.
+pub fn read_bars<P: AsRef<Path>>(path: P) -> TResult<Bars> {
+    let table = read_table(path.as_ref())?;
+    let time = table.time_column("time")?;
+    let volume = table
+        .f64_column("volume")?
+        .unwrap_or_else(|| vec![f64::NAN; time.len()]);
+    Ok(Bars {
+        open: table.required("open")?,
+        high: table.required("high")?,
+        low: table.required("low")?,
+        close: table.required("close")?,
+        volume,
+        time,
+    })
+}
+
+/// Read best bid and ask ticks.
+pub fn read_ticks<P: AsRef<Path>>(path: P) -> TResult<Ticks> {
+    let table = read_table(path.as_ref())?;
+    Ok(Ticks {
+        time: table.time_column("time")?,
+        bid: table.required("bid")?,
+        ask: table.required("ask")?,
+    })
+}
+
+/// Read five levels of depth, missing levels are filled with `NaN`.
+pub fn read_depth<P: AsRef<Path>>(path: P) -> TResult<Depth> {
+    let table = read_table(path.as_ref())?;
+    let time = table.time_column("time")?;
+    let len = time.len();
+    let mut books = vec![OrderBook::default(); len];
+    for level in 1..=5 {
+        let col = |name: &str| -> TResult<Vec<f64>> {
+            Ok(table
+                .f64_column(&format!("{}{}", name, level))?
+                .unwrap_or_else(|| vec![f64::NAN; len]))
+        };
+        let (ask_price, bid_price) = (col("ask_price")?, col("bid_price")?);
+        let (ask_volume, bid_volume) = (col("ask_volume")?, col("bid_volume")?);
+        for (i, book) in books.iter_mut().enumerate() {
+            let l = OrderBookLevel::new(ask_price[i], bid_price[i], ask_volume[i], bid_volume[i]);
+            match level {
+                1 => book.level1 = l,
+                2 => book.level2 = l,
+                3 => book.level3 = l,
+                4 => book.level4 = l,
+                _ => book.level5 = l,
+            }
+        }
+    }
+    Ok(Depth { time, books })
+}
+
+/// Write trades.
+pub fn write_trades<P: AsRef<Path>>(path: P, trades: &[Trade]) -> TResult<()> {
+    let time = trades.iter().map(|t| t.time).collect::<Vec<_>>();
+    write_table(
+        path.as_ref(),
+        &[
+            ("time", ColumnData::Time(&time)),
+            (
+                "side",
+                ColumnData::Str(trades.iter().map(|t| t.side.as_str()).collect()),
+            ),
+            (
+                "price",
+                ColumnData::F64(trades.iter().map(|t| t.price).collect()),
+            ),
+            (
+                "num",
+                ColumnData::F64(trades.iter().map(|t| t.num).collect()),
+            ),
+        ],
+    )
+}
+
+/// Write profits of [`calc_tick_future_ret_full`](crate::equity::calc_tick_future_ret_full).
+pub fn write_profits<P: AsRef<Path>>(
+    path: P,
+    time: &[DateTime],
+    profits: &[Profit],
+) -> TResult<()> {
+    write_table(
+        path.as_ref(),
+        &[
+            ("time", ColumnData::Time(time)),
+            (
+                "unrealize",
+                ColumnData::F64(profits.iter().map(|p| p.unrealize).collect()),
+            ),
+            (
+                "realize",
+                ColumnData::F64(profits.iter().map(|p| p.realize).collect()),
+            ),
+            (
+                "open_price",
+                ColumnData::F64(profits.iter().map(|p| p.open_price).collect()),
+            ),
+        ],
+    )
+}
+
+/// Write a signal.
+pub fn write_signal<P: AsRef<Path>>(path: P, time: &[DateTime], signal: &[f64]) -> TResult<()> {
+    write_table(
+        path.as_ref(),
+        &[
+            ("time", ColumnData::Time(time)),
+            ("signal", ColumnData::F64(signal.to_vec())),
+        ],
+    )
+}
+
+/// Write an equity curve.
+pub fn write_equity<P: AsRef<Path>>(path: P, time: &[DateTime], equity: &[f64]) -> TResult<()> {
+    write_table(
+        path.as_ref(),
+        &[
+            ("time", ColumnData::Time(time)),
+            ("equity", ColumnData::F64(equity.to_vec())),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::TradeSide;
+
+    #[test]
+    fn test_io() -> TResult<()> {
+        let dir = temp_dir().join(format!("tea_strategy_io_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let bar_path = dir.join("bars.csv");
+        fs::write(
+            &bar_path,
+            "time,open,high,low,close\n\
+             2024-01-02 09:00:00,10,11,9.5,10.5\n\
+             2024-01-02 09:01:00,10.5,,10,10.2\n",
+        )
+        .unwrap();
+        let bars = read_bars(&bar_path)?;
+        assert_eq!(bars.close, vec![10.5, 10.2]);
+        assert!(bars.high[1].is_nan() && bars.volume[0].is_nan());
+        assert_eq!(bars.time[1].minute(), Some(1));
+
+        let depth_path = dir.join("depth.csv");
+        fs::write(
+            &depth_path,
+            "time,ask_price1,bid_price1,ask_volume1,bid_volume1,ask_price2,bid_price2\n\
+             2024-01-02 09:00:00,10.1,10,5,6,10.2,9.9\n",
+        )
+        .unwrap();
+        let depth = read_depth(&depth_path)?;
+        assert_eq!(
+            depth.books[0].level1,
+            OrderBookLevel::new(10.1, 10., 5., 6.)
+        );
+        assert_eq!(depth.books[0].level2, (10.2, 9.9).into());
+        assert!(depth.books[0].level5.ask_price.is_nan());
+
+        // round trip of outputs
+        let trades = vec![
+            Trade::new(bars.time[0], TradeSide::Buy, 10., 1.),
+            Trade::new(bars.time[1], TradeSide::Sell, f64::NAN, 1.),
+        ];
+        let trade_path = dir.join("trades.csv");
+        write_trades(&trade_path, &trades)?;
+        let table = CsvTable::read(&trade_path)?;
+        assert_eq!(table.header, vec!["time", "side", "price", "num"]);
+        assert_eq!(table.time_column("time")?, bars.time);
+        assert!(table.required("price")?[1].is_nan());
+        let equity_path = dir.join("equity.csv");
+        write_equity(&equity_path, &bars.time, &[1., 1.5])?;
+        assert_eq!(
+            CsvTable::read(&equity_path)?.required("equity")?,
+            vec![1., 1.5]
+        );
+        // string fields are quoted on write
+        let path = dir.join("quoted.csv");
+        write_table(
+            &path,
+            &[("note", ColumnData::Str(vec!["a,b", "say \"hi\"", "plain"]))],
+        )?;
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "note\n\"a,b\"\n\"say \"\"hi\"\"\"\nplain\n"
+        );
+        assert_eq!(
+            CsvTable::read(&path)?.rows,
+            vec![vec!["a,b"], vec!["say \"hi\""], vec!["plain"]]
+        );
+        assert!(write_equity(&equity_path, &bars.time, &[1.]).is_err());
+        assert!(read_ticks(&bar_path).is_err());
+        // quoted fields, integer timestamps in nanoseconds and bool columns
+        let tick_path = dir.join("ticks.csv");
+        fs::write(
+            &tick_path,
+            "time,bid,ask,note,chg\r\n\
+             1704186000000000000,10,10.1,\"open, \"\"auction\"\"\nbell\",true\r\n\
+             1704186060000000000,10.1,,,\r\n",
+        )
+        .unwrap();
+        let ticks = read_ticks(&tick_path)?;
+        assert_eq!(ticks.time[1].minute(), Some(1));
+        assert_eq!(ticks.bid, vec![10., 10.1]);
+        assert!(ticks.ask[1].is_nan());
+        let table = read_table(&tick_path)?;
+        assert_eq!(table.bool_column("chg")?, Some(vec![Some(true), None]));
+        assert!(table.f64_column("vol")?.is_none());
+        let table = CsvTable::read(&tick_path)?;
+        assert_eq!(table.rows[0][3], "open, \"auction\"\nbell");
+        assert!(parse_csv("a,\"b\n").is_err());
+        #[cfg(feature = "parquet")]
+        {
+            let path = dir.join("equity.parquet");
+            write_equity(&path, &bars.time, &[1., f64::NAN])?;
+            let table = read_table(&path)?;
+            assert_eq!(table.time_column("time")?, bars.time);
+            assert!(table.required("equity")?[1].is_nan());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+}
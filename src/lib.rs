@@ -2,8 +2,26 @@ mod strategies;
 #[cfg(feature = "time")]
 mod trade;
 
+#[cfg(feature = "ndarray")]
+pub mod batch;
 pub mod equity;
+#[cfg(feature = "polars-lazy")]
+pub mod expr;
+#[cfg(feature = "time")]
+pub mod io;
+pub mod metrics;
+pub mod optimizer;
+pub mod options;
 mod order_book;
+#[cfg(feature = "polars-lazy")]
+pub mod pipeline;
+#[cfg(feature = "python")]
+mod plugin;
+#[cfg(feature = "python")]
+mod python;
+pub mod registry;
+pub mod robustness;
+mod serde_utils;
 pub use order_book::{OrderBook, OrderBookLevel};
 pub use strategies::*;
 pub use tevec;
@@ -0,0 +1,200 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tevec::prelude::*;
+
+/// Performance metrics of an equity curve.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metrics {
+    pub total_return: f64,
+    /// geometric annualized return
+    pub annual_return: f64,
+    pub annual_vol: f64,
+    pub sharpe: f64,
+    /// max drawdown as a positive fraction of the peak equity
+    pub max_drawdown: f64,
+    pub calmar: f64,
+}
+
+impl Default for Metrics {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            total_return: f64::NAN,
+            annual_return: f64::NAN,
+            annual_vol: f64::NAN,
+            sharpe: f64::NAN,
+            max_drawdown: f64::NAN,
+            calmar: f64::NAN,
+        }
+    }
+}
+
+impl Metrics {
+    /// Calculate metrics from an equity curve, `annualize` is the number of
+    /// periods in a year. Invalid values in the equity curve are skipped.
+    pub fn from_equity<T: IsNone, V: Vec1View<T>>(equity: &V, annualize: f64) -> Self
+    where
+        T::Inner: Number,
+    {
+        let mut first = f64::NAN;
+        let mut last = f64::NAN;
+        let mut peak = f64::NAN;
+        let mut max_drawdown = 0.;
+        let mut n = 0_usize;
+        let mut sum = 0.;
+        let mut sum2 = 0.;
+        equity.titer().for_each(|v| {
+            if v.is_none() {
+                return;
+            }
+            let v = v.unwrap().f64();
+            if first.is_nan() {
+                first = v;
+                peak = v;
+            } else {
+                let ret = v / last - 1.;
+                if ret.is_finite() {
+                    n += 1;
+                    sum += ret;
+                    sum2 += ret * ret;
+                }
+            }
+            if v > peak {
+                peak = v;
+            }
+            if peak > 0. {
+                let drawdown = (peak - v) / peak;
+                if drawdown > max_drawdown {
+                    max_drawdown = drawdown;
+                }
+            }
+            last = v;
+        });
+        if n == 0 {
+            return Self::default();
+        }
+        let total_return = last / first - 1.;
+        let annual_return = (1. + total_return).powf(annualize / n as f64) - 1.;
+        let mean = sum / n as f64;
+        let std = if n > 1 {
+            ((sum2 - sum * mean) / (n - 1) as f64).max(0.).sqrt()
+        } else {
+            f64::NAN
+        };
+        let annual_vol = std * annualize.sqrt();
+        let sharpe = if std > 0. {
+            mean / std * annualize.sqrt()
+        } else {
+            f64::NAN
+        };
+        let calmar = if max_drawdown > 0. {
+            annual_return / max_drawdown
+        } else {
+            f64::NAN
+        };
+        Self {
+            total_return,
+            annual_return,
+            annual_vol,
+            sharpe,
+            max_drawdown,
+            calmar,
+        }
+    }
+}
+
+/// Objective used to rank backtests, a larger score is always better.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Objective {
+    Sharpe,
+    TotalReturn,
+    AnnualReturn,
+    Calmar,
+    /// minimize max drawdown
+    MaxDrawdown,
+}
+
+impl Objective {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "sharpe" => Ok(Objective::Sharpe),
+            "total_return" | "return" => Ok(Objective::TotalReturn),
+            "annual_return" => Ok(Objective::AnnualReturn),
+            "calmar" => Ok(Objective::Calmar),
+            "max_drawdown" | "drawdown" => Ok(Objective::MaxDrawdown),
+            _ => tbail!("invalid objective"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Objective::Sharpe => "sharpe",
+            Objective::TotalReturn => "total_return",
+            Objective::AnnualReturn => "annual_return",
+            Objective::Calmar => "calmar",
+            Objective::MaxDrawdown => "max_drawdown",
+        }
+    }
+
+    #[inline]
+    pub fn score(&self, metrics: &Metrics) -> f64 {
+        match self {
+            Objective::Sharpe => metrics.sharpe,
+            Objective::TotalReturn => metrics.total_return,
+            Objective::AnnualReturn => metrics.annual_return,
+            Objective::Calmar => metrics.calmar,
+            Objective::MaxDrawdown => -metrics.max_drawdown,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Objective {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Objective::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Objective {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let equity = vec![100., 110., 99., f64::NAN, 121.];
+        let metrics = Metrics::from_equity(&equity, 3.);
+        assert!((metrics.total_return - 0.21).abs() < 1e-12);
+        // three returns, annualized by three periods
+        assert!((metrics.annual_return - 0.21).abs() < 1e-12);
+        assert!((metrics.max_drawdown - 0.1).abs() < 1e-12);
+        assert!((metrics.calmar - 2.1).abs() < 1e-12);
+        let rets: [f64; 3] = [0.1, -0.1, 121. / 99. - 1.];
+        let mean = (rets[0] + rets[1] + rets[2]) / 3.;
+        let var = rets
+            .into_iter()
+            .map(|r| (r - mean).powi(2))
+            .fold(0., |a, b| a + b)
+            / 2.;
+        let std = var.sqrt();
+        assert!((metrics.sharpe - mean / std * 3_f64.sqrt()).abs() < 1e-12);
+        assert_eq!(
+            Objective::MaxDrawdown.score(&metrics),
+            -metrics.max_drawdown
+        );
+    }
+}
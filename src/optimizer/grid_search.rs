@@ -0,0 +1,154 @@
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use tevec::prelude::*;
+
+use super::BarData;
+use crate::{
+    equity::{calc_future_ret, FutureRetKwargs},
+    metrics::{Metrics, Objective},
+};
+
+/// Backtest result of one parameter set.
+#[derive(Clone, Debug)]
+pub struct GridSearchResult<K> {
+    pub kwargs: K,
+    pub metrics: Metrics,
+    /// score of the objective, larger is better
+    pub score: f64,
+}
+
+/// Expand the value lists of each parameter into their Cartesian product,
+/// every parameter set has one value of each list in the order of `values`,
+/// and the last parameter changes the fastest.
+///
+/// ```
+/// use tea_strategy::optimizer::param_grid;
+///
+/// let grid = param_grid(&[vec![5., 10.], vec![1., 2.]]);
+/// assert_eq!(grid, vec![vec![5., 1.], vec![5., 2.], vec![10., 1.], vec![10., 2.]]);
+/// ```
+pub fn param_grid<T: Clone>(values: &[Vec<T>]) -> Vec<Vec<T>> {
+    values.iter().fold(vec![vec![]], |sets, values| {
+        sets.iter()
+            .flat_map(|set| {
+                values.iter().map(move |v| {
+                    let mut set = set.clone();
+                    set.push(v.clone());
+                    set
+                })
+            })
+            .collect()
+    })
+}
+
+/// Run the strategy on every parameter set and rank them by the objective.
+///
+/// `strategy` maps `data` and a parameter set to a signal,
+/// the signal is evaluated by [`calc_future_ret`]. Results are sorted by
+/// score in descending order and parameter sets with a NaN score are put
+/// at the end. Parameter sets are evaluated in parallel when the `rayon`
+/// feature is enabled.
+pub fn grid_search<K, F>(
+    params: &[K],
+    strategy: F,
+    data: &BarData,
+    kwargs: &FutureRetKwargs,
+    objective: Objective,
+    annualize: f64,
+) -> TResult<Vec<GridSearchResult<K>>>
+where
+    K: Clone + Send + Sync,
+    F: Fn(&BarData, &K) -> TResult<Vec<f64>> + Sync,
+{
+    tensure!(
+        data.open.len() == data.len() && data.fac.len() == data.len(),
+        "length of fac, open and close should be equal"
+    );
+    let run = |k: &K| -> TResult<GridSearchResult<K>> {
+        let signal = strategy(data, k)?;
+        tensure!(
+            signal.len() == data.len(),
+            "length of signal should be equal to the length of data"
+        );
+        let equity: Vec<f64> = calc_future_ret(
+            &signal,
+            &data.open,
+            &data.close,
+            data.contract_chg.clone(),
+            kwargs,
+        );
+        let metrics = Metrics::from_equity(&equity, annualize);
+        Ok(GridSearchResult {
+            kwargs: k.clone(),
+            metrics,
+            score: objective.score(&metrics),
+        })
+    };
+    #[cfg(feature = "rayon")]
+    let mut res = params.par_iter().map(run).collect::<TResult<Vec<_>>>()?;
+    #[cfg(not(feature = "rayon"))]
+    let mut res = params.iter().map(run).collect::<TResult<Vec<_>>>()?;
+    res.sort_by(|a, b| match (a.score.is_nan(), b.score.is_nan()) {
+        (true, true) => std::cmp::Ordering::Equal,
+        (true, false) => std::cmp::Ordering::Greater,
+        (false, true) => std::cmp::Ordering::Less,
+        (false, false) => b.score.partial_cmp(&a.score).unwrap(),
+    });
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{boll, equity::CommissionType, BollKwargs, StrategyFilter};
+
+    #[test]
+    fn test_grid_search() -> TResult<()> {
+        let close: Vec<f64> = (0..200)
+            .map(|i| 100. + 5. * (i as f64 / 7.).sin() + 0.05 * i as f64)
+            .collect();
+        let data = BarData::new(close.clone(), close.clone(), close);
+        let kwargs = FutureRetKwargs {
+            init_cash: 100000,
+            multiplier: 1.,
+            leverage: 1.,
+            slippage: 0.,
+            c_rate: 0.0003,
+            blowup: false,
+            commission_type: CommissionType::Percent,
+        };
+        let params: Vec<BollKwargs> = param_grid(&[vec![5., 10., 20.], vec![1., 1.5, 2.]])
+            .into_iter()
+            .map(|p| BollKwargs {
+                params: (p[0] as usize, p[1], 0., None),
+                ..Default::default()
+            })
+            .collect();
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let res = grid_search(
+            &params,
+            |data, k| Ok(boll(&data.fac, filter.as_ref(), k)),
+            &data,
+            &kwargs,
+            Objective::Sharpe,
+            252.,
+        )?;
+        assert_eq!(res.len(), 9);
+        for w in res.windows(2) {
+            assert!(w[0].score >= w[1].score || w[1].score.is_nan());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_param_grid() {
+        let grid = param_grid(&[vec![1, 2], vec![3], vec![4, 5, 6]]);
+        assert_eq!(grid.len(), 6);
+        assert_eq!(grid[0], vec![1, 3, 4]);
+        assert_eq!(grid[2], vec![1, 3, 6]);
+        assert_eq!(grid[5], vec![2, 3, 6]);
+        // no parameters is a single empty set, and an empty list has no set
+        assert_eq!(param_grid::<i32>(&[]), vec![Vec::<i32>::new()]);
+        assert!(param_grid(&[vec![1, 2], vec![]]).is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+mod grid_search;
+mod walk_forward;
+
+pub use grid_search::{grid_search, param_grid, GridSearchResult};
+pub use walk_forward::{
+    walk_forward, WalkForwardFold, WalkForwardKwargs, WalkForwardResult, WindowType,
+};
+
+/// Bar data used to run backtests in the optimizers.
+#[derive(Clone, Debug, Default)]
+pub struct BarData {
+    /// factor passed to the strategy
+    pub fac: Vec<f64>,
+    pub open: Vec<f64>,
+    pub close: Vec<f64>,
+    /// whether the contract is changed at this bar
+    pub contract_chg: Option<Vec<Option<bool>>>,
+    /// index of the first bar in the full data, so inputs aligned with the
+    /// full data (e.g. filters) can be sliced in the same way
+    pub offset: usize,
+}
+
+impl BarData {
+    #[inline]
+    pub fn new(fac: Vec<f64>, open: Vec<f64>, close: Vec<f64>) -> Self {
+        Self {
+            fac,
+            open,
+            close,
+            contract_chg: None,
+            offset: 0,
+        }
+    }
+
+    #[inline]
+    pub fn with_contract_chg(mut self, contract_chg: Vec<Option<bool>>) -> Self {
+        self.contract_chg = Some(contract_chg);
+        self
+    }
+
+    /// data in the range `[start, end)`, the offset is moved to `start`
+    #[inline]
+    pub fn slice(&self, start: usize, end: usize) -> Self {
+        Self {
+            fac: self.fac[start..end].to_vec(),
+            open: self.open[start..end].to_vec(),
+            close: self.close[start..end].to_vec(),
+            contract_chg: self
+                .contract_chg
+                .as_ref()
+                .map(|chg| chg[start..end].to_vec()),
+            offset: self.offset + start,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.close.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.close.is_empty()
+    }
+}
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tevec::prelude::*;
+
+use super::{grid_search, BarData};
+use crate::{
+    equity::{calc_future_ret, FutureRetKwargs},
+    metrics::{Metrics, Objective},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WindowType {
+    /// in-sample window has a fixed length and rolls forward
+    #[default]
+    Rolling,
+    /// in-sample window always starts at the beginning of the data
+    Anchored,
+}
+
+impl WindowType {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "rolling" => Ok(WindowType::Rolling),
+            "anchored" | "expanding" => Ok(WindowType::Anchored),
+            _ => tbail!("invalid window type"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WindowType::Rolling => "rolling",
+            WindowType::Anchored => "anchored",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for WindowType {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        WindowType::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for WindowType {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct WalkForwardKwargs {
+    #[serde(default)]
+    pub window_type: WindowType,
+    /// length of the in-sample window
+    pub train_len: usize,
+    /// length of the out-of-sample window
+    pub test_len: usize,
+    pub objective: Objective,
+    /// number of periods in a year
+    pub annualize: f64,
+}
+
+/// One in-sample / out-of-sample split of the walk-forward optimization.
+#[derive(Clone, Debug)]
+pub struct WalkForwardFold<K> {
+    /// in-sample range `[start, end)`
+    pub train: (usize, usize),
+    /// out-of-sample range `[start, end)`
+    pub test: (usize, usize),
+    /// parameter set selected in-sample
+    pub kwargs: K,
+    pub train_metrics: Metrics,
+}
+
+#[derive(Clone, Debug)]
+pub struct WalkForwardResult<K> {
+    pub folds: Vec<WalkForwardFold<K>>,
+    /// stitched out-of-sample signal, zero before the first out-of-sample window
+    pub signal: Vec<f64>,
+    /// equity curve of the stitched signal
+    pub equity: Vec<f64>,
+    /// metrics of the equity curve in the out-of-sample period
+    pub metrics: Metrics,
+}
+
+/// Walk-forward optimization.
+///
+/// For each fold the parameter set with the best in-sample score is
+/// selected by [`grid_search`] and applied to the following out-of-sample
+/// window. The out-of-sample signal is taken from a run of the strategy
+/// starting at the in-sample window, so indicators are warmed up and no
+/// future data is used. `strategy` receives the data of the run sliced from
+/// `data`, [`BarData::offset`] is the start of the run in `data`. The
+/// signals of all folds are stitched into one position vector and evaluated
+/// by [`calc_future_ret`].
+///
+/// At the start of each out-of-sample window the position switches from the
+/// signal of the previous fold to the signal of the new one, as a live
+/// switch of parameters would do. If they differ the difference is traded at
+/// that bar and charged with commission, even if neither run trades there.
+pub fn walk_forward<K, F>(
+    params: &[K],
+    strategy: F,
+    data: &BarData,
+    kwargs: &FutureRetKwargs,
+    wf_kwargs: &WalkForwardKwargs,
+) -> TResult<WalkForwardResult<K>>
+where
+    K: Clone + Send + Sync,
+    F: Fn(&BarData, &K) -> TResult<Vec<f64>> + Sync,
+{
+    tensure!(!params.is_empty(), "params should not be empty");
+    tensure!(
+        wf_kwargs.train_len > 0 && wf_kwargs.test_len > 0,
+        "train_len and test_len should be greater than 0"
+    );
+    let len = data.len();
+    tensure!(
+        wf_kwargs.train_len < len,
+        "train_len should be less than the length of data"
+    );
+    let mut signal = vec![0.; len];
+    let mut folds = Vec::new();
+    let mut test_start = wf_kwargs.train_len;
+    while test_start < len {
+        let test_end = (test_start + wf_kwargs.test_len).min(len);
+        let train_start = match wf_kwargs.window_type {
+            WindowType::Rolling => test_start - wf_kwargs.train_len,
+            WindowType::Anchored => 0,
+        };
+        let train_data = data.slice(train_start, test_start);
+        let best = grid_search(
+            params,
+            &strategy,
+            &train_data,
+            kwargs,
+            wf_kwargs.objective,
+            wf_kwargs.annualize,
+        )?
+        .swap_remove(0);
+        let oos_signal = strategy(&data.slice(train_start, test_end), &best.kwargs)?;
+        tensure!(
+            oos_signal.len() == test_end - train_start,
+            "length of signal should be equal to the length of data"
+        );
+        signal[test_start..test_end].copy_from_slice(&oos_signal[test_start - train_start..]);
+        folds.push(WalkForwardFold {
+            train: (train_start, test_start),
+            test: (test_start, test_end),
+            kwargs: best.kwargs,
+            train_metrics: best.metrics,
+        });
+        test_start = test_end;
+    }
+    let equity: Vec<f64> = calc_future_ret(
+        &signal,
+        &data.open,
+        &data.close,
+        data.contract_chg.clone(),
+        kwargs,
+    );
+    // the equity before the first out-of-sample window is flat
+    let metrics = Metrics::from_equity(
+        &equity[wf_kwargs.train_len - 1..].to_vec(),
+        wf_kwargs.annualize,
+    );
+    Ok(WalkForwardResult {
+        folds,
+        signal,
+        equity,
+        metrics,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{boll, equity::CommissionType, BollKwargs, StrategyFilter};
+
+    #[test]
+    fn test_walk_forward() -> TResult<()> {
+        let close: Vec<f64> = (0..300)
+            .map(|i| 100. + 5. * (i as f64 / 7.).sin() + 0.05 * i as f64)
+            .collect();
+        let data = BarData::new(close.clone(), close.clone(), close);
+        let kwargs = FutureRetKwargs {
+            init_cash: 100000,
+            multiplier: 1.,
+            leverage: 1.,
+            slippage: 0.,
+            c_rate: 0.0003,
+            blowup: false,
+            commission_type: CommissionType::Percent,
+        };
+        let params: Vec<BollKwargs> = [5, 10, 20]
+            .into_iter()
+            .map(|n| BollKwargs {
+                params: (n, 1., 0., None),
+                ..Default::default()
+            })
+            .collect();
+        // a filter aligned with the full data is sliced by the offset
+        let long_open: Vec<Option<bool>> = (0..300).map(|i| Some(i % 50 < 40)).collect();
+        let strategy = |data: &BarData, k: &BollKwargs| {
+            let end = data.offset + data.len();
+            let filter = StrategyFilter {
+                long_open: long_open[data.offset..end].to_vec(),
+                long_stop: vec![None; data.len()],
+                short_open: vec![None; data.len()],
+                short_stop: vec![None; data.len()],
+            };
+            Ok(boll(&data.fac, Some(&filter), k))
+        };
+        let mut wf_kwargs = WalkForwardKwargs {
+            window_type: WindowType::Rolling,
+            train_len: 100,
+            test_len: 60,
+            objective: Objective::Sharpe,
+            annualize: 252.,
+        };
+        let res = walk_forward(&params, strategy, &data, &kwargs, &wf_kwargs)?;
+        assert_eq!(res.folds.len(), 4);
+        assert_eq!(res.folds[1].train, (60, 160));
+        assert_eq!(res.folds[3].test, (280, 300));
+        assert_eq!(res.signal.len(), 300);
+        assert_eq!(&res.signal[..100], &[0.; 100]);
+        // out-of-sample signal only depends on data up to the end of the fold
+        let fold = &res.folds[2];
+        let fold_data = data.slice(fold.train.0, fold.test.1);
+        assert_eq!(fold_data.offset, fold.train.0);
+        let expect = strategy(&fold_data, &fold.kwargs)?;
+        assert_eq!(
+            &res.signal[fold.test.0..fold.test.1],
+            &expect[fold.test.0 - fold.train.0..]
+        );
+        wf_kwargs.window_type = WindowType::Anchored;
+        let res = walk_forward(&params, strategy, &data, &kwargs, &wf_kwargs)?;
+        assert_eq!(res.folds[3].train, (0, 280));
+        assert_eq!(res.equity.len(), 300);
+        Ok(())
+    }
+}
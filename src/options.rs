@@ -0,0 +1,280 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tevec::prelude::{tbail, TResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionType {
+    Call,
+    Put,
+}
+
+impl OptionType {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "call" | "c" => Ok(OptionType::Call),
+            "put" | "p" => Ok(OptionType::Put),
+            _ => tbail!("invalid option type"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OptionType::Call => "call",
+            OptionType::Put => "put",
+        }
+    }
+
+    /// 1 for a call and -1 for a put
+    #[inline]
+    fn sign(&self) -> f64 {
+        match self {
+            OptionType::Call => 1.,
+            OptionType::Put => -1.,
+        }
+    }
+
+    /// payoff at expiry
+    #[inline]
+    pub fn intrinsic(&self, forward: f64, strike: f64) -> f64 {
+        (self.sign() * (forward - strike)).max(0.)
+    }
+}
+
+impl<'de> Deserialize<'de> for OptionType {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        OptionType::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for OptionType {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Probability density function of the standard normal distribution.
+#[inline]
+pub fn norm_pdf(x: f64) -> f64 {
+    (-0.5 * x * x).exp() / (2. * std::f64::consts::PI).sqrt()
+}
+
+/// Cumulative distribution function of the standard normal distribution,
+/// the double precision approximation of Hart (1968).
+pub fn norm_cdf(x: f64) -> f64 {
+    let abs = x.abs();
+    let tail = if abs > 37. {
+        0.
+    } else {
+        let exp = (-0.5 * abs * abs).exp();
+        if abs < 7.07106781186547 {
+            const NUM: [f64; 7] = [
+                3.52624965998911e-2,
+                0.700383064443688,
+                6.37396220353165,
+                33.912866078383,
+                112.079291497871,
+                221.213596169931,
+                220.206867912376,
+            ];
+            const DEN: [f64; 8] = [
+                8.83883476483184e-2,
+                1.75566716318264,
+                16.064177579207,
+                86.7807322029461,
+                296.564248779674,
+                637.333633378831,
+                793.826512519948,
+                440.413735824752,
+            ];
+            let horner = |coef: &[f64]| coef.iter().fold(0., |acc, c| acc * abs + c);
+            exp * horner(&NUM) / horner(&DEN)
+        } else {
+            let frac = abs + 1. / (abs + 2. / (abs + 3. / (abs + 4. / (abs + 0.65))));
+            exp / frac / 2.506628274631
+        }
+    };
+    if x > 0. {
+        1. - tail
+    } else {
+        tail
+    }
+}
+
+/// Price and sensitivities of an option.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Greeks {
+    pub price: f64,
+    /// change of the price per unit change of the forward
+    pub delta: f64,
+    /// change of the delta per unit change of the forward
+    pub gamma: f64,
+    /// change of the price per unit (100%) change of the volatility
+    pub vega: f64,
+    /// change of the price per year passed
+    pub theta: f64,
+    /// change of the price per unit change of the rate
+    pub rho: f64,
+}
+
+/// Black-76 price of a european option on a future.
+///
+/// `t` is the time to expiry in years, `rate` is the continuously compounded
+/// discount rate and `vol` is the annualized volatility of the forward.
+pub fn black76_price(
+    option_type: OptionType,
+    forward: f64,
+    strike: f64,
+    t: f64,
+    rate: f64,
+    vol: f64,
+) -> f64 {
+    let df = (-rate * t.max(0.)).exp();
+    if t <= 0. || vol <= 0. {
+        return df * option_type.intrinsic(forward, strike);
+    }
+    let std = vol * t.sqrt();
+    let d1 = ((forward / strike).ln() + 0.5 * std * std) / std;
+    let d2 = d1 - std;
+    let sign = option_type.sign();
+    df * sign * (forward * norm_cdf(sign * d1) - strike * norm_cdf(sign * d2))
+}
+
+/// The same as [`black76_price`], but also return the greeks.
+pub fn black76_greeks(
+    option_type: OptionType,
+    forward: f64,
+    strike: f64,
+    t: f64,
+    rate: f64,
+    vol: f64,
+) -> Greeks {
+    let df = (-rate * t.max(0.)).exp();
+    let sign = option_type.sign();
+    if t <= 0. || vol <= 0. {
+        // the option is worth its discounted intrinsic value
+        let price = df * option_type.intrinsic(forward, strike);
+        let delta = if price > 0. { sign * df } else { 0. };
+        return Greeks {
+            price,
+            delta,
+            theta: rate * price,
+            rho: -t.max(0.) * price,
+            ..Default::default()
+        };
+    }
+    let sqrt_t = t.sqrt();
+    let std = vol * sqrt_t;
+    let d1 = ((forward / strike).ln() + 0.5 * std * std) / std;
+    let d2 = d1 - std;
+    let price = df * sign * (forward * norm_cdf(sign * d1) - strike * norm_cdf(sign * d2));
+    let pdf = norm_pdf(d1);
+    Greeks {
+        price,
+        delta: df * sign * norm_cdf(sign * d1),
+        gamma: df * pdf / (forward * std),
+        vega: df * forward * pdf * sqrt_t,
+        theta: rate * price - df * forward * pdf * vol / (2. * sqrt_t),
+        rho: -t * price,
+    }
+}
+
+/// Implied volatility of a Black-76 price, `NaN` if the price is not within
+/// the no-arbitrage bounds.
+pub fn black76_implied_vol(
+    option_type: OptionType,
+    price: f64,
+    forward: f64,
+    strike: f64,
+    t: f64,
+    rate: f64,
+) -> f64 {
+    let df = (-rate * t).exp();
+    let lower = df * option_type.intrinsic(forward, strike);
+    let upper = match option_type {
+        OptionType::Call => df * forward,
+        OptionType::Put => df * strike,
+    };
+    if t <= 0. || !(price > lower && price < upper) {
+        return f64::NAN;
+    }
+    let (mut lo, mut hi) = (0., 1.);
+    while black76_price(option_type, forward, strike, t, rate, hi) < price {
+        lo = hi;
+        hi *= 2.;
+        if hi > 1e4 {
+            return f64::NAN;
+        }
+    }
+    // newton steps, fall back to bisection if a step leaves the bracket
+    let mut vol = 0.5 * (lo + hi);
+    for _ in 0..100 {
+        let greeks = black76_greeks(option_type, forward, strike, t, rate, vol);
+        let diff = greeks.price - price;
+        if diff.abs() < 1e-12 * upper {
+            break;
+        }
+        if diff > 0. {
+            hi = vol;
+        } else {
+            lo = vol;
+        }
+        let next = vol - diff / greeks.vega;
+        vol = if next > lo && next < hi {
+            next
+        } else {
+            0.5 * (lo + hi)
+        };
+    }
+    vol
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_black76() {
+        assert_eq!(norm_cdf(0.), 0.5);
+        assert!((norm_cdf(1.) - 0.8413447460685429).abs() < 1e-14);
+        assert!((norm_cdf(-1.96) - 0.024997895148220435).abs() < 1e-14);
+        let (f, k, t, r, vol) = (100., 95., 0.5, 0.03, 0.25);
+        let call = black76_greeks(OptionType::Call, f, k, t, r, vol);
+        let put = black76_greeks(OptionType::Put, f, k, t, r, vol);
+        // put call parity
+        let df = (-r * t).exp();
+        assert!((call.price - put.price - df * (f - k)).abs() < 1e-12);
+        assert!((call.delta - put.delta - df).abs() < 1e-12);
+        assert_eq!(call.price, black76_price(OptionType::Call, f, k, t, r, vol));
+        // greeks by finite difference
+        let price =
+            |f: f64, t: f64, r: f64, vol: f64| black76_price(OptionType::Call, f, k, t, r, vol);
+        let h = 1e-4;
+        let delta = (price(f + h, t, r, vol) - price(f - h, t, r, vol)) / (2. * h);
+        let gamma = (price(f + h, t, r, vol) - 2. * call.price + price(f - h, t, r, vol)) / (h * h);
+        let vega = (price(f, t, r, vol + h) - price(f, t, r, vol - h)) / (2. * h);
+        let theta = (price(f, t - h, r, vol) - price(f, t + h, r, vol)) / (2. * h);
+        let rho = (price(f, t, r + h, vol) - price(f, t, r - h, vol)) / (2. * h);
+        assert!((call.delta - delta).abs() < 1e-8);
+        assert!((call.gamma - gamma).abs() < 1e-5);
+        assert!((call.vega - vega).abs() < 1e-6);
+        assert!((call.theta - theta).abs() < 1e-6);
+        assert!((call.rho - rho).abs() < 1e-6);
+        // implied volatility
+        let iv = black76_implied_vol(OptionType::Put, put.price, f, k, t, r);
+        assert!((iv - vol).abs() < 1e-10);
+        assert!(black76_implied_vol(OptionType::Call, 1., f, k, t, r).is_nan());
+        // expired options are worth the intrinsic value
+        let expired = black76_greeks(OptionType::Put, 90., k, 0., r, vol);
+        assert_eq!((expired.price, expired.delta), (5., -1.));
+    }
+}
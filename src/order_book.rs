@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 /// Represents an order book with five levels of depth.
-#[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct OrderBook {
     /// The first (best) level of the order book.
     pub level1: OrderBookLevel,
@@ -14,15 +16,19 @@ pub struct OrderBook {
 }
 
 /// Represents a single level in the order book.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct OrderBookLevel {
     /// The ask (sell) price at this level.
+    #[serde(with = "crate::serde_utils::nan_as_null")]
     pub ask_price: f64,
     /// The bid (buy) price at this level.
+    #[serde(with = "crate::serde_utils::nan_as_null")]
     pub bid_price: f64,
     /// The volume available at the ask price.
+    #[serde(with = "crate::serde_utils::nan_as_null")]
     pub ask_volume: f64,
     /// The volume available at the bid price.
+    #[serde(with = "crate::serde_utils::nan_as_null")]
     pub bid_volume: f64,
 }
 
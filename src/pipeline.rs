@@ -0,0 +1,232 @@
+//! Backtest pipeline on polars lazy frames.
+//!
+//! The signal and equity are computed group-wise by symbol, so a frame with
+//! many symbols is backtested in one lazy query.
+//!
+//! The query is not streaming: each symbol is collected into vectors by
+//! `.over()` before its strategy and engine run, so the rows of every symbol
+//! should fit in memory.
+use serde::{Deserialize, Serialize};
+use tevec::{export::polars::prelude::*, prelude::TResult};
+
+use crate::{
+    equity::{calc_future_ret_with_fee, FutureRetKwargs},
+    expr::{column_to_vec, strategy_expr},
+    registry::StrategyConfig,
+};
+
+/// Column names used by [`backtest_lazy`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PipelineColumns {
+    pub symbol: String,
+    pub time: String,
+    pub open: String,
+    pub close: String,
+    pub fac: String,
+    /// boolean column of contract changes of a continuous contract, the
+    /// position of the old contract is closed at its previous close and
+    /// reopened at the open of the new one, so the roll gap is not booked as
+    /// profit
+    pub contract_chg: Option<String>,
+}
+
+impl Default for PipelineColumns {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            symbol: "symbol".into(),
+            time: "time".into(),
+            open: "open".into(),
+            close: "close".into(),
+            fac: "fac".into(),
+            contract_chg: None,
+        }
+    }
+}
+
+/// Equity and fee expression of a signal, the output is a struct with
+/// `equity` and `fee` fields. `contract_chg` is a boolean expression of
+/// contract changes without nulls.
+pub fn future_ret_expr(
+    signal: Expr,
+    open: Expr,
+    close: Expr,
+    contract_chg: Option<Expr>,
+    kwargs: &FutureRetKwargs,
+) -> Expr {
+    let kwargs = kwargs.clone();
+    let mut inputs = vec![open, close];
+    inputs.extend(contract_chg);
+    signal.apply_many(
+        move |cols: &mut [Column]| {
+            let signal = column_to_vec(&cols[0])?;
+            let open = column_to_vec(&cols[1])?;
+            let close = column_to_vec(&cols[2])?;
+            let contract_chg = cols
+                .get(3)
+                .map(|c| -> PolarsResult<Vec<Option<bool>>> {
+                    let c = c.cast(&DataType::Boolean)?;
+                    polars_ensure!(
+                        c.null_count() == 0,
+                        ComputeError: "contract_chg column should not have null values"
+                    );
+                    Ok(c.bool()?.into_iter().collect())
+                })
+                .transpose()?;
+            let (equity, fee) =
+                calc_future_ret_with_fee(&signal, &open, &close, contract_chg, &kwargs);
+            let len = equity.len();
+            let res = StructChunked::from_columns(
+                cols[0].name().clone(),
+                len,
+                &[
+                    Float64Chunked::from_vec("equity".into(), equity).into_column(),
+                    Float64Chunked::from_vec("fee".into(), fee).into_column(),
+                ],
+            )?;
+            Ok(Some(res.into_column()))
+        },
+        &inputs,
+        GetOutput::from_type(DataType::Struct(vec![
+            Field::new("equity".into(), DataType::Float64),
+            Field::new("fee".into(), DataType::Float64),
+        ])),
+    )
+}
+
+/// Backtest the strategy for each symbol of the lazy frame.
+///
+/// The frame is sorted by symbol and time, and `signal`, `equity` and `fee`
+/// columns are appended. The equity of each symbol starts from
+/// `init_cash` of the kwargs, and contract changes are handled if the
+/// `contract_chg` column is given.
+pub fn backtest_lazy(
+    lf: LazyFrame,
+    strategy: &StrategyConfig,
+    kwargs: &FutureRetKwargs,
+    columns: &PipelineColumns,
+) -> TResult<LazyFrame> {
+    let symbol = col(columns.symbol.as_str());
+    let signal = strategy_expr(
+        col(columns.fac.as_str()),
+        &strategy.name,
+        strategy.kwargs.clone(),
+    )?;
+    let res = future_ret_expr(
+        col("signal"),
+        col(columns.open.as_str()),
+        col(columns.close.as_str()),
+        columns.contract_chg.as_deref().map(col),
+        kwargs,
+    );
+    Ok(lf
+        .sort(
+            [columns.symbol.as_str(), columns.time.as_str()],
+            Default::default(),
+        )
+        .with_column(signal.over([symbol.clone()]).alias("signal"))
+        .with_column(res.over([symbol]).alias("__backtest"))
+        .unnest(["__backtest"]))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        equity::calc_future_ret,
+        registry::{StrategyData, StrategyRegistry},
+    };
+
+    #[test]
+    fn test_backtest_lazy() -> TResult<()> {
+        let close = vec![
+            10., 11., 11.9, 10., 11., 12., 10., 11., 12., 13., 14., 10., 7., 5., 4., 3., 4., 4.,
+            3., 2.,
+        ];
+        let open: Vec<f64> = close.iter().map(|v| v - 0.1).collect();
+        let n = close.len();
+        let strategy = StrategyConfig {
+            name: "boll".into(),
+            kwargs: json!({"params": [4, 1.0, 0.0, null], "min_periods": null, "delay_open": false,
+                "zscore": true, "long_signal": 1.0, "short_signal": -1.0, "close_signal": 0.0}),
+        };
+        let kwargs = FutureRetKwargs {
+            init_cash: 10000,
+            multiplier: 1.,
+            leverage: 1.,
+            slippage: 0.,
+            c_rate: 0.0003,
+            blowup: false,
+            commission_type: "percent".into(),
+        };
+        let signal =
+            StrategyRegistry::default().run_config(&strategy, &StrategyData::new(close.clone()))?;
+        let equity: Vec<f64> = calc_future_ret(&signal, &open, &close, None::<Vec<_>>, &kwargs);
+        // symbol b is given first and in reversed time order
+        let symbol: Vec<&str> = vec!["b"; n].into_iter().chain(vec!["a"; n]).collect();
+        let time: Vec<i64> = (0..n as i64).rev().chain(0..n as i64).collect();
+        let rev = |v: &Vec<f64>| -> Vec<f64> { v.iter().rev().chain(v.iter()).copied().collect() };
+        let df = df!(
+            "symbol" => symbol,
+            "time" => time,
+            "open" => rev(&open),
+            "close" => rev(&close),
+            "fac" => rev(&close),
+        )
+        .unwrap();
+        let out = backtest_lazy(df.lazy(), &strategy, &kwargs, &Default::default())?
+            .collect()
+            .unwrap();
+        let get =
+            |name: &str| -> Vec<f64> { out[name].f64().unwrap().into_no_null_iter().collect() };
+        let (out_signal, out_equity, fee) = (get("signal"), get("equity"), get("fee"));
+        for i in 0..2 {
+            assert_eq!(&out_signal[i * n..(i + 1) * n], &signal[..]);
+            assert_eq!(&out_equity[i * n..(i + 1) * n], &equity[..]);
+        }
+        assert!(fee.iter().filter(|f| **f > 0.).count() > 0);
+
+        // a continuous contract rolls to a new contract 100 higher at bar 12,
+        // the gap is not a profit
+        let roll = |v: &Vec<f64>| -> Vec<f64> {
+            v.iter()
+                .enumerate()
+                .map(|(i, v)| if i >= 12 { v + 100. } else { *v })
+                .collect()
+        };
+        let (open, close2) = (roll(&open), roll(&close));
+        let chg: Vec<bool> = (0..n).map(|i| i == 12).collect();
+        let expect: Vec<f64> = calc_future_ret(
+            &signal,
+            &open,
+            &close2,
+            Some(chg.iter().map(|c| Some(*c)).collect::<Vec<_>>()),
+            &kwargs,
+        );
+        let gap: Vec<f64> = calc_future_ret(&signal, &open, &close2, None::<Vec<_>>, &kwargs);
+        assert_ne!(expect, gap);
+        let df = df!(
+            "symbol" => vec!["a"; n],
+            "time" => (0..n as i64).collect::<Vec<_>>(),
+            "open" => open,
+            "close" => close2,
+            "fac" => close,
+            "chg" => chg,
+        )
+        .unwrap();
+        let columns = PipelineColumns {
+            contract_chg: Some("chg".into()),
+            ..Default::default()
+        };
+        let out = backtest_lazy(df.lazy(), &strategy, &kwargs, &columns)?
+            .collect()
+            .unwrap();
+        let get =
+            |name: &str| -> Vec<f64> { out[name].f64().unwrap().into_no_null_iter().collect() };
+        assert_eq!(get("equity"), expect);
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+//! Polars expression plugins of the strategies for python.
+//!
+//! The plugins are exported by the python extension module, kwargs are
+//! python dicts with the same fields as the kwargs structs. The strategies
+//! are run per group, so they can be used with `.over("symbol")`:
+//!
+//! ```python
+//! from pathlib import Path
+//! import polars as pl
+//! import tea_strategy
+//!
+//! def boll(fac: pl.Expr, **kwargs) -> pl.Expr:
+//!     return pl.plugins.register_plugin_function(
+//!         plugin_path=Path(tea_strategy.__file__),
+//!         function_name="boll",
+//!         args=fac,
+//!         kwargs=kwargs,
+//!     )
+//!
+//! df.with_columns(boll(pl.col("fac"), params=[20, 1.0, 0.0, None], ...).over("symbol"))
+//! ```
+//!
+//! Filters are not supported in plugins, `martingale` takes the close price.
+use pyo3_polars::{derive::polars_expr, export::polars_core::prelude::*};
+use tevec::prelude::{TError, TResult};
+
+use crate::*;
+
+#[inline]
+fn to_polars_err(e: TError) -> PolarsError {
+    PolarsError::ComputeError(e.to_string().into())
+}
+
+/// Run a strategy on the first input, nulls are passed as `NaN`.
+fn strategy_series<F>(inputs: &[Series], f: F) -> PolarsResult<Series>
+where
+    F: FnOnce(&Vec<f64>, Option<&StrategyFilter<Vec<Option<bool>>>>) -> TResult<Vec<f64>>,
+{
+    let fac = inputs[0].cast(&DataType::Float64)?;
+    let fac: Vec<f64> = fac
+        .f64()?
+        .into_iter()
+        .map(|v| v.unwrap_or(f64::NAN))
+        .collect();
+    let out = f(&fac, None).map_err(to_polars_err)?;
+    Ok(Float64Chunked::from_vec(inputs[0].name().clone(), out).into_series())
+}
+
+macro_rules! strategy_plugin_impl {
+    ($name: ident, $func: ident, $kwargs: ty) => {
+        #[polars_expr(output_type = Float64)]
+        fn $name(inputs: &[Series], kwargs: $kwargs) -> PolarsResult<Series> {
+            strategy_series(inputs, |fac, filter| crate::$func(fac, filter, &kwargs))
+        }
+    };
+    // strategies that never fail
+    ($name: ident, $func: ident, $kwargs: ty, infallible) => {
+        #[polars_expr(output_type = Float64)]
+        fn $name(inputs: &[Series], kwargs: $kwargs) -> PolarsResult<Series> {
+            strategy_series(inputs, |fac, filter| Ok(crate::$func(fac, filter, &kwargs)))
+        }
+    };
+}
+
+strategy_plugin_impl!(boll, boll, BollKwargs, infallible);
+strategy_plugin_impl!(auto_boll, auto_boll, AutoBollKwargs);
+strategy_plugin_impl!(delay_boll, delay_boll, DelayBollKwargs);
+strategy_plugin_impl!(fix_time, fix_time, FixTimeKwargs);
+strategy_plugin_impl!(prob_threshold, prob_threshold, ProbThresholdKwargs);
+strategy_plugin_impl!(martingale, martingale, MartingaleKwargs);
+strategy_plugin_impl!(auto_tangqian, auto_tangqian, AutoTangQiAnKwargs);
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use pyo3_polars::{
+        derive::{_polars_plugin_get_last_error_message, CallerContext},
+        export::polars_ffi::version_0::*,
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    type Plugin = unsafe extern "C" fn(
+        *mut SeriesExport,
+        usize,
+        *const u8,
+        usize,
+        *mut SeriesExport,
+        *mut CallerContext,
+    );
+
+    /// call a plugin the way polars does, kwargs are pickled like a python dict
+    fn call(plugin: Plugin, fac: &Series, kwargs: Value) -> PolarsResult<Series> {
+        let kwargs = serde_pickle::to_vec(&kwargs, Default::default()).unwrap();
+        let mut inputs = [export_series(fac)];
+        let mut out = SeriesExport::empty();
+        let mut context = CallerContext::default();
+        unsafe {
+            plugin(
+                inputs.as_mut_ptr(),
+                inputs.len(),
+                kwargs.as_ptr(),
+                kwargs.len(),
+                &mut out,
+                &mut context,
+            );
+            // the inputs are moved into and released by the plugin
+            std::mem::forget(inputs);
+            if out.is_null() {
+                let msg = CStr::from_ptr(_polars_plugin_get_last_error_message());
+                polars_bail!(ComputeError: "{}", msg.to_string_lossy());
+            }
+            import_series(out)
+        }
+    }
+
+    #[test]
+    fn test_strategy_plugin() -> PolarsResult<()> {
+        let fac = vec![
+            10., 11., 11.9, 10., 11., 12., 10., 11., 12., 13., 14., 10., 7., 5., 4., 3., 4., 4.,
+            3., 2.,
+        ];
+        let kwargs = json!({"params": [4, 1.0, 0.0, null], "min_periods": null,
+            "delay_open": false, "zscore": true, "long_signal": 1.0, "short_signal": -1.0,
+            "close_signal": 0.0});
+        let out = call(
+            _polars_plugin_boll,
+            &Series::new("fac".into(), &fac),
+            kwargs.clone(),
+        )?;
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let expect: Vec<f64> = crate::boll(
+            &fac,
+            filter.as_ref(),
+            &serde_json::from_value(kwargs).unwrap(),
+        );
+        assert_eq!(out.name().as_str(), "fac");
+        assert_eq!(out.f64()?.into_no_null_iter().collect::<Vec<_>>(), expect);
+        // strategy errors and invalid kwargs are polars errors
+        let kwargs = json!({"thresholds": [0.4, 0.5, 0.6, 0.5], "per_hand": 1.0,
+            "max_hand": 1.0});
+        let err = call(
+            _polars_plugin_prob_threshold,
+            &Series::new("fac".into(), &fac),
+            kwargs,
+        );
+        assert!(err.unwrap_err().to_string().contains("long open thres"));
+        let err = call(
+            _polars_plugin_delay_boll,
+            &Series::new("fac".into(), &fac),
+            json!({"params": 1}),
+        );
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("could not parse kwargs"));
+        Ok(())
+    }
+}
@@ -0,0 +1,1015 @@
+//! Python bindings, array arguments accept numpy arrays, polars Series and
+//! anything that can be converted by `numpy.asarray`, kwargs are python
+//! dicts with the same fields as the kwargs structs.
+use numpy::{IntoPyArray, PyArray1, PyReadonlyArray1};
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyDict, PyList, PyTuple},
+};
+use serde::de::DeserializeOwned;
+use tevec::prelude::*;
+
+use crate::{equity::*, options::OptionType, *};
+
+type PyArr<'py> = Bound<'py, PyArray1<f64>>;
+
+#[inline]
+fn to_py_err(e: TError) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// convert an array like object to a numpy array of `dtype`
+fn as_numpy<'py>(obj: &Bound<'py, PyAny>, dtype: &str) -> PyResult<Bound<'py, PyAny>> {
+    let obj = if obj.hasattr("to_numpy")? {
+        obj.call_method0("to_numpy")?
+    } else {
+        obj.clone()
+    };
+    obj.py()
+        .import("numpy")?
+        .call_method1("asarray", (obj, dtype))
+}
+
+fn to_f64_vec(obj: &Bound<'_, PyAny>) -> PyResult<Vec<f64>> {
+    let arr: PyReadonlyArray1<f64> = as_numpy(obj, "float64")?.extract()?;
+    Ok(arr.as_array().to_vec())
+}
+
+/// null values are converted to None
+fn to_mask_vec(obj: &Bound<'_, PyAny>) -> PyResult<Vec<Option<bool>>> {
+    Ok(to_f64_vec(obj)?
+        .into_iter()
+        .map(|v| if v.is_nan() { None } else { Some(v != 0.) })
+        .collect())
+}
+
+fn to_time_vec(obj: &Bound<'_, PyAny>) -> PyResult<Vec<DateTime>> {
+    let arr: PyReadonlyArray1<i64> = as_numpy(obj, "datetime64[ns]")?
+        .call_method1("astype", ("int64",))?
+        .extract()?;
+    Ok(arr
+        .as_array()
+        .iter()
+        .map(|v| {
+            // NaT of numpy is the min value of int64
+            if *v == i64::MIN {
+                DateTime::nat()
+            } else {
+                DateTime::new(*v)
+            }
+        })
+        .collect())
+}
+
+fn parse_kwargs<K: DeserializeOwned>(kwargs: &Bound<'_, PyDict>) -> PyResult<K> {
+    let s: String = kwargs
+        .py()
+        .import("json")?
+        .call_method1("dumps", (kwargs,))?
+        .extract()?;
+    serde_json::from_str(&s).map_err(|e| PyValueError::new_err(format!("invalid kwargs: {}", e)))
+}
+
+/// filter is a sequence of long open, long stop, short open and short stop
+fn to_filter(
+    filter: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Option<StrategyFilter<Vec<Option<bool>>>>> {
+    let Some(filter) = filter else {
+        return Ok(None);
+    };
+    if filter.len()? != 4 {
+        return Err(PyValueError::new_err("filter should have 4 columns"));
+    }
+    Ok(Some(StrategyFilter {
+        long_open: to_mask_vec(&filter.get_item(0)?)?,
+        long_stop: to_mask_vec(&filter.get_item(1)?)?,
+        short_open: to_mask_vec(&filter.get_item(2)?)?,
+        short_stop: to_mask_vec(&filter.get_item(3)?)?,
+    }))
+}
+
+/// The contract change signal should not have null values. The Rust engines
+/// take a null as no change, but numpy arrays carry nulls as `NaN`, which is
+/// more likely a misaligned join than a missing flag, so python asks for an
+/// explicit 0 instead.
+fn to_contract_chg(obj: Option<&Bound<'_, PyAny>>) -> PyResult<Option<Vec<Option<bool>>>> {
+    let chg = obj.map(to_mask_vec).transpose()?;
+    if chg.as_ref().is_some_and(|c| c.contains(&None)) {
+        return Err(PyValueError::new_err(
+            "contract_chg should not have null values",
+        ));
+    }
+    Ok(chg)
+}
+
+/// create a polars DataFrame from columns
+fn polars_frame<'py>(
+    py: Python<'py>,
+    columns: Vec<(&str, Bound<'py, PyAny>)>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let dict = PyDict::new(py);
+    for (name, col) in columns {
+        dict.set_item(name, col)?;
+    }
+    py.import("polars")?.call_method1("DataFrame", (dict,))
+}
+
+/// create a polars DataFrame from float columns
+fn f64_frame<'py>(py: Python<'py>, columns: Vec<(&str, Vec<f64>)>) -> PyResult<Bound<'py, PyAny>> {
+    polars_frame(
+        py,
+        columns
+            .into_iter()
+            .map(|(name, col)| (name, col.into_pyarray(py).into_any()))
+            .collect(),
+    )
+}
+
+/// A [`BacktestResult`] as a tuple of polars DataFrames, one row per bar with
+/// equity, lots, fee, slippage, realized, unrealized and funding columns and
+/// one row per fill with idx, price and lots columns. `extra` columns are
+/// appended to the bar frame.
+fn result_frames<'py>(
+    py: Python<'py>,
+    res: BacktestResult,
+    extra: Vec<(&str, Vec<f64>)>,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let fills = polars_frame(
+        py,
+        vec![
+            (
+                "idx",
+                res.fills
+                    .iter()
+                    .map(|f| f.idx as u64)
+                    .collect::<Vec<_>>()
+                    .into_pyarray(py)
+                    .into_any(),
+            ),
+            (
+                "price",
+                res.fills
+                    .iter()
+                    .map(|f| f.price)
+                    .collect::<Vec<_>>()
+                    .into_pyarray(py)
+                    .into_any(),
+            ),
+            (
+                "lots",
+                res.fills
+                    .iter()
+                    .map(|f| f.lots)
+                    .collect::<Vec<_>>()
+                    .into_pyarray(py)
+                    .into_any(),
+            ),
+        ],
+    )?;
+    let mut columns = vec![
+        ("equity", res.equity),
+        ("lots", res.lots),
+        ("fee", res.fee),
+        ("slippage", res.slippage),
+        ("realized", res.realized),
+        ("unrealized", res.unrealized),
+        ("funding", res.funding),
+    ];
+    columns.extend(extra);
+    PyTuple::new(py, [f64_frame(py, columns)?, fills])
+}
+
+macro_rules! fac_strategy {
+    ($py_name: ident, $name: literal, $func: ident, $kwargs: ty) => {
+        #[pyfunction]
+        #[pyo3(name = $name)]
+        #[pyo3(signature = (fac, kwargs, filter=None))]
+        fn $py_name<'py>(
+            py: Python<'py>,
+            fac: &Bound<'py, PyAny>,
+            kwargs: &Bound<'py, PyDict>,
+            filter: Option<&Bound<'py, PyAny>>,
+        ) -> PyResult<PyArr<'py>> {
+            let kwargs: $kwargs = parse_kwargs(kwargs)?;
+            let filter = to_filter(filter)?;
+            let out: Vec<f64> =
+                $func(&to_f64_vec(fac)?, filter.as_ref(), &kwargs).map_err(to_py_err)?;
+            Ok(out.into_pyarray(py))
+        }
+    };
+}
+
+fac_strategy!(py_delay_boll, "delay_boll", delay_boll, DelayBollKwargs);
+fac_strategy!(py_auto_boll, "auto_boll", auto_boll, AutoBollKwargs);
+fac_strategy!(
+    py_auto_tangqian,
+    "auto_tangqian",
+    auto_tangqian,
+    AutoTangQiAnKwargs
+);
+fac_strategy!(py_fix_time, "fix_time", fix_time, FixTimeKwargs);
+fac_strategy!(
+    py_prob_threshold,
+    "prob_threshold",
+    prob_threshold,
+    ProbThresholdKwargs
+);
+fac_strategy!(py_grid, "grid", grid, GridKwargs);
+
+#[pyfunction]
+#[pyo3(name = "boll")]
+#[pyo3(signature = (fac, kwargs, filter=None))]
+fn py_boll<'py>(
+    py: Python<'py>,
+    fac: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    filter: Option<&Bound<'py, PyAny>>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: BollKwargs = parse_kwargs(kwargs)?;
+    let filter = to_filter(filter)?;
+    let fac = to_f64_vec(fac)?;
+    let out: Vec<f64> = boll(&fac, filter.as_ref(), &kwargs);
+    Ok(out.into_pyarray(py))
+}
+
+#[pyfunction]
+#[pyo3(name = "martingale")]
+#[pyo3(signature = (close, kwargs, fac=None, filter=None))]
+fn py_martingale<'py>(
+    py: Python<'py>,
+    close: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    fac: Option<&Bound<'py, PyAny>>,
+    filter: Option<&Bound<'py, PyAny>>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: MartingaleKwargs = parse_kwargs(kwargs)?;
+    let filter = to_filter(filter)?;
+    let fac = fac.map(to_f64_vec).transpose()?;
+    let out: Vec<f64> =
+        martingale_with_fac(&to_f64_vec(close)?, fac.as_ref(), filter.as_ref(), &kwargs)
+            .map_err(to_py_err)?;
+    Ok(out.into_pyarray(py))
+}
+
+#[pyfunction]
+#[pyo3(name = "oscillator_threshold")]
+#[pyo3(signature = (close, kwargs, high=None, low=None, filter=None))]
+fn py_oscillator_threshold<'py>(
+    py: Python<'py>,
+    close: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    high: Option<&Bound<'py, PyAny>>,
+    low: Option<&Bound<'py, PyAny>>,
+    filter: Option<&Bound<'py, PyAny>>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: OscillatorThresholdKwargs = parse_kwargs(kwargs)?;
+    let filter = to_filter(filter)?;
+    let (high, low) = (
+        high.map(to_f64_vec).transpose()?,
+        low.map(to_f64_vec).transpose()?,
+    );
+    let out: Vec<f64> = oscillator_threshold(
+        &to_f64_vec(close)?,
+        high.as_ref().zip(low.as_ref()),
+        filter.as_ref(),
+        &kwargs,
+    )
+    .map_err(to_py_err)?;
+    Ok(out.into_pyarray(py))
+}
+
+/// returns the signal of leg a and leg b
+#[pyfunction]
+#[pyo3(name = "pairs")]
+#[pyo3(signature = (a, b, kwargs, filter=None))]
+fn py_pairs<'py>(
+    py: Python<'py>,
+    a: &Bound<'py, PyAny>,
+    b: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    filter: Option<&Bound<'py, PyAny>>,
+) -> PyResult<(PyArr<'py>, PyArr<'py>)> {
+    let kwargs: PairsKwargs = parse_kwargs(kwargs)?;
+    let filter = to_filter(filter)?;
+    let (leg_a, leg_b): (Vec<f64>, Vec<f64>) =
+        pairs(&to_f64_vec(a)?, &to_f64_vec(b)?, filter.as_ref(), &kwargs).map_err(to_py_err)?;
+    Ok((leg_a.into_pyarray(py), leg_b.into_pyarray(py)))
+}
+
+#[pyfunction]
+#[pyo3(name = "vol_target")]
+fn py_vol_target<'py>(
+    py: Python<'py>,
+    signal: &Bound<'py, PyAny>,
+    close: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: VolTargetKwargs = parse_kwargs(kwargs)?;
+    let out: Vec<f64> =
+        vol_target(&to_f64_vec(signal)?, &to_f64_vec(close)?, &kwargs).map_err(to_py_err)?;
+    Ok(out.into_pyarray(py))
+}
+
+#[pyfunction]
+#[pyo3(name = "combine_signals")]
+fn py_combine_signals<'py>(
+    py: Python<'py>,
+    signals: &Bound<'py, PyList>,
+    kwargs: &Bound<'py, PyDict>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: CombineKwargs = parse_kwargs(kwargs)?;
+    let signals = signals
+        .iter()
+        .map(|s| to_f64_vec(&s))
+        .collect::<PyResult<Vec<_>>>()?;
+    let signals: Vec<&Vec<f64>> = signals.iter().collect();
+    let out: Vec<f64> = combine_signals(&signals, &kwargs).map_err(to_py_err)?;
+    Ok(out.into_pyarray(py))
+}
+
+#[pyfunction]
+#[pyo3(name = "calc_future_ret")]
+#[pyo3(signature = (pos, open, close, kwargs, contract_chg=None))]
+fn py_calc_future_ret<'py>(
+    py: Python<'py>,
+    pos: &Bound<'py, PyAny>,
+    open: &Bound<'py, PyAny>,
+    close: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    contract_chg: Option<&Bound<'py, PyAny>>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: FutureRetKwargs = parse_kwargs(kwargs)?;
+    let out: Vec<f64> = calc_future_ret(
+        &to_f64_vec(pos)?,
+        &to_f64_vec(open)?,
+        &to_f64_vec(close)?,
+        to_contract_chg(contract_chg)?,
+        &kwargs,
+    );
+    Ok(out.into_pyarray(py))
+}
+
+#[pyfunction]
+#[pyo3(name = "calc_future_ret_with_spread")]
+#[pyo3(signature = (pos, open, close, spread, kwargs, contract_chg=None))]
+fn py_calc_future_ret_with_spread<'py>(
+    py: Python<'py>,
+    pos: &Bound<'py, PyAny>,
+    open: &Bound<'py, PyAny>,
+    close: &Bound<'py, PyAny>,
+    spread: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    contract_chg: Option<&Bound<'py, PyAny>>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: FutureRetSpreadKwargs = parse_kwargs(kwargs)?;
+    let out: Vec<f64> = calc_future_ret_with_spread(
+        &to_f64_vec(pos)?,
+        &to_f64_vec(open)?,
+        &to_f64_vec(close)?,
+        &to_f64_vec(spread)?,
+        to_contract_chg(contract_chg)?,
+        &kwargs,
+    );
+    Ok(out.into_pyarray(py))
+}
+
+#[pyfunction]
+#[pyo3(name = "calc_pairs_future_ret")]
+#[allow(clippy::too_many_arguments)]
+fn py_calc_pairs_future_ret<'py>(
+    py: Python<'py>,
+    pos_a: &Bound<'py, PyAny>,
+    pos_b: &Bound<'py, PyAny>,
+    open_a: &Bound<'py, PyAny>,
+    close_a: &Bound<'py, PyAny>,
+    open_b: &Bound<'py, PyAny>,
+    close_b: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: PairsFutureRetKwargs = parse_kwargs(kwargs)?;
+    let out: Vec<f64> = calc_pairs_future_ret(
+        &to_f64_vec(pos_a)?,
+        &to_f64_vec(pos_b)?,
+        &to_f64_vec(open_a)?,
+        &to_f64_vec(close_a)?,
+        &to_f64_vec(open_b)?,
+        &to_f64_vec(close_b)?,
+        &kwargs,
+    );
+    Ok(out.into_pyarray(py))
+}
+
+#[pyfunction]
+#[pyo3(name = "calc_tick_future_ret")]
+#[pyo3(signature = (signal, bid, ask, kwargs, contract_chg=None))]
+fn py_calc_tick_future_ret<'py>(
+    py: Python<'py>,
+    signal: &Bound<'py, PyAny>,
+    bid: &Bound<'py, PyAny>,
+    ask: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    contract_chg: Option<&Bound<'py, PyAny>>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: TickFutureRetKwargs = parse_kwargs(kwargs)?;
+    let out: Vec<f64> = calc_tick_future_ret(
+        &to_f64_vec(signal)?,
+        &to_f64_vec(bid)?,
+        &to_f64_vec(ask)?,
+        to_contract_chg(contract_chg)?.as_ref(),
+        &kwargs,
+    );
+    Ok(out.into_pyarray(py))
+}
+
+/// returns a polars DataFrame with unrealize, realize and open_price columns
+#[pyfunction]
+#[pyo3(name = "calc_tick_future_ret_full")]
+#[pyo3(signature = (signal, bid, ask, kwargs, contract_chg=None))]
+fn py_calc_tick_future_ret_full<'py>(
+    py: Python<'py>,
+    signal: &Bound<'py, PyAny>,
+    bid: &Bound<'py, PyAny>,
+    ask: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    contract_chg: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let kwargs: TickFutureRetFullKwargs = parse_kwargs(kwargs)?;
+    if let SignalType::Percent = kwargs.signal_type {
+        return Err(PyValueError::new_err(
+            "percent signal is not supported by calc_tick_future_ret_full",
+        ));
+    }
+    let profits = calc_tick_future_ret_full(
+        &to_f64_vec(signal)?,
+        &to_f64_vec(bid)?,
+        &to_f64_vec(ask)?,
+        to_contract_chg(contract_chg)?.as_ref(),
+        &kwargs,
+    );
+    let column = |f: fn(&Profit) -> f64| -> Bound<'py, PyAny> {
+        profits
+            .iter()
+            .map(f)
+            .collect::<Vec<_>>()
+            .into_pyarray(py)
+            .into_any()
+    };
+    polars_frame(
+        py,
+        vec![
+            ("unrealize", column(|p| p.unrealize)),
+            ("realize", column(|p| p.realize)),
+            ("open_price", column(|p| p.open_price)),
+        ],
+    )
+}
+
+/// Returns a polars DataFrame with time, side, price and num columns, price
+/// is a single price or a tuple of (bid, ask).
+#[pyfunction]
+#[pyo3(name = "signal_to_trades")]
+fn py_signal_to_trades<'py>(
+    py: Python<'py>,
+    signal: &Bound<'py, PyAny>,
+    price: &Bound<'py, PyAny>,
+    time: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let signal = to_f64_vec(signal)?;
+    let time = to_time_vec(time)?;
+    let trades = if let Ok((bid, ask)) = price.extract::<(Bound<'py, PyAny>, Bound<'py, PyAny>)>() {
+        let (bid, ask) = (to_f64_vec(&bid)?, to_f64_vec(&ask)?);
+        signal_to_trades(
+            signal.titer(),
+            PriceVec::BidAsk(bid.titer(), ask.titer()),
+            time.titer(),
+        )
+    } else {
+        let price = to_f64_vec(price)?;
+        signal_to_trades(
+            signal.titer(),
+            PriceVec::Single(price.titer()),
+            time.titer(),
+        )
+    };
+    let time = trades
+        .iter()
+        .map(|t| t.time.into_i64())
+        .collect::<Vec<_>>()
+        .into_pyarray(py)
+        .call_method1("view", ("datetime64[ns]",))?;
+    let side = PyList::new(py, trades.iter().map(|t| t.side.as_str()))?;
+    let price = trades.iter().map(|t| t.price).collect::<Vec<_>>();
+    let num = trades.iter().map(|t| t.num).collect::<Vec<_>>();
+    polars_frame(
+        py,
+        vec![
+            ("time", time),
+            ("side", side.into_any()),
+            ("price", price.into_pyarray(py).into_any()),
+            ("num", num.into_pyarray(py).into_any()),
+        ],
+    )
+}
+
+/// the same as `calc_future_ret`, but return the frames of a backtest result
+#[pyfunction]
+#[pyo3(name = "calc_future_ret_result")]
+#[pyo3(signature = (pos, open, close, kwargs, contract_chg=None))]
+fn py_calc_future_ret_result<'py>(
+    py: Python<'py>,
+    pos: &Bound<'py, PyAny>,
+    open: &Bound<'py, PyAny>,
+    close: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    contract_chg: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let kwargs: FutureRetKwargs = parse_kwargs(kwargs)?;
+    let res = calc_future_ret_result(
+        &to_f64_vec(pos)?,
+        &to_f64_vec(open)?,
+        &to_f64_vec(close)?,
+        to_contract_chg(contract_chg)?,
+        &kwargs,
+    );
+    result_frames(py, res, vec![])
+}
+
+/// the same as `calc_future_ret_with_spread`, but return the frames of a
+/// backtest result
+#[pyfunction]
+#[pyo3(name = "calc_future_ret_with_spread_result")]
+#[pyo3(signature = (pos, open, close, spread, kwargs, contract_chg=None))]
+fn py_calc_future_ret_with_spread_result<'py>(
+    py: Python<'py>,
+    pos: &Bound<'py, PyAny>,
+    open: &Bound<'py, PyAny>,
+    close: &Bound<'py, PyAny>,
+    spread: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    contract_chg: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let kwargs: FutureRetSpreadKwargs = parse_kwargs(kwargs)?;
+    let res = calc_future_ret_with_spread_result(
+        &to_f64_vec(pos)?,
+        &to_f64_vec(open)?,
+        &to_f64_vec(close)?,
+        &to_f64_vec(spread)?,
+        to_contract_chg(contract_chg)?,
+        &kwargs,
+    );
+    result_frames(py, res, vec![])
+}
+
+/// the same as `calc_pairs_future_ret`, but return the frames of the backtest
+/// results of leg a and leg b
+#[pyfunction]
+#[pyo3(name = "calc_pairs_future_ret_result")]
+#[allow(clippy::too_many_arguments)]
+fn py_calc_pairs_future_ret_result<'py>(
+    py: Python<'py>,
+    pos_a: &Bound<'py, PyAny>,
+    pos_b: &Bound<'py, PyAny>,
+    open_a: &Bound<'py, PyAny>,
+    close_a: &Bound<'py, PyAny>,
+    open_b: &Bound<'py, PyAny>,
+    close_b: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let kwargs: PairsFutureRetKwargs = parse_kwargs(kwargs)?;
+    let (res_a, res_b) = calc_pairs_future_ret_result(
+        &to_f64_vec(pos_a)?,
+        &to_f64_vec(pos_b)?,
+        &to_f64_vec(open_a)?,
+        &to_f64_vec(close_a)?,
+        &to_f64_vec(open_b)?,
+        &to_f64_vec(close_b)?,
+        &kwargs,
+    );
+    PyTuple::new(
+        py,
+        [
+            result_frames(py, res_a, vec![])?,
+            result_frames(py, res_b, vec![])?,
+        ],
+    )
+}
+
+/// the same as `calc_tick_future_ret`, but return the frames of a backtest
+/// result
+#[pyfunction]
+#[pyo3(name = "calc_tick_future_ret_result")]
+#[pyo3(signature = (signal, bid, ask, kwargs, contract_chg=None))]
+fn py_calc_tick_future_ret_result<'py>(
+    py: Python<'py>,
+    signal: &Bound<'py, PyAny>,
+    bid: &Bound<'py, PyAny>,
+    ask: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    contract_chg: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let kwargs: TickFutureRetKwargs = parse_kwargs(kwargs)?;
+    let res = calc_tick_future_ret_result(
+        &to_f64_vec(signal)?,
+        &to_f64_vec(bid)?,
+        &to_f64_vec(ask)?,
+        to_contract_chg(contract_chg)?.as_ref(),
+        &kwargs,
+    );
+    result_frames(py, res, vec![])
+}
+
+/// the same as `calc_tick_future_ret_full`, but return the frames of a
+/// backtest result
+#[pyfunction]
+#[pyo3(name = "calc_tick_future_ret_full_result")]
+#[pyo3(signature = (signal, bid, ask, kwargs, contract_chg=None))]
+fn py_calc_tick_future_ret_full_result<'py>(
+    py: Python<'py>,
+    signal: &Bound<'py, PyAny>,
+    bid: &Bound<'py, PyAny>,
+    ask: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    contract_chg: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let kwargs: TickFutureRetFullKwargs = parse_kwargs(kwargs)?;
+    if let SignalType::Percent = kwargs.signal_type {
+        return Err(PyValueError::new_err(
+            "percent signal is not supported by calc_tick_future_ret_full_result",
+        ));
+    }
+    let res = calc_tick_future_ret_full_result(
+        &to_f64_vec(signal)?,
+        &to_f64_vec(bid)?,
+        &to_f64_vec(ask)?,
+        to_contract_chg(contract_chg)?.as_ref(),
+        &kwargs,
+    );
+    result_frames(py, res, vec![])
+}
+
+#[pyfunction]
+#[pyo3(name = "calc_stock_ret")]
+#[pyo3(signature = (pos, open, close, kwargs, split=None, dividend=None, new_day=None))]
+#[allow(clippy::too_many_arguments)]
+fn py_calc_stock_ret<'py>(
+    py: Python<'py>,
+    pos: &Bound<'py, PyAny>,
+    open: &Bound<'py, PyAny>,
+    close: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    split: Option<&Bound<'py, PyAny>>,
+    dividend: Option<&Bound<'py, PyAny>>,
+    new_day: Option<&Bound<'py, PyAny>>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: StockRetKwargs = parse_kwargs(kwargs)?;
+    let split = split.map(to_f64_vec).transpose()?;
+    let dividend = dividend.map(to_f64_vec).transpose()?;
+    let new_day = new_day.map(to_mask_vec).transpose()?;
+    let out: Vec<f64> = calc_stock_ret(
+        &to_f64_vec(pos)?,
+        &to_f64_vec(open)?,
+        &to_f64_vec(close)?,
+        split.as_ref(),
+        dividend.as_ref(),
+        new_day.as_ref(),
+        &kwargs,
+    );
+    Ok(out.into_pyarray(py))
+}
+
+/// the same as `calc_stock_ret`, but return the frames of a backtest result
+#[pyfunction]
+#[pyo3(name = "calc_stock_ret_result")]
+#[pyo3(signature = (pos, open, close, kwargs, split=None, dividend=None, new_day=None))]
+#[allow(clippy::too_many_arguments)]
+fn py_calc_stock_ret_result<'py>(
+    py: Python<'py>,
+    pos: &Bound<'py, PyAny>,
+    open: &Bound<'py, PyAny>,
+    close: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    split: Option<&Bound<'py, PyAny>>,
+    dividend: Option<&Bound<'py, PyAny>>,
+    new_day: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let kwargs: StockRetKwargs = parse_kwargs(kwargs)?;
+    let split = split.map(to_f64_vec).transpose()?;
+    let dividend = dividend.map(to_f64_vec).transpose()?;
+    let new_day = new_day.map(to_mask_vec).transpose()?;
+    let res = calc_stock_ret_result(
+        &to_f64_vec(pos)?,
+        &to_f64_vec(open)?,
+        &to_f64_vec(close)?,
+        split.as_ref(),
+        dividend.as_ref(),
+        new_day.as_ref(),
+        &kwargs,
+    );
+    result_frames(py, res, vec![])
+}
+
+#[pyfunction]
+#[pyo3(name = "calc_perp_ret")]
+#[pyo3(signature = (signal, bid, ask, kwargs, funding_rate=None, funding_mask=None))]
+fn py_calc_perp_ret<'py>(
+    py: Python<'py>,
+    signal: &Bound<'py, PyAny>,
+    bid: &Bound<'py, PyAny>,
+    ask: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    funding_rate: Option<&Bound<'py, PyAny>>,
+    funding_mask: Option<&Bound<'py, PyAny>>,
+) -> PyResult<PyArr<'py>> {
+    let kwargs: PerpRetKwargs = parse_kwargs(kwargs)?;
+    let funding_rate = funding_rate.map(to_f64_vec).transpose()?;
+    let funding_mask = funding_mask.map(to_mask_vec).transpose()?;
+    let out: Vec<f64> = calc_perp_ret(
+        &to_f64_vec(signal)?,
+        &to_f64_vec(bid)?,
+        &to_f64_vec(ask)?,
+        funding_rate.as_ref(),
+        funding_mask.as_ref(),
+        &kwargs,
+    );
+    Ok(out.into_pyarray(py))
+}
+
+/// the same as `calc_perp_ret`, but return the frames of a backtest result,
+/// the bar frame has an extra liquidation column
+#[pyfunction]
+#[pyo3(name = "calc_perp_ret_result")]
+#[pyo3(signature = (signal, bid, ask, kwargs, funding_rate=None, funding_mask=None))]
+fn py_calc_perp_ret_result<'py>(
+    py: Python<'py>,
+    signal: &Bound<'py, PyAny>,
+    bid: &Bound<'py, PyAny>,
+    ask: &Bound<'py, PyAny>,
+    kwargs: &Bound<'py, PyDict>,
+    funding_rate: Option<&Bound<'py, PyAny>>,
+    funding_mask: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let kwargs: PerpRetKwargs = parse_kwargs(kwargs)?;
+    let funding_rate = funding_rate.map(to_f64_vec).transpose()?;
+    let funding_mask = funding_mask.map(to_mask_vec).transpose()?;
+    let (res, liquidation) = calc_perp_ret_result(
+        &to_f64_vec(signal)?,
+        &to_f64_vec(bid)?,
+        &to_f64_vec(ask)?,
+        funding_rate.as_ref(),
+        funding_mask.as_ref(),
+        &kwargs,
+    );
+    result_frames(py, res, vec![("liquidation", liquidation)])
+}
+
+/// returns a polars boolean Series, null for an invalid time, the first
+/// valid tick is never a funding event
+#[pyfunction]
+#[pyo3(name = "perp_funding_mask")]
+fn py_perp_funding_mask<'py>(
+    py: Python<'py>,
+    time: &Bound<'py, PyAny>,
+    interval: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let mask = perp_funding_mask(&to_time_vec(time)?, interval).map_err(to_py_err)?;
+    py.import("polars")?
+        .call_method1("Series", ("funding_mask", PyList::new(py, mask)?))
+}
+
+/// Option legs are dicts of `option_type` ("call" or "put"), `strike`,
+/// `expiry` (in years), `pos` and an optional `price`. Returns a polars
+/// DataFrame with the columns of the option result. The option legs have no
+/// margin.
+#[pyfunction]
+#[pyo3(name = "calc_option_ret")]
+#[allow(clippy::too_many_arguments)]
+fn py_calc_option_ret<'py>(
+    py: Python<'py>,
+    pos: &Bound<'py, PyAny>,
+    open: &Bound<'py, PyAny>,
+    close: &Bound<'py, PyAny>,
+    time: &Bound<'py, PyAny>,
+    vol: &Bound<'py, PyAny>,
+    legs: &Bound<'py, PyList>,
+    kwargs: &Bound<'py, PyDict>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let kwargs: OptionRetKwargs = parse_kwargs(kwargs)?;
+    // (option type, strike, expiry, pos, price) of each leg
+    let mut leg_data = Vec::with_capacity(legs.len());
+    for leg in legs.iter() {
+        let get = |name: &str| -> PyResult<Bound<'py, PyAny>> {
+            leg.get_item(name)
+                .map_err(|_| PyValueError::new_err(format!("option leg should have {}", name)))
+        };
+        let option_type: String = get("option_type")?.extract()?;
+        let price = match leg.get_item("price") {
+            Ok(price) if !price.is_none() => Some(to_f64_vec(&price)?),
+            _ => None,
+        };
+        leg_data.push((
+            OptionType::parse(&option_type).map_err(to_py_err)?,
+            get("strike")?.extract::<f64>()?,
+            get("expiry")?.extract::<f64>()?,
+            to_f64_vec(&get("pos")?)?,
+            price,
+        ));
+    }
+    let legs = leg_data
+        .iter()
+        .map(|(option_type, strike, expiry, pos, price)| OptionLeg {
+            option_type: *option_type,
+            strike: *strike,
+            expiry: *expiry,
+            pos,
+            price: price.as_ref(),
+        })
+        .collect::<Vec<_>>();
+    let res = calc_option_ret(
+        &to_f64_vec(pos)?,
+        &to_f64_vec(open)?,
+        &to_f64_vec(close)?,
+        &to_f64_vec(time)?,
+        &to_f64_vec(vol)?,
+        &legs,
+        &kwargs,
+    );
+    f64_frame(
+        py,
+        vec![
+            ("equity", res.equity),
+            ("option_value", res.option_value),
+            ("delta", res.delta),
+            ("gamma", res.gamma),
+            ("vega", res.vega),
+            ("theta", res.theta),
+            ("future_pnl", res.future_pnl),
+            ("delta_pnl", res.delta_pnl),
+            ("gamma_pnl", res.gamma_pnl),
+            ("vega_pnl", res.vega_pnl),
+            ("theta_pnl", res.theta_pnl),
+            ("residual_pnl", res.residual_pnl),
+            ("option_cost", res.option_cost),
+            ("hedged_pnl", res.hedged_pnl),
+        ],
+    )
+}
+
+#[pymodule]
+fn tea_strategy(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    macro_rules! add {
+        ($($func: ident),* $(,)?) => {
+            $(m.add_function(wrap_pyfunction!($func, m)?)?;)*
+        };
+    }
+    add!(
+        py_boll,
+        py_delay_boll,
+        py_auto_boll,
+        py_auto_tangqian,
+        py_fix_time,
+        py_prob_threshold,
+        py_grid,
+        py_martingale,
+        py_oscillator_threshold,
+        py_pairs,
+        py_vol_target,
+        py_combine_signals,
+        py_calc_future_ret,
+        py_calc_future_ret_with_spread,
+        py_calc_pairs_future_ret,
+        py_calc_tick_future_ret,
+        py_calc_tick_future_ret_full,
+        py_calc_future_ret_result,
+        py_calc_future_ret_with_spread_result,
+        py_calc_pairs_future_ret_result,
+        py_calc_tick_future_ret_result,
+        py_calc_tick_future_ret_full_result,
+        py_calc_stock_ret,
+        py_calc_stock_ret_result,
+        py_calc_perp_ret,
+        py_calc_perp_ret_result,
+        py_perp_funding_mask,
+        py_calc_option_ret,
+        py_signal_to_trades,
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// kwargs from a json string
+    fn json_dict<'py>(py: Python<'py>, s: &str) -> PyResult<Bound<'py, PyDict>> {
+        Ok(py
+            .import("json")?
+            .call_method1("loads", (s,))?
+            .extract::<Bound<'py, PyDict>>()?)
+    }
+
+    #[test]
+    fn test_python_module() -> PyResult<()> {
+        Python::initialize();
+        Python::attach(|py| {
+            let m = PyModule::new(py, "tea_strategy")?;
+            tea_strategy(&m)?;
+            let boll = m.getattr("boll")?;
+            assert_eq!(boll.getattr("__name__")?.extract::<String>()?, "boll");
+            assert!(m.hasattr("calc_pairs_future_ret")?);
+            for name in [
+                "calc_future_ret_result",
+                "calc_future_ret_with_spread_result",
+                "calc_pairs_future_ret_result",
+                "calc_tick_future_ret_result",
+                "calc_tick_future_ret_full_result",
+                "calc_stock_ret",
+                "calc_stock_ret_result",
+                "calc_perp_ret",
+                "calc_perp_ret_result",
+                "perp_funding_mask",
+                "calc_option_ret",
+            ] {
+                assert!(m.hasattr(name)?, "{} is not exported", name);
+            }
+            // invalid kwargs raise a ValueError
+            let kwargs = json_dict(py, r#"{"params": [4, 1.0]}"#)?;
+            let err = boll.call1((vec![1.0_f64; 5], kwargs)).unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+            // percent signal is not supported by the full tick engine
+            let full_kwargs = r#"{"init_cash": 0, "multiplier": 1.0, "c_rate": 0.0,
+                "blowup": false, "commission_type": "percent", "open_price_method": "average","#;
+            let kwargs = json_dict(py, &format!(r#"{full_kwargs} "signal_type": "percent"}}"#))?;
+            let full = m.getattr("calc_tick_future_ret_full")?;
+            let err = full
+                .call1((vec![1.0_f64], vec![1.0_f64], vec![1.0_f64], kwargs))
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+            assert!(err.to_string().contains("percent signal is not supported"));
+            // arrays are converted by numpy
+            if py.import("numpy").is_err() {
+                return Ok(());
+            }
+            let kwargs = r#"{"params": [4, 1.0, 0.0, null], "min_periods": null,
+                "delay_open": false, "zscore": true, "long_signal": 1.0,
+                "short_signal": -1.0, "close_signal": 0.0"#;
+            let fac: Vec<f64> = (0..20).map(|i| (i as f64 * 0.7).sin()).collect();
+            let out = boll.call1((fac.clone(), json_dict(py, &format!("{kwargs}}}"))?))?;
+            assert_eq!(out.len()?, fac.len());
+            // hold controls without day boundaries count all the bars as one day
+            let kwargs = json_dict(
+                py,
+                &format!(r#"{kwargs}, "control": {{"max_trades_per_day": 1}}}}"#),
+            )?;
+            let out = boll.call1((fac.clone(), kwargs))?;
+            assert_eq!(out.len()?, fac.len());
+            // null contract change signals of the equity engines are
+            // ValueErrors
+            let (signal, bid, ask) = (vec![0., 1., 1.], vec![9., 10., 11.], vec![10., 11., 12.]);
+            let kwargs = json_dict(py, &format!(r#"{full_kwargs} "signal_type": "absolute"}}"#))?;
+            let chg = vec![0., f64::NAN, 0.];
+            let err = full
+                .call1((signal, bid, ask, kwargs, chg.clone()))
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+            let kwargs = json_dict(
+                py,
+                r#"{"init_cash": 10000, "multiplier": 1.0, "leverage": 1.0, "slippage": 0.0,
+                    "c_rate": 0.0, "blowup": false, "commission_type": "percent"}"#,
+            )?;
+            let (pos, price) = (vec![0., 1., 1.], vec![10., 11., 12.]);
+            let err = m
+                .getattr("calc_future_ret")?
+                .call1((
+                    pos.clone(),
+                    price.clone(),
+                    price.clone(),
+                    kwargs.clone(),
+                    chg,
+                ))
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+            // results are a frame of bars and a frame of fills
+            if py.import("polars").is_err() {
+                return Ok(());
+            }
+            let equity = m.getattr("calc_future_ret")?.call1((
+                pos.clone(),
+                price.clone(),
+                price.clone(),
+                kwargs.clone(),
+            ))?;
+            let (bars, fills) = m
+                .getattr("calc_future_ret_result")?
+                .call1((pos, price.clone(), price, kwargs))?
+                .extract::<(Bound<'_, PyAny>, Bound<'_, PyAny>)>()?;
+            let res_equity: Vec<f64> = bars
+                .get_item("equity")?
+                .call_method0("to_list")?
+                .extract()?;
+            assert_eq!(res_equity, equity.extract::<Vec<f64>>()?);
+            assert_eq!(fills.len()?, 1);
+            Ok(())
+        })
+    }
+}
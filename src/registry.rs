@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tevec::prelude::*;
+
+use crate::strategies::*;
+
+/// Input data of strategies in the registry.
+#[derive(Clone, Default)]
+pub struct StrategyData {
+    /// factor of the strategy, also used as close price by strategies
+    /// trading on price when `close` is None
+    pub fac: Vec<f64>,
+    pub close: Option<Vec<f64>>,
+    pub high: Option<Vec<f64>>,
+    pub low: Option<Vec<f64>>,
+    pub filter: Option<StrategyFilter<Vec<Option<bool>>>>,
+    /// time of bars, the day boundaries of `max_trades_per_day` are built
+    /// from it if they are not given in kwargs
+    #[cfg(feature = "time")]
+    pub time: Option<Vec<DateTime>>,
+}
+
+impl StrategyData {
+    #[inline]
+    pub fn new(fac: Vec<f64>) -> Self {
+        Self {
+            fac,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn with_close(mut self, close: Vec<f64>) -> Self {
+        self.close = Some(close);
+        self
+    }
+
+    #[inline]
+    pub fn with_high_low(mut self, high: Vec<f64>, low: Vec<f64>) -> Self {
+        self.high = Some(high);
+        self.low = Some(low);
+        self
+    }
+
+    #[inline]
+    pub fn with_filter(mut self, filter: StrategyFilter<Vec<Option<bool>>>) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    #[cfg(feature = "time")]
+    #[inline]
+    pub fn with_time(mut self, time: Vec<DateTime>) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// fill the day boundaries of the holding controls by the time of bars
+    #[cfg_attr(not(feature = "time"), allow(unused_variables))]
+    fn fill_new_day(&self, control: &mut HoldControl) {
+        #[cfg(feature = "time")]
+        if control.max_trades_per_day.is_some() && control.new_day.is_none() {
+            if let Some(time) = &self.time {
+                *control = std::mem::take(control).with_time(time);
+            }
+        }
+    }
+
+    #[inline]
+    fn close(&self) -> &Vec<f64> {
+        self.close.as_ref().unwrap_or(&self.fac)
+    }
+}
+
+/// A strategy that can be called by name.
+pub type StrategyFn = Box<dyn Fn(&StrategyData, &Value) -> TResult<Vec<f64>> + Send + Sync>;
+
+/// Name and kwargs of a strategy, usually read from a config file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StrategyConfig {
+    pub name: String,
+    #[serde(default)]
+    pub kwargs: Value,
+}
+
+#[inline]
+fn parse_kwargs<K: DeserializeOwned>(name: &str, kwargs: &Value) -> TResult<K> {
+    K::deserialize(kwargs).map_err(|e| terr!("invalid kwargs of strategy {}: {}", name, e))
+}
+
+/// Registry of strategies, strategies are looked up by name and their kwargs
+/// are deserialized from a json value.
+pub struct StrategyRegistry {
+    strategies: HashMap<String, StrategyFn>,
+}
+
+macro_rules! register_fac_strategy {
+    ($registry: expr, $name: expr, $func: ident, $kwargs: ty) => {
+        $registry.register($name, |data, kwargs| {
+            let kwargs: $kwargs = parse_kwargs($name, kwargs)?;
+            $func(&data.fac, data.filter.as_ref(), &kwargs)
+        })
+    };
+    // strategies with holding controls
+    ($registry: expr, $name: expr, $func: ident, $kwargs: ty, control) => {
+        $registry.register($name, |data, kwargs| {
+            let mut kwargs: $kwargs = parse_kwargs($name, kwargs)?;
+            data.fill_new_day(&mut kwargs.control);
+            $func(&data.fac, data.filter.as_ref(), &kwargs)
+        })
+    };
+}
+
+impl Default for StrategyRegistry {
+    /// registry with all builtin single asset strategies
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register("boll", |data, kwargs| {
+            let mut kwargs: BollKwargs = parse_kwargs("boll", kwargs)?;
+            data.fill_new_day(&mut kwargs.control);
+            Ok(boll(&data.fac, data.filter.as_ref(), &kwargs))
+        });
+        register_fac_strategy!(registry, "delay_boll", delay_boll, DelayBollKwargs, control);
+        register_fac_strategy!(registry, "auto_boll", auto_boll, AutoBollKwargs);
+        register_fac_strategy!(registry, "auto_tangqian", auto_tangqian, AutoTangQiAnKwargs);
+        register_fac_strategy!(registry, "fix_time", fix_time, FixTimeKwargs);
+        register_fac_strategy!(
+            registry,
+            "prob_threshold",
+            prob_threshold,
+            ProbThresholdKwargs,
+            control
+        );
+        registry.register("oscillator_threshold", |data, kwargs| {
+            let kwargs: OscillatorThresholdKwargs = parse_kwargs("oscillator_threshold", kwargs)?;
+            let high_low = data.high.as_ref().zip(data.low.as_ref());
+            oscillator_threshold(data.close(), high_low, data.filter.as_ref(), &kwargs)
+        });
+        registry.register("grid", |data, kwargs| {
+            let kwargs: GridKwargs = parse_kwargs("grid", kwargs)?;
+            grid(data.close(), data.filter.as_ref(), &kwargs)
+        });
+        registry.register("martingale", |data, kwargs| {
+            let kwargs: MartingaleKwargs = parse_kwargs("martingale", kwargs)?;
+            let fac = data.close.as_ref().map(|_| &data.fac);
+            martingale_with_fac(data.close(), fac, data.filter.as_ref(), &kwargs)
+        });
+        registry
+    }
+}
+
+impl StrategyRegistry {
+    /// an empty registry
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            strategies: HashMap::new(),
+        }
+    }
+
+    /// Register a strategy, an existing strategy with the same name is replaced.
+    #[inline]
+    pub fn register<F>(&mut self, name: &str, f: F)
+    where
+        F: Fn(&StrategyData, &Value) -> TResult<Vec<f64>> + Send + Sync + 'static,
+    {
+        self.strategies.insert(name.to_string(), Box::new(f));
+    }
+
+    #[inline]
+    pub fn contains(&self, name: &str) -> bool {
+        self.strategies.contains_key(name)
+    }
+
+    /// sorted names of registered strategies
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.strategies.keys().map(|s| s.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn run(&self, name: &str, data: &StrategyData, kwargs: &Value) -> TResult<Vec<f64>> {
+        let f = self
+            .strategies
+            .get(name)
+            .ok_or_else(|| terr!("strategy {} is not registered", name))?;
+        f(data, kwargs)
+    }
+
+    #[inline]
+    pub fn run_config(&self, config: &StrategyConfig, data: &StrategyData) -> TResult<Vec<f64>> {
+        self.run(&config.name, data, &config.kwargs)
+    }
+
+    /// run the strategy with kwargs in a json string
+    pub fn run_json(&self, name: &str, data: &StrategyData, kwargs: &str) -> TResult<Vec<f64>> {
+        let kwargs: Value =
+            serde_json::from_str(kwargs).map_err(|e| terr!("invalid json kwargs: {}", e))?;
+        self.run(name, data, &kwargs)
+    }
+
+    /// run the strategy with kwargs in a toml string
+    #[cfg(feature = "toml")]
+    pub fn run_toml(&self, name: &str, data: &StrategyData, kwargs: &str) -> TResult<Vec<f64>> {
+        let kwargs: Value =
+            toml::from_str(kwargs).map_err(|e| terr!("invalid toml kwargs: {}", e))?;
+        self.run(name, data, &kwargs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() -> TResult<()> {
+        let registry = StrategyRegistry::default();
+        assert!(registry.contains("delay_boll"));
+        let fac = vec![
+            10., 11., 11.9, 10., 11., 12., 10., 11., 12., 13., 14., 10., 7., 5., 4., 3., 4., 4.,
+            3., 2.,
+        ];
+        let data = StrategyData::new(fac.clone());
+        let signal = registry.run_json(
+            "boll",
+            &data,
+            r#"{"params": [4, 1.0, 0.0, null], "min_periods": null, "delay_open": false,
+                "zscore": true, "long_signal": 1.0, "short_signal": -1.0, "close_signal": 0.0}"#,
+        )?;
+        let kwargs = BollKwargs {
+            params: (4, 1.0, 0., None),
+            delay_open: false,
+            ..Default::default()
+        };
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let expect: Vec<f64> = boll(&fac, filter.as_ref(), &kwargs);
+        assert_eq!(signal, expect);
+        let config: StrategyConfig = serde_json::from_str(
+            r#"{"name": "prob_threshold", "kwargs": {"thresholds": [12.0, 11.0, 9.0, 10.0],
+                "per_hand": 1.0, "max_hand": 1.0, "control": {"cooldown": 2}}}"#,
+        )
+        .unwrap();
+        let signal = registry.run_config(&config, &data)?;
+        assert_eq!(signal.len(), fac.len());
+        assert!(registry.run("unknown", &data, &Value::Null).is_err());
+        assert!(registry.run_json("fix_time", &data, "{}").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_registry_hold_control() -> TResult<()> {
+        let registry = StrategyRegistry::default();
+        let fac: Vec<f64> = (0..20).map(|i| 10. + (i as f64 * 0.7).sin()).collect();
+        let data = StrategyData::new(fac.clone());
+        let kwargs = r#"{"params": [4, 0.5, 0.0, null], "min_periods": null, "delay_open": false,
+            "zscore": true, "long_signal": 1.0, "short_signal": -1.0, "close_signal": 0.0,
+            "control": {"max_trades_per_day": 1}}"#;
+        // without day boundaries all the bars are one day
+        let signal = registry.run_json("boll", &data, kwargs)?;
+        let kwargs2: BollKwargs = serde_json::from_str(kwargs).unwrap();
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let expect: Vec<f64> = boll(&fac, filter.as_ref(), &kwargs2);
+        assert_eq!(signal, expect);
+        #[cfg(feature = "time")]
+        {
+            let time: Vec<DateTime> = (0..20)
+                .map(|i| DateTime::parse(&format!("2024-01-{:02} 10:00:00", i / 5 + 1), None))
+                .collect::<TResult<_>>()?;
+            let data = data.with_time(time.clone());
+            let signal = registry.run_json("boll", &data, kwargs)?;
+            let mut kwargs: BollKwargs = serde_json::from_str(kwargs).unwrap();
+            kwargs.control = kwargs.control.with_time(&time);
+            let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+            let expect: Vec<f64> = boll(&fac, filter.as_ref(), &kwargs);
+            assert_eq!(signal, expect);
+        }
+        Ok(())
+    }
+}
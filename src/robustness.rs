@@ -0,0 +1,307 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tevec::prelude::*;
+
+use crate::metrics::Metrics;
+#[cfg(feature = "time")]
+use crate::{Trade, TradeSide};
+
+/// SplitMix64 random number generator, the output only depends on the seed
+/// so the resampling is reproducible across platforms and versions.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// random index in `[0, n)`
+    #[inline]
+    fn gen_index(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ResampleMethod {
+    /// draw returns with replacement
+    #[default]
+    Bootstrap,
+    /// draw blocks of consecutive returns with replacement (circular)
+    BlockBootstrap,
+    /// shuffle the order of returns without replacement
+    Shuffle,
+}
+
+impl ResampleMethod {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "bootstrap" => Ok(ResampleMethod::Bootstrap),
+            "block_bootstrap" | "block" => Ok(ResampleMethod::BlockBootstrap),
+            "shuffle" | "permutation" => Ok(ResampleMethod::Shuffle),
+            _ => tbail!("invalid resample method"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResampleMethod::Bootstrap => "bootstrap",
+            ResampleMethod::BlockBootstrap => "block_bootstrap",
+            ResampleMethod::Shuffle => "shuffle",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ResampleMethod {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        ResampleMethod::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for ResampleMethod {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RobustnessKwargs {
+    #[serde(default)]
+    pub method: ResampleMethod,
+    /// length of each block, only used by block bootstrap
+    pub block_len: Option<usize>,
+    /// number of resampled paths
+    pub n_sims: usize,
+    pub seed: u64,
+    /// confidence level of the intervals, e.g. 0.95
+    pub confidence: f64,
+    /// number of periods in a year, use the number of trades per year when
+    /// resampling trades
+    pub annualize: f64,
+}
+
+impl Default for RobustnessKwargs {
+    fn default() -> Self {
+        Self {
+            method: ResampleMethod::Bootstrap,
+            block_len: None,
+            n_sims: 1000,
+            seed: 0,
+            confidence: 0.95,
+            annualize: 252.,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RobustnessResult {
+    /// resampled equity curves, each starts at 1
+    pub equity: Vec<Vec<f64>>,
+    pub sharpe: Vec<f64>,
+    pub max_drawdown: Vec<f64>,
+    pub sharpe_ci: (f64, f64),
+    pub max_drawdown_ci: (f64, f64),
+}
+
+/// quantile with linear interpolation, NaN values are ignored
+fn quantile(arr: &[f64], q: f64) -> f64 {
+    let mut arr: Vec<f64> = arr.iter().copied().filter(|v| !v.is_nan()).collect();
+    if arr.is_empty() {
+        return f64::NAN;
+    }
+    arr.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let pos = q * (arr.len() - 1) as f64;
+    let (lower, upper) = (pos.floor() as usize, pos.ceil() as usize);
+    arr[lower] + (arr[upper] - arr[lower]) * (pos - lower as f64)
+}
+
+/// Resample a return series and calculate the distribution of sharpe and
+/// max drawdown of the resampled equity curves. Invalid returns are dropped.
+pub fn resample_returns<T: IsNone, V: Vec1View<T>>(
+    ret_vec: &V,
+    kwargs: &RobustnessKwargs,
+) -> TResult<RobustnessResult>
+where
+    T::Inner: Number,
+{
+    tensure!(kwargs.n_sims > 0, "n_sims should be greater than 0");
+    tensure!(
+        kwargs.confidence > 0. && kwargs.confidence < 1.,
+        "confidence should be in (0, 1)"
+    );
+    let rets: Vec<f64> = ret_vec
+        .titer()
+        .filter_map(|v| v.to_opt().map(|v| v.f64()))
+        .filter(|v| v.is_finite())
+        .collect();
+    let n = rets.len();
+    tensure!(n > 0, "no valid return to resample");
+    let block_len = kwargs.block_len.unwrap_or(1);
+    if let ResampleMethod::BlockBootstrap = kwargs.method {
+        tensure!(
+            block_len >= 1 && block_len <= n,
+            "block_len should be in [1, length of returns]"
+        );
+    }
+    let mut rng = SplitMix64(kwargs.seed);
+    let mut sample = Vec::with_capacity(n);
+    let mut equity = Vec::with_capacity(kwargs.n_sims);
+    let mut sharpe = Vec::with_capacity(kwargs.n_sims);
+    let mut max_drawdown = Vec::with_capacity(kwargs.n_sims);
+    for _ in 0..kwargs.n_sims {
+        sample.clear();
+        match kwargs.method {
+            ResampleMethod::Bootstrap => {
+                sample.extend((0..n).map(|_| rets[rng.gen_index(n)]));
+            },
+            ResampleMethod::BlockBootstrap => {
+                while sample.len() < n {
+                    let start = rng.gen_index(n);
+                    let take = block_len.min(n - sample.len());
+                    sample.extend((start..start + take).map(|i| rets[i % n]));
+                }
+            },
+            ResampleMethod::Shuffle => {
+                // Fisher-Yates shuffle
+                sample.extend_from_slice(&rets);
+                for i in (1..n).rev() {
+                    sample.swap(i, rng.gen_index(i + 1));
+                }
+            },
+        }
+        let mut value = 1.;
+        let curve: Vec<f64> = std::iter::once(1.)
+            .chain(sample.iter().map(|r| {
+                value *= 1. + r;
+                value
+            }))
+            .collect();
+        let metrics = Metrics::from_equity(&curve, kwargs.annualize);
+        sharpe.push(metrics.sharpe);
+        max_drawdown.push(metrics.max_drawdown);
+        equity.push(curve);
+    }
+    let (lower, upper) = ((1. - kwargs.confidence) / 2., (1. + kwargs.confidence) / 2.);
+    Ok(RobustnessResult {
+        sharpe_ci: (quantile(&sharpe, lower), quantile(&sharpe, upper)),
+        max_drawdown_ci: (
+            quantile(&max_drawdown, lower),
+            quantile(&max_drawdown, upper),
+        ),
+        equity,
+        sharpe,
+        max_drawdown,
+    })
+}
+
+/// Return of each closing trade, calculated against the average cost of the
+/// position, the size of the trade is ignored.
+#[cfg(feature = "time")]
+pub fn trades_to_returns(trades: &[Trade]) -> Vec<f64> {
+    let mut pos = 0_f64;
+    let mut cost = 0_f64;
+    let mut rets = Vec::new();
+    for trade in trades {
+        if trade.price.is_nan() || trade.num.is_nan() {
+            continue;
+        }
+        let num = match trade.side {
+            TradeSide::Buy => trade.num,
+            TradeSide::Sell => -trade.num,
+        };
+        if pos == 0. || pos.signum() == num.signum() {
+            // open or add position
+            cost = (cost * pos.abs() + trade.price * num.abs()) / (pos.abs() + num.abs());
+        } else {
+            rets.push((trade.price / cost - 1.) * pos.signum());
+            if num.abs() > pos.abs() {
+                // reverse the position
+                cost = trade.price;
+            }
+        }
+        pos += num;
+        if pos.abs() < 1e-12 {
+            pos = 0.;
+            cost = 0.;
+        }
+    }
+    rets
+}
+
+/// Resample the returns of closing trades, see [`trades_to_returns`].
+#[cfg(feature = "time")]
+#[inline]
+pub fn resample_trades(trades: &[Trade], kwargs: &RobustnessKwargs) -> TResult<RobustnessResult> {
+    resample_returns(&trades_to_returns(trades), kwargs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resample_returns() -> TResult<()> {
+        let rets: Vec<f64> = (0..100)
+            .map(|i| 0.001 + 0.01 * ((i * 7 % 11) as f64 - 5.) / 5.)
+            .collect();
+        let mut kwargs = RobustnessKwargs {
+            n_sims: 200,
+            seed: 42,
+            ..Default::default()
+        };
+        let res = resample_returns(&rets, &kwargs)?;
+        assert_eq!(res.equity.len(), 200);
+        assert_eq!(res.equity[0].len(), 101);
+        assert!(res.sharpe_ci.0 < res.sharpe_ci.1);
+        assert!(res.max_drawdown_ci.0 <= res.max_drawdown_ci.1);
+        // the same seed gives the same result
+        let res2 = resample_returns(&rets, &kwargs)?;
+        assert_eq!(res.sharpe, res2.sharpe);
+        kwargs.method = ResampleMethod::BlockBootstrap;
+        kwargs.block_len = Some(10);
+        let res = resample_returns(&rets, &kwargs)?;
+        assert_eq!(res.equity[10].len(), 101);
+        // shuffle keeps the final equity and sharpe
+        kwargs.method = ResampleMethod::Shuffle;
+        let res = resample_returns(&rets, &kwargs)?;
+        let last = res.equity[0][100];
+        for (curve, sharpe) in res.equity.iter().zip(&res.sharpe) {
+            assert!((curve[100] - last).abs() < 1e-10);
+            assert!((sharpe - res.sharpe[0]).abs() < 1e-10);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn test_trades_to_returns() {
+        let time = DateTime::parse("2021-01-01 00:00:00", None).unwrap();
+        let trades = vec![
+            Trade::new(time, TradeSide::Buy, 10., 0.5),
+            Trade::new(time, TradeSide::Buy, 12., 0.5),
+            Trade::new(time, TradeSide::Sell, 12.1, 0.5),
+            Trade::new(time, TradeSide::Sell, 11., 1.5),
+            Trade::new(time, TradeSide::Buy, 9.9, 1.),
+        ];
+        let rets = trades_to_returns(&trades);
+        assert_eq!(rets.len(), 3);
+        assert!((rets[0] - 0.1).abs() < 1e-12);
+        assert!((rets[1] - 0.).abs() < 1e-12);
+        assert!((rets[2] - 0.1).abs() < 1e-12);
+    }
+}
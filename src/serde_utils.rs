@@ -0,0 +1,152 @@
+//! Serde helpers shared by output types.
+
+/// Serialize `NaN` as null so that `f64` fields round-trip through json,
+/// null is deserialized back to `NaN`.
+pub(crate) mod nan_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[inline]
+    pub fn serialize<S: Serializer>(v: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if v.is_nan() {
+            serializer.serialize_none()
+        } else {
+            serializer.serialize_some(v)
+        }
+    }
+
+    #[inline]
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN))
+    }
+}
+
+/// Serialize a `DateTime` as nanoseconds since the unix epoch, `NaT` is
+/// null, so it doesn't need the `serde` feature of tevec.
+#[cfg(feature = "time")]
+pub(crate) mod datetime_as_ns {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use tevec::prelude::DateTime;
+
+    #[inline]
+    pub fn serialize<S: Serializer>(v: &DateTime, serializer: S) -> Result<S::Ok, S::Error> {
+        v.into_opt_i64().serialize(serializer)
+    }
+
+    #[inline]
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime, D::Error> {
+        Ok(DateTime::from_opt_i64(Option::<i64>::deserialize(
+            deserializer,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::Value;
+
+    use crate::{
+        equity::{FutureRetKwargs, Profit, TickFutureRetFullKwargs},
+        BollKwargs, DelayBollKwargs, HoldControl, OrderBook, OrderBookLevel, ProbThresholdKwargs,
+    };
+
+    /// serialize, deserialize and serialize again, the json should be equal
+    fn round_trip<T: Serialize + DeserializeOwned>(v: &T) -> (Value, T) {
+        let json = serde_json::to_value(v).unwrap();
+        let de: T = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&de).unwrap(), json);
+        (json, de)
+    }
+
+    /// round trip through bincode, the json of the output should be equal
+    fn bincode_round_trip<T: Serialize + DeserializeOwned>(v: &T) -> T {
+        let bytes = bincode::serialize(v).unwrap();
+        let de: T = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(
+            serde_json::to_value(&de).unwrap(),
+            serde_json::to_value(v).unwrap()
+        );
+        de
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let book = OrderBook {
+            level1: (10., 9., 1., 2.).into(),
+            level2: (11., 8.).into(),
+            ..Default::default()
+        };
+        let (json, de) = round_trip(&book);
+        assert_eq!(de, book);
+        assert_eq!(json["level2"]["ask_volume"], Value::Null);
+        assert!(de.level3.ask_price.is_nan());
+        let level = OrderBookLevel::default();
+        assert_eq!(round_trip(&level).1, level);
+
+        let profit: Profit = (1., 2., f64::NAN).into();
+        let (_, de) = round_trip(&profit);
+        assert_eq!((de.unrealize, de.realize), (1., 2.));
+        assert!(de.open_price.is_nan());
+
+        let (json, _) = round_trip(&TickFutureRetFullKwargs::default());
+        assert_eq!(json["commission_type"], "percent");
+        assert_eq!(json["signal_type"], "absolute");
+        assert_eq!(json["open_price_method"], "average");
+        let kwargs = FutureRetKwargs {
+            init_cash: 10000,
+            multiplier: 10.,
+            leverage: 1.,
+            slippage: 0.,
+            c_rate: 0.0003,
+            blowup: false,
+            commission_type: "fix".into(),
+        };
+        assert_eq!(round_trip(&kwargs).0["commission_type"], "absolute");
+        let kwargs = BollKwargs {
+            params: (20, 1.5, 0.5, None),
+            ..Default::default()
+        };
+        let (_, de) = round_trip(&kwargs);
+        assert_eq!(de.params, kwargs.params);
+    }
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let control = HoldControl::default()
+            .with_cooldown(2)
+            .with_max_trades_per_day(1, vec![true, false, true]);
+        let kwargs = BollKwargs {
+            params: (20, 1.5, 0.5, Some(3.)),
+            control: control.clone(),
+            ..Default::default()
+        };
+        let de = bincode_round_trip(&kwargs);
+        assert_eq!(de.control.new_day, control.new_day);
+        let de = bincode_round_trip(&BollKwargs::default());
+        assert!(de.control.new_day.is_none());
+        let kwargs = DelayBollKwargs {
+            params: (20, 1., 0., 0.5, None),
+            min_periods: Some(5),
+            long_signal: 1.,
+            short_signal: -1.,
+            close_signal: 0.,
+            control: control.clone().with_max_hold(10),
+        };
+        assert_eq!(bincode_round_trip(&kwargs).control.max_hold, Some(10));
+        let kwargs = ProbThresholdKwargs {
+            thresholds: (0.6, 0.5, 0.4, 0.5),
+            per_hand: 1.,
+            max_hand: 1.,
+            control,
+        };
+        assert_eq!(bincode_round_trip(&kwargs).control.cooldown, Some(2));
+        bincode_round_trip(&TickFutureRetFullKwargs::default());
+        let profit: Profit = (1., 2., f64::NAN).into();
+        assert!(bincode_round_trip(&profit).open_price.is_nan());
+        let book = OrderBook {
+            level1: (10., 9., 1., 2.).into(),
+            ..Default::default()
+        };
+        assert_eq!(bincode_round_trip(&book), book);
+    }
+}
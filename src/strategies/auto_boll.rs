@@ -1,12 +1,12 @@
 use std::collections::VecDeque;
 
 use itertools::izip;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tevec::prelude::*;
 
 use crate::StrategyFilter;
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct AutoBollKwargs {
     // window, open_width, stop_width
     pub params: (usize, f64, f64),
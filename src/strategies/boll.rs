@@ -1,10 +1,11 @@
 use itertools::izip;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tevec::prelude::*;
 
+use super::hold_control::{HoldControl, HoldState};
 use crate::StrategyFilter;
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct BollKwargs {
     /// window, open_width, stop_width, take_profit_width
     pub params: (usize, f64, f64, Option<f64>),
@@ -15,6 +16,9 @@ pub struct BollKwargs {
     pub long_signal: f64,
     pub short_signal: f64,
     pub close_signal: f64,
+    /// holding controls, given as a nested `control` table in configs
+    #[serde(default)]
+    pub control: HoldControl,
 }
 
 impl Default for BollKwargs {
@@ -27,6 +31,7 @@ impl Default for BollKwargs {
             long_signal: 1.0,
             short_signal: -1.0,
             close_signal: 0.0,
+            control: HoldControl::default(),
         }
     }
 }
@@ -98,6 +103,9 @@ macro_rules! boll_logic_impl {
     };
 }
 
+/// Bollinger band strategy.
+///
+/// See [`HoldControl`] for the holding controls in kwargs.
 #[allow(clippy::collapsible_else_if)]
 pub fn boll<O: Vec1<T::Cast<f64>>, T, V: Vec1View<T>, VMask: Vec1View<Option<bool>>>(
     fac_arr: &V,
@@ -112,6 +120,12 @@ where
     let mut last_signal = kwargs.close_signal;
     let mut last_fac = 0.;
     let min_periods = kwargs.min_periods.unwrap_or(kwargs.params.0 / 2);
+    if filter.is_none() && kwargs.control.is_active() {
+        // holding controls are applied through the open flags of filter
+        let filter = StrategyFilter::full(fac_arr.len(), None);
+        return boll(fac_arr, Some(&filter), kwargs);
+    }
+    let mut state = HoldState::new(&kwargs.control, kwargs.close_signal);
     let (middle_arr, std_arr) = if kwargs.zscore {
         let middle_arr: Vec<f64> = fac_arr.ts_vmean(kwargs.params.0, Some(min_periods));
         let std_arr: Vec<f64> = fac_arr.ts_vstd(kwargs.params.0, Some(min_periods));
@@ -130,25 +144,31 @@ where
             if let Some(m3) = kwargs.params.3 {
                 zip_.map(
                     |(fac, middle, std, (long_open, long_stop, short_open, short_stop))| {
-                        boll_logic_impl!(
+                        let (long_open, short_open) = state.open_flags(long_open, short_open);
+                        let prev_signal = last_signal;
+                        let signal = boll_logic_impl!(
                             kwargs, fac, middle, std,
                             last_signal, last_fac,
                             filters=>(long_open, long_stop, short_open, short_stop),
                             profit_p=>m3,
-                        )
-                        .into_cast::<T>()
+                        );
+                        last_signal = state.update(prev_signal, signal);
+                        last_signal.into_cast::<T>()
                     },
                 )
                 .collect_trusted_vec1()
             } else {
                 zip_.map(
                     |(fac, middle, std, (long_open, long_stop, short_open, short_stop))| {
-                        boll_logic_impl!(
+                        let (long_open, short_open) = state.open_flags(long_open, short_open);
+                        let prev_signal = last_signal;
+                        let signal = boll_logic_impl!(
                             kwargs, fac, middle, std,
                             last_signal, last_fac,
                             filters=>(long_open, long_stop, short_open, short_stop),
-                        )
-                        .into_cast::<T>()
+                        );
+                        last_signal = state.update(prev_signal, signal);
+                        last_signal.into_cast::<T>()
                     },
                 )
                 .collect_trusted_vec1()
@@ -157,29 +177,35 @@ where
             if let Some(m3) = kwargs.params.3 {
                 zip_.map(
                     |(fac, middle, std, (long_open, long_stop, short_open, short_stop))| {
-                        boll_logic_impl!(
+                        let (long_open, short_open) = state.open_flags(long_open, short_open);
+                        let prev_signal = last_signal;
+                        let signal = boll_logic_impl!(
                             kwargs, fac, middle, std,
                             last_signal, last_fac,
                             filters=>(long_open, long_stop, short_open, short_stop),
                             long_open=>last_fac < m,
                             short_open=>last_fac > -m,
                             profit_p=>m3,
-                        )
-                        .into_cast::<T>()
+                        );
+                        last_signal = state.update(prev_signal, signal);
+                        last_signal.into_cast::<T>()
                     },
                 )
                 .collect_trusted_vec1()
             } else {
                 zip_.map(
                     |(fac, middle, std, (long_open, long_stop, short_open, short_stop))| {
-                        boll_logic_impl!(
+                        let (long_open, short_open) = state.open_flags(long_open, short_open);
+                        let prev_signal = last_signal;
+                        let signal = boll_logic_impl!(
                             kwargs, fac, middle, std,
                             last_signal, last_fac,
                             filters=>(long_open, long_stop, short_open, short_stop),
                             long_open=>last_fac < m,
                             short_open=>last_fac > -m,
-                        )
-                        .into_cast::<T>()
+                        );
+                        last_signal = state.update(prev_signal, signal);
+                        last_signal.into_cast::<T>()
                     },
                 )
                 .collect_trusted_vec1()
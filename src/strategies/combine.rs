@@ -0,0 +1,205 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tevec::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CombineMethod {
+    /// weighted sum of signals
+    #[default]
+    WeightedSum,
+    /// sign of the majority of the signals, zero if there is no majority
+    MajorityVote,
+    /// mean of the signals only if all of them are non-zero and agree in sign
+    And,
+    /// mean of the non-zero signals if at least one of them is non-zero and
+    /// they do not conflict in sign
+    Or,
+    /// the first non-zero signal wins, signals are ordered by priority
+    Priority,
+    /// net position of sub-strategies, which is the sum of signals
+    Net,
+}
+
+impl CombineMethod {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "weighted_sum" | "weight" | "sum" => Ok(CombineMethod::WeightedSum),
+            "majority_vote" | "vote" => Ok(CombineMethod::MajorityVote),
+            "and" | "all" => Ok(CombineMethod::And),
+            "or" | "any" => Ok(CombineMethod::Or),
+            "priority" | "override" => Ok(CombineMethod::Priority),
+            "net" | "netting" => Ok(CombineMethod::Net),
+            _ => tbail!("invalid combine method"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CombineMethod::WeightedSum => "weighted_sum",
+            CombineMethod::MajorityVote => "majority_vote",
+            CombineMethod::And => "and",
+            CombineMethod::Or => "or",
+            CombineMethod::Priority => "priority",
+            CombineMethod::Net => "net",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CombineMethod {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        CombineMethod::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for CombineMethod {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CombineKwargs {
+    #[serde(default)]
+    pub method: CombineMethod,
+    /// weights of signals, only used by weighted sum, equal weights if None
+    pub weights: Option<Vec<f64>>,
+    /// max absolute value of the combined signal
+    pub cap: Option<f64>,
+}
+
+/// Combine several signals into one position.
+///
+/// Invalid values are treated as a flat position. The combined signal is
+/// clamped by `cap` for every combine method.
+pub fn combine_signals<O: Vec1<T::Cast<f64>>, T, V: Vec1View<T>>(
+    signals: &[&V],
+    kwargs: &CombineKwargs,
+) -> TResult<O>
+where
+    T: IsNone,
+    T::Inner: Number,
+{
+    tensure!(!signals.is_empty(), "signals should not be empty");
+    let len = signals[0].len();
+    for s in signals {
+        tensure!(s.len() == len, "length of signals should be equal");
+    }
+    let k = signals.len();
+    let weights = if let Some(weights) = &kwargs.weights {
+        tensure!(
+            weights.len() == k,
+            "length of weights should be equal to the number of signals"
+        );
+        weights.clone()
+    } else {
+        vec![1. / k as f64; k]
+    };
+    if let Some(cap) = kwargs.cap {
+        tensure!(cap >= 0., "cap should be greater than or equal to 0");
+    }
+    let signals: Vec<Vec<f64>> = signals
+        .iter()
+        .map(|s| {
+            s.titer()
+                .map(|v| v.to_opt().map(|v| v.f64()).unwrap_or(0.))
+                .collect_trusted_to_vec()
+        })
+        .collect();
+    let mut row = vec![0.; k];
+    let out: Vec<f64> = (0..len)
+        .map(|i| {
+            row.iter_mut()
+                .zip(&signals)
+                .for_each(|(v, s)| *v = if s[i].is_nan() { 0. } else { s[i] });
+            let (n_long, n_short) = row.iter().fold((0, 0), |(l, s), v| {
+                (l + (*v > 0.) as usize, s + (*v < 0.) as usize)
+            });
+            let mean_non_zero = || row.iter().fold(0., |a, b| a + b) / (n_long + n_short) as f64;
+            let value = match kwargs.method {
+                CombineMethod::WeightedSum => {
+                    row.iter().zip(&weights).fold(0., |a, (v, w)| a + v * w)
+                },
+                CombineMethod::Net => row.iter().fold(0., |a, b| a + b),
+                CombineMethod::MajorityVote => {
+                    if n_long * 2 > k {
+                        1.
+                    } else if n_short * 2 > k {
+                        -1.
+                    } else {
+                        0.
+                    }
+                },
+                CombineMethod::And => {
+                    if n_long == k || n_short == k {
+                        mean_non_zero()
+                    } else {
+                        0.
+                    }
+                },
+                CombineMethod::Or => {
+                    if (n_long > 0) ^ (n_short > 0) {
+                        mean_non_zero()
+                    } else {
+                        0.
+                    }
+                },
+                CombineMethod::Priority => row.iter().copied().find(|v| *v != 0.).unwrap_or(0.),
+            };
+            if let Some(cap) = kwargs.cap {
+                value.clamp(-cap, cap)
+            } else {
+                value
+            }
+        })
+        .collect();
+    Ok(out
+        .titer()
+        .map(|v| v.into_cast::<T>())
+        .collect_trusted_vec1())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_combine_signals() -> TResult<()> {
+        let s1 = vec![1., 1., 0., -1., f64::NAN, 1.];
+        let s2 = vec![1., -1., 0., -1., 1., 0.];
+        let s3 = vec![0.5, 1., 1., -0.5, 1., 0.];
+        let signals = [&s1, &s2, &s3];
+        let mut kwargs = CombineKwargs {
+            method: CombineMethod::WeightedSum,
+            weights: Some(vec![1., 1., 2.]),
+            cap: Some(2.5),
+        };
+        let out: Vec<f64> = combine_signals(&signals, &kwargs)?;
+        assert_eq!(out, vec![2.5, 2., 2., -2.5, 2.5, 1.]);
+        kwargs.method = CombineMethod::Net;
+        kwargs.cap = None;
+        let out: Vec<f64> = combine_signals(&signals, &kwargs)?;
+        assert_eq!(out, vec![2.5, 1., 1., -2.5, 2., 1.]);
+        kwargs.method = CombineMethod::MajorityVote;
+        let out: Vec<f64> = combine_signals(&signals, &kwargs)?;
+        assert_eq!(out, vec![1., 1., 0., -1., 1., 0.]);
+        kwargs.method = CombineMethod::And;
+        let out: Vec<f64> = combine_signals(&signals, &kwargs)?;
+        assert_eq!(out, vec![2.5 / 3., 0., 0., -2.5 / 3., 0., 0.]);
+        kwargs.method = CombineMethod::Or;
+        let out: Vec<f64> = combine_signals(&signals, &kwargs)?;
+        assert_eq!(out, vec![2.5 / 3., 0., 1., -2.5 / 3., 1., 1.]);
+        kwargs.method = CombineMethod::Priority;
+        let out: Vec<f64> = combine_signals(&signals, &kwargs)?;
+        assert_eq!(out, vec![1., 1., 1., -1., 1., 1.]);
+        Ok(())
+    }
+}
@@ -1,10 +1,11 @@
 use itertools::izip;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tevec::prelude::*;
 
+use super::hold_control::{HoldControl, HoldState};
 use crate::StrategyFilter;
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct DelayBollKwargs {
     // window, open_width, stop_width, delay_width
     pub params: (usize, f64, f64, f64, Option<f64>),
@@ -12,6 +13,9 @@ pub struct DelayBollKwargs {
     pub long_signal: f64,
     pub short_signal: f64,
     pub close_signal: f64,
+    /// holding controls, given as a nested `control` table in configs
+    #[serde(default)]
+    pub control: HoldControl,
 }
 
 macro_rules! boll_logic_impl {
@@ -101,6 +105,12 @@ where
             "open_width should be less than chase_param"
         )
     }
+    if filter.is_none() && kwargs.control.is_active() {
+        // holding controls are applied through the open flags of filter
+        let filter = StrategyFilter::full(fac_arr.len(), None);
+        return delay_boll(fac_arr, Some(&filter), kwargs);
+    }
+    let mut state = HoldState::new(&kwargs.control, kwargs.close_signal);
     // let m = kwargs.params.1;
     let mut last_signal = kwargs.close_signal;
     let mut last_fac = 0.;
@@ -119,11 +129,15 @@ where
         );
         zip_.map(
             |(fac, middle, std, (long_open, long_stop, short_open, short_stop))| {
-                T::inner_cast(boll_logic_impl!(
+                let (long_open, short_open) = state.open_flags(long_open, short_open);
+                let prev_signal = last_signal;
+                let signal = boll_logic_impl!(
                     kwargs, fac, middle, std,
                     last_signal, last_fac, delay_open_flag,
                     filters=>(long_open, long_stop, short_open, short_stop),
-                ))
+                );
+                last_signal = state.update(prev_signal, signal);
+                T::inner_cast(last_signal)
             },
         )
         .collect_trusted_vec1()
@@ -144,3 +158,49 @@ where
     };
     Ok(out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_boll_control() -> TResult<()> {
+        let fac = vec![
+            -0.7, 0.4, 0.6, -0.9, -1.2, -0.2, -0.8, 1.2, 0.3, -0.2, -0.7, -0.3, -0.8, -1.2, 0.5,
+            0.1, -1.9, -0.4, 0.5, -1.8,
+        ];
+        let mut kwargs = DelayBollKwargs {
+            params: (4, 1., 0., 0.5, None),
+            min_periods: None,
+            long_signal: 1.0,
+            short_signal: -1.0,
+            close_signal: 0.0,
+            control: HoldControl::default(),
+        };
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let signal: Vec<f64> = delay_boll(&fac, filter.as_ref(), &kwargs)?;
+        let mut expect = vec![0.; 20];
+        expect[8] = 1.;
+        expect[11..14].copy_from_slice(&[-1., -1., -1.]);
+        assert_eq!(signal, expect);
+        // the short is closed after 2 bars
+        kwargs.control = HoldControl::default().with_max_hold(2);
+        let signal: Vec<f64> = delay_boll(&fac, filter.as_ref(), &kwargs)?;
+        expect[13] = 0.;
+        assert_eq!(signal, expect);
+        // the short is forbidden in the 3 bars after the long is stopped
+        kwargs.control = HoldControl::default().with_cooldown(3);
+        let signal: Vec<f64> = delay_boll(&fac, filter.as_ref(), &kwargs)?;
+        expect[11..13].copy_from_slice(&[0., 0.]);
+        assert_eq!(signal, expect);
+        // one open in the day without day boundaries
+        kwargs.control = HoldControl {
+            max_trades_per_day: Some(1),
+            ..Default::default()
+        };
+        let signal: Vec<f64> = delay_boll(&fac, filter.as_ref(), &kwargs)?;
+        expect[11..14].copy_from_slice(&[0., 0., 0.]);
+        assert_eq!(signal, expect);
+        Ok(())
+    }
+}
@@ -1,10 +1,10 @@
 use itertools::izip;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tevec::prelude::*;
 
 use crate::StrategyFilter;
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct FixTimeKwargs {
     pub n: usize,
     pub pos_map: Option<(Vec<f64>, Vec<f64>)>,
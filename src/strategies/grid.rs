@@ -0,0 +1,257 @@
+use itertools::izip;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tevec::prelude::*;
+
+use super::strategy_filter::FilterElement;
+use crate::StrategyFilter;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GridSpacing {
+    /// spacing is an absolute price difference
+    Absolute,
+    /// spacing is a percent of the center price
+    Percent,
+    /// spacing is a multiple of the rolling std of close
+    Std,
+}
+
+impl GridSpacing {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "fixed" | "absolute" | "fix" => Ok(GridSpacing::Absolute),
+            "percent" | "pct" => Ok(GridSpacing::Percent),
+            "std" => Ok(GridSpacing::Std),
+            _ => tbail!("invalid grid spacing type"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GridSpacing::Absolute => "absolute",
+            GridSpacing::Percent => "percent",
+            GridSpacing::Std => "std",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for GridSpacing {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        GridSpacing::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for GridSpacing {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GridKwargs {
+    pub spacing_type: GridSpacing,
+    pub spacing: f64,
+    /// rolling window of std, only used when spacing type is std
+    pub n: Option<usize>,
+    /// number of grid levels on each side of the center
+    pub levels: usize,
+    /// position of each grid level
+    pub per_level: f64,
+    /// max absolute position
+    pub max_pos: f64,
+    /// re-center the grid and close the position when price breaks out of the grid
+    pub recenter: bool,
+}
+
+impl Default for GridKwargs {
+    fn default() -> Self {
+        Self {
+            spacing_type: GridSpacing::Percent,
+            spacing: 0.01,
+            n: None,
+            levels: 5,
+            per_level: 0.2,
+            max_pos: 1.,
+            recenter: false,
+        }
+    }
+}
+
+fn check_kwargs(kwargs: &GridKwargs) -> TResult<()> {
+    tensure!(kwargs.spacing > 0., "grid spacing should be greater than 0");
+    tensure!(
+        kwargs.levels >= 1,
+        "grid levels should be greater than or equal to 1"
+    );
+    tensure!(
+        kwargs.per_level > 0.,
+        "position per level should be greater than 0"
+    );
+    tensure!(
+        kwargs.per_level <= kwargs.max_pos,
+        "position per level should be less than or equal to max position"
+    );
+    if let GridSpacing::Std = kwargs.spacing_type {
+        tensure!(
+            kwargs.n.is_some(),
+            "rolling window n should be set when spacing type is std"
+        );
+    }
+    Ok(())
+}
+
+/// Grid strategy, buy one level each time the price falls by a grid spacing
+/// below the center and sell one level each time it rises back, the short
+/// side is symmetric above the center.
+///
+/// A level is only reduced after the price moves back by a full spacing, so
+/// each round trip of a level earns one grid spacing.
+pub fn grid<O: Vec1<T::Cast<f64>>, T, V: Vec1View<T>, VMask: Vec1View<Option<bool>>>(
+    close_vec: &V,
+    filter: Option<&StrategyFilter<VMask>>,
+    kwargs: &GridKwargs,
+) -> TResult<O>
+where
+    T: IsNone,
+    T::Inner: Number,
+{
+    check_kwargs(kwargs)?;
+    let std_vec: Vec<f64> = if let GridSpacing::Std = kwargs.spacing_type {
+        close_vec.ts_vstd(kwargs.n.unwrap(), None)
+    } else {
+        vec![f64::NAN; close_vec.len()]
+    };
+    let levels = kwargs.levels as f64;
+    // the level is capped by max position, so that it does not drift beyond
+    // the position held
+    let max_level = levels.min((kwargs.max_pos / kwargs.per_level + 1e-9).floor());
+    let mut center = f64::NAN;
+    let mut step = f64::NAN;
+    // current grid level, positive means long and negative means short
+    let mut level = 0_f64;
+    let get_step = |center: f64, std: f64| match kwargs.spacing_type {
+        GridSpacing::Absolute => kwargs.spacing,
+        GridSpacing::Percent => kwargs.spacing * center,
+        GridSpacing::Std => kwargs.spacing * std,
+    };
+    let mut logic =
+        |close: T, std: f64, (long_open, long_stop, short_open, short_stop): FilterElement| {
+            if close.not_none() {
+                let close = close.unwrap().f64();
+                if center.is_nan() {
+                    // initialize the grid
+                    let new_step = get_step(close, std);
+                    if new_step.not_none() && new_step > 0. {
+                        center = close;
+                        step = new_step;
+                    }
+                } else {
+                    let x = (center - close) / step;
+                    let new_level = if x >= level + 1. {
+                        x.floor()
+                    } else if x <= level - 1. {
+                        x.ceil()
+                    } else {
+                        level
+                    }
+                    .clamp(-max_level, max_level);
+                    // an increase of the position is blocked by the open filter
+                    let blocked =
+                        (new_level > level && new_level > 0. && !long_open.unwrap_or(true))
+                            || (new_level < level && new_level < 0. && !short_open.unwrap_or(true));
+                    if !blocked {
+                        level = new_level;
+                    }
+                    if (level > 0. && long_stop.unwrap_or(false))
+                        || (level < 0. && short_stop.unwrap_or(false))
+                    {
+                        level = 0.;
+                    }
+                    if kwargs.recenter && x.abs() > levels + 1. {
+                        // price breaks out of the grid
+                        let new_step = get_step(close, std);
+                        if new_step.not_none() && new_step > 0. {
+                            center = close;
+                            step = new_step;
+                        }
+                        level = 0.;
+                    }
+                }
+            }
+            (level * kwargs.per_level).into_cast::<T>()
+        };
+    let out = if let Some(filter) = filter {
+        izip!(close_vec.titer(), std_vec.titer(), filter.titer())
+            .map(|(close, std, filter)| logic(close, std, filter))
+            .collect_trusted_vec1()
+    } else {
+        izip!(close_vec.titer(), std_vec.titer())
+            .map(|(close, std)| logic(close, std, (None, None, None, None)))
+            .collect_trusted_vec1()
+    };
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_grid() -> TResult<()> {
+        let close = vec![
+            10., 9.5, 9., 8.5, 8., 7., 8.2, 9., 9.6, 10., 11., 12., 13.5, 14.,
+        ];
+        let mut kwargs = GridKwargs {
+            spacing_type: GridSpacing::Absolute,
+            spacing: 1.,
+            n: None,
+            levels: 2,
+            per_level: 0.5,
+            max_pos: 1.,
+            recenter: false,
+        };
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let signal: Vec<f64> = grid(&close, filter.as_ref(), &kwargs)?;
+        let expect = vec![
+            0., 0., 0.5, 0.5, 1., 1., 1., 0.5, 0.5, 0., -0.5, -1., -1., -1.,
+        ];
+        assert_eq!(signal, expect);
+        kwargs.recenter = true;
+        let signal: Vec<f64> = grid(&close, filter.as_ref(), &kwargs)?;
+        let expect = vec![
+            0., 0., 0.5, 0.5, 1., 1., 1., 0.5, 0.5, 0., -0.5, -1., 0., 0.,
+        ];
+        assert_eq!(signal, expect);
+        Ok(())
+    }
+
+    #[test]
+    fn test_grid_max_pos() -> TResult<()> {
+        let close = vec![10., 9., 8., 7., 6., 7., 8., 9., 10., 6.5, 6.];
+        let kwargs = GridKwargs {
+            spacing_type: GridSpacing::Absolute,
+            spacing: 1.,
+            n: None,
+            levels: 4,
+            per_level: 0.3,
+            max_pos: 0.6,
+            recenter: false,
+        };
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let signal: Vec<f64> = grid(&close, filter.as_ref(), &kwargs)?;
+        // the position is capped at 2 levels, and it is reduced a level after
+        // the price rises back by a spacing from the capped level
+        let expect = vec![0., 0.3, 0.6, 0.6, 0.6, 0.6, 0.6, 0.3, 0., 0.6, 0.6];
+        assert_eq!(signal, expect);
+        Ok(())
+    }
+}
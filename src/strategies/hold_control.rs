@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+use tevec::prelude::*;
+
+/// Holding controls shared by threshold strategies.
+///
+/// The controls are never validated, every setting has a defined meaning:
+/// a `max_hold` of 0 is the same as 1, and without `new_day` (or past its
+/// end) `max_trades_per_day` counts the bars as one day.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct HoldControl {
+    /// number of bars that opening is forbidden after a stop
+    pub cooldown: Option<usize>,
+    /// close the position after it has been held for n bars
+    pub max_hold: Option<usize>,
+    /// max number of opens in a day, `new_day` should be set
+    pub max_trades_per_day: Option<usize>,
+    /// whether the bar is the first bar of a new day, given in the config or
+    /// built from the time of bars by [`HoldControl::with_time`]
+    #[serde(default)]
+    pub new_day: Option<Vec<bool>>,
+}
+
+impl HoldControl {
+    #[inline]
+    pub fn with_cooldown(mut self, n: usize) -> Self {
+        self.cooldown = Some(n);
+        self
+    }
+
+    #[inline]
+    pub fn with_max_hold(mut self, n: usize) -> Self {
+        self.max_hold = Some(n);
+        self
+    }
+
+    #[inline]
+    pub fn with_max_trades_per_day(mut self, n: usize, new_day: Vec<bool>) -> Self {
+        self.max_trades_per_day = Some(n);
+        self.new_day = Some(new_day);
+        self
+    }
+
+    /// Set the day boundaries from the time of bars, a bar whose date
+    /// differs from the previous valid bar starts a new day.
+    #[cfg(feature = "time")]
+    pub fn with_time<V: Vec1View<DateTime>>(mut self, time_vec: &V) -> Self {
+        let mut last_date = None;
+        let new_day = time_vec
+            .titer()
+            .map(|dt| {
+                let date = dt.year().and_then(|y| Some((y, dt.month()?, dt.day()?)));
+                if date.is_some() && date != last_date {
+                    last_date = date;
+                    true
+                } else {
+                    false
+                }
+            })
+            .collect();
+        self.new_day = Some(new_day);
+        self
+    }
+
+    #[inline]
+    pub(super) fn is_active(&self) -> bool {
+        self.cooldown.is_some() || self.max_hold.is_some() || self.max_trades_per_day.is_some()
+    }
+}
+
+/// Running state of [`HoldControl`], `open_flags` should be called before
+/// the strategy logic of each bar and `update` after it.
+pub(super) struct HoldState<'a> {
+    control: &'a HoldControl,
+    close_signal: f64,
+    idx: usize,
+    cooldown_left: usize,
+    hold_bars: usize,
+    trades_today: usize,
+}
+
+impl<'a> HoldState<'a> {
+    #[inline]
+    pub fn new(control: &'a HoldControl, close_signal: f64) -> Self {
+        Self {
+            control,
+            close_signal,
+            idx: 0,
+            cooldown_left: 0,
+            hold_bars: 0,
+            trades_today: 0,
+        }
+    }
+
+    /// merge the open flags of the filter with the holding controls
+    #[inline]
+    pub fn open_flags(
+        &mut self,
+        long_open: Option<bool>,
+        short_open: Option<bool>,
+    ) -> (Option<bool>, Option<bool>) {
+        if let Some(new_day) = &self.control.new_day {
+            if new_day.get(self.idx).unwrap_or(false) {
+                self.trades_today = 0;
+            }
+        }
+        let allow = self.cooldown_left == 0
+            && self
+                .control
+                .max_trades_per_day
+                .map(|n| self.trades_today < n)
+                .unwrap_or(true);
+        if allow {
+            (long_open, short_open)
+        } else {
+            (Some(false), Some(false))
+        }
+    }
+
+    /// Update the state by the signal of last bar and the signal given by
+    /// the strategy, return the signal after the holding controls.
+    #[inline]
+    pub fn update(&mut self, last_signal: f64, signal: f64) -> f64 {
+        self.idx += 1;
+        let close = self.close_signal;
+        let mut signal = signal;
+        let opened = signal != close
+            && (last_signal == close
+                || (signal - close).signum() != (last_signal - close).signum());
+        let mut stopped = last_signal != close && signal == close;
+        if opened {
+            self.trades_today += 1;
+            self.hold_bars = 0;
+            if last_signal != close {
+                // reverse the position
+                stopped = true;
+            }
+        } else if signal != close {
+            self.hold_bars += 1;
+            if let Some(max_hold) = self.control.max_hold {
+                if self.hold_bars >= max_hold {
+                    signal = close;
+                    stopped = true;
+                }
+            }
+        }
+        if stopped {
+            self.cooldown_left = self.control.cooldown.unwrap_or(0);
+        } else if self.cooldown_left > 0 {
+            self.cooldown_left -= 1;
+        }
+        signal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{boll, prob_threshold, BollKwargs, ProbThresholdKwargs, StrategyFilter};
+
+    #[test]
+    fn test_hold_control() -> TResult<()> {
+        let prob_vec = vec![0.7, 0.55, 0.55, 0.55, 0.45, 0.7, 0.7, 0.55, 0.45, 0.7];
+        let mut kwargs = ProbThresholdKwargs {
+            thresholds: (0.6, 0.5, 0.4, 0.5),
+            per_hand: 1.,
+            max_hand: 1.,
+            control: HoldControl::default(),
+        };
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let signal: Vec<f64> = prob_threshold(&prob_vec, filter.as_ref(), &kwargs)?;
+        assert_eq!(signal, vec![1., 1., 1., 1., 0., 1., 1., 1., 0., 1.]);
+        kwargs.control = HoldControl::default().with_max_hold(2);
+        let signal: Vec<f64> = prob_threshold(&prob_vec, filter.as_ref(), &kwargs)?;
+        assert_eq!(signal, vec![1., 1., 0., 0., 0., 1., 1., 0., 0., 1.]);
+        kwargs.control = HoldControl::default().with_cooldown(1);
+        let signal: Vec<f64> = prob_threshold(&prob_vec, filter.as_ref(), &kwargs)?;
+        assert_eq!(signal, vec![1., 1., 1., 1., 0., 0., 1., 1., 0., 0.]);
+        let mut new_day = vec![false; 10];
+        new_day[0] = true;
+        new_day[5] = true;
+        kwargs.control = HoldControl::default().with_max_trades_per_day(1, new_day);
+        let signal: Vec<f64> = prob_threshold(&prob_vec, filter.as_ref(), &kwargs)?;
+        assert_eq!(signal, vec![1., 1., 1., 1., 0., 1., 1., 1., 0., 0.]);
+        // new_day is kept by a serde round trip
+        let json = serde_json::to_string(&kwargs).unwrap();
+        let kwargs2: ProbThresholdKwargs = serde_json::from_str(&json).unwrap();
+        let signal2: Vec<f64> = prob_threshold(&prob_vec, filter.as_ref(), &kwargs2)?;
+        assert_eq!(signal2, signal);
+        // without new_day all the bars are one day
+        kwargs.control.new_day = None;
+        let signal: Vec<f64> = prob_threshold(&prob_vec, filter.as_ref(), &kwargs)?;
+        assert_eq!(signal, vec![1., 1., 1., 1., 0., 0., 0., 0., 0., 0.]);
+        let boll_kwargs = BollKwargs {
+            control: kwargs.control.clone(),
+            ..BollKwargs::new(4, 1.)
+        };
+        let signal: Vec<f64> = boll(&prob_vec, filter.as_ref(), &boll_kwargs);
+        assert_eq!(signal.len(), prob_vec.len());
+        // a max_hold of 0 is the same as 1
+        kwargs.control = HoldControl::default().with_max_hold(0);
+        let signal: Vec<f64> = prob_threshold(&prob_vec, filter.as_ref(), &kwargs)?;
+        kwargs.control = HoldControl::default().with_max_hold(1);
+        let expect: Vec<f64> = prob_threshold(&prob_vec, filter.as_ref(), &kwargs)?;
+        assert_eq!(signal, expect);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hold_control_from_config() -> TResult<()> {
+        let prob_vec = vec![0.7, 0.55, 0.45, 0.7, 0.45, 0.7, 0.7, 0.55, 0.45, 0.7];
+        let kwargs: ProbThresholdKwargs = serde_json::from_str(
+            r#"{"thresholds": [0.6, 0.5, 0.4, 0.5], "per_hand": 1.0, "max_hand": 1.0,
+                "control": {"max_trades_per_day": 1,
+                "new_day": [true, false, false, false, false, true, false, false, false, false]}}"#,
+        )
+        .unwrap();
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let signal: Vec<f64> = prob_threshold(&prob_vec, filter.as_ref(), &kwargs)?;
+        assert_eq!(signal, vec![1., 1., 0., 0., 0., 1., 1., 1., 0., 0.]);
+        Ok(())
+    }
+}
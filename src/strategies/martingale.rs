@@ -1,7 +1,8 @@
 use itertools::izip;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tevec::prelude::*;
 
+use super::strategy_filter::FilterElement;
 use crate::StrategyFilter;
 
 #[inline]
@@ -15,7 +16,58 @@ fn arc_kelly(pos: f64, b: f64) -> f64 {
     (pos * b + 1.) / (b + 1.)
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Direction {
+    #[default]
+    Long,
+    Short,
+    /// direction is decided by the sign of a factor
+    Both,
+}
+
+impl Direction {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "long" => Ok(Direction::Long),
+            "short" => Ok(Direction::Short),
+            "both" | "fac" => Ok(Direction::Both),
+            _ => tbail!("invalid direction"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Long => "long",
+            Direction::Short => "short",
+            Direction::Both => "both",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Direction {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Direction::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Direction {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct MartingaleKwargs {
     pub n: usize,            // rolling window
     pub step: Option<usize>, // adjust step
@@ -23,16 +75,46 @@ pub struct MartingaleKwargs {
     pub win_p_addup: Option<f64>,
     pub pos_mul: Option<f64>,
     pub take_profit: f64,
-    // pub stop_loss: f64,
     pub b: f64, // profit loss ratio
+    /// close the position if the loss since the start of the current
+    /// martingale cycle is greater than `stop_loss_m` times std
     pub stop_loss_m: Option<f64>,
+    #[serde(default)]
+    pub direction: Direction,
+    /// anti-martingale, scale up after wins and reset after losses
+    #[serde(default)]
+    pub anti: bool,
 }
 
+/// Martingale strategy on close price, see [`martingale_with_fac`] for
+/// trading both sides driven by a factor.
 pub fn martingale<O: Vec1<T::Cast<f64>>, T, V: Vec1View<T>, VMask: Vec1View<Option<bool>>>(
     close_vec: &V,
     filter: Option<&StrategyFilter<VMask>>,
     kwargs: &MartingaleKwargs,
 ) -> TResult<O>
+where
+    T::Inner: Number,
+    T: IsNone,
+{
+    martingale_with_fac(close_vec, None, filter, kwargs)
+}
+
+/// Martingale strategy on close price.
+///
+/// If direction is `Both`, the sign of `fac_vec` decides the side of the
+/// martingale cycle, a new cycle is started when the sign changes.
+pub fn martingale_with_fac<
+    O: Vec1<T::Cast<f64>>,
+    T,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+>(
+    close_vec: &V,
+    fac_vec: Option<&V>,
+    filter: Option<&StrategyFilter<VMask>>,
+    kwargs: &MartingaleKwargs,
+) -> TResult<O>
 where
     T::Inner: Number,
     T: IsNone,
@@ -44,131 +126,209 @@ where
             && !(kwargs.win_p_addup.is_some() && kwargs.pos_mul.is_some()),
         "win_p_addup and pos_mul should be exclusive"
     );
+    if let Direction::Both = kwargs.direction {
+        tensure!(
+            fac_vec.is_some(),
+            "factor should be provided when direction is both"
+        );
+    }
     let win_p_flag = kwargs.win_p_addup.is_some();
-    let mut win_p = init_win_p; // probability of win
+    // probability of win
+    let mut win_p = init_win_p;
+    // absolute position and side of the position
     let mut last_signal = kwargs.init_pos;
+    let mut side = match kwargs.direction {
+        Direction::Short => -1.,
+        _ => 1.,
+    };
+    let mut side_init = !matches!(kwargs.direction, Direction::Both);
     let mut open_price: Option<f64> = None;
+    // price at the start of current martingale cycle
+    let mut cycle_price: Option<f64> = None;
     let mut current_step = 0;
     // let middle_vec: O = close_vec.ts_vmean(kwargs.n, None);
     let std_vec: Vec<f64> = close_vec.ts_vstd(kwargs.n, None);
     let step = kwargs.step.unwrap_or(1);
-    let out = if let Some(filter) = filter {
-        izip!(close_vec.titer(), std_vec.titer(), filter.titer(),)
-            .map(|(close, std, (long_open, _, _, _))| {
-                if close.is_none() | std.is_none() {
-                    return last_signal.into_cast::<T>();
-                }
-                let close = close.unwrap().f64();
-                let std = std.unwrap().f64();
-                current_step += 1;
-                if current_step >= step {
-                    // adjust position
-                    current_step = 0;
-                    if let Some(op) = open_price {
-                        let profit = close - op;
-                        if let Some(long_open) = long_open {
-                            if !long_open {
-                                // stop loss in downtrend
-                                win_p = init_win_p;
-                                last_signal = 0.;
-                                open_price = Some(close);
-                                return 0f64.into_cast::<T>();
-                            }
-                        }
-                        if profit > std * kwargs.take_profit {
-                            // take profit and reset win probability
-                            win_p = init_win_p;
-                            last_signal = kwargs.init_pos;
-                            open_price = Some(close);
-                        } else if profit < -std * kwargs.take_profit {
-                            // increment win probability
-                            if win_p_flag {
-                                win_p += kwargs.win_p_addup.unwrap();
-                                if win_p > 1. {
-                                    win_p = 1.;
-                                }
-                                last_signal = kelly(win_p, b);
-                            } else {
-                                if last_signal != 0. {
-                                    last_signal *= kwargs.pos_mul.unwrap();
-                                } else {
-                                    // in this case, we just finish stop loss
-                                    // in downtrend
-                                    last_signal = kwargs.init_pos;
-                                }
+    let fac_vec: Vec<f64> = if let Some(fac_vec) = fac_vec {
+        fac_vec
+            .titer()
+            .map(|v| v.to_opt().map(|v| v.f64()).unwrap_or(f64::NAN))
+            .collect_trusted_to_vec()
+    } else {
+        vec![f64::NAN; close_vec.len()]
+    };
+    // increase the position of the martingale cycle
+    let scale_up = |last_signal: &mut f64, win_p: &mut f64| {
+        if win_p_flag {
+            *win_p += kwargs.win_p_addup.unwrap();
+            if *win_p > 1. {
+                *win_p = 1.;
+            }
+            *last_signal = kelly(*win_p, b);
+        } else {
+            if *last_signal != 0. {
+                *last_signal *= kwargs.pos_mul.unwrap();
+            } else {
+                // in this case, we just finish stop loss
+                // in downtrend
+                *last_signal = kwargs.init_pos;
+            }
 
-                                if last_signal > 1. {
-                                    last_signal = 1.;
-                                }
-                            }
-                            open_price = Some(close)
-                        } else {
-                            // just keep position
-                        }
+            if *last_signal > 1. {
+                *last_signal = 1.;
+            }
+        }
+    };
+    let mut logic =
+        |close: T, std: f64, fac: f64, (long_open, _, short_open, _): FilterElement| -> f64 {
+            if close.is_none() || std.is_none() || !side_init && fac.is_none() {
+                return side * last_signal;
+            }
+            let close = close.unwrap().f64();
+            if let Direction::Both = kwargs.direction {
+                let fac_side = if fac > 0. {
+                    1.
+                } else if fac < 0. {
+                    -1.
+                } else {
+                    side
+                };
+                if !side_init || fac_side != side {
+                    // start a new cycle on the other side
+                    side = fac_side;
+                    side_init = true;
+                    win_p = init_win_p;
+                    last_signal = kwargs.init_pos;
+                    open_price = Some(close);
+                    cycle_price = Some(close);
+                    current_step = 0;
+                    return side * last_signal;
+                }
+            }
+            current_step += 1;
+            if current_step >= step {
+                // adjust position
+                current_step = 0;
+                if let Some(op) = open_price {
+                    let profit = (close - op) * side;
+                    let open_cond = if side > 0. { long_open } else { short_open };
+                    let stop_loss = if let (Some(m), Some(cp)) = (kwargs.stop_loss_m, cycle_price) {
+                        (close - cp) * side < -std * m
                     } else {
+                        false
+                    };
+                    if !open_cond.unwrap_or(true) || stop_loss {
+                        // stop loss in downtrend or hit the hard stop loss
+                        win_p = init_win_p;
+                        last_signal = 0.;
                         open_price = Some(close);
+                        cycle_price = None;
+                        return 0.;
                     }
-                    last_signal.into_cast::<T>()
-                } else {
-                    last_signal.into_cast::<T>()
-                }
-                // 是否止盈或止损
-            })
-            .collect_trusted_vec1()
-    } else {
-        izip!(close_vec.titer(), std_vec.titer(),)
-            .map(|(close, std)| {
-                if close.is_none() || std.is_none() {
-                    return last_signal.into_cast::<T>();
-                }
-                let close = close.unwrap().f64();
-                let std = std.unwrap().f64();
-                current_step += 1;
-                if current_step >= step {
-                    // adjust position
-                    current_step = 0;
-                    if let Some(op) = open_price {
-                        let profit = close - op;
-                        if profit > std * kwargs.take_profit {
-                            // take profit and reset win probability
-                            win_p = init_win_p;
-                            last_signal = kwargs.init_pos;
-                            open_price = Some(close);
-                        } else if profit < -std * kwargs.take_profit {
-                            // increment win probability
-                            if win_p_flag {
-                                win_p += kwargs.win_p_addup.unwrap();
-                                if win_p > 1. {
-                                    win_p = 1.;
-                                }
-                                last_signal = kelly(win_p, b);
-                            } else {
-                                if last_signal != 0. {
-                                    last_signal *= kwargs.pos_mul.unwrap();
-                                } else {
-                                    // in this case, we just finish stop loss
-                                    // in downtrend
-                                    last_signal = kwargs.init_pos;
-                                }
-
-                                if last_signal > 1. {
-                                    last_signal = 1.;
-                                }
-                            }
-                            open_price = Some(close)
-                        } else {
-                            // just keep position
-                        }
+                    let (win, loss) = (
+                        profit > std * kwargs.take_profit,
+                        profit < -std * kwargs.take_profit,
+                    );
+                    let (reset, scale) = if kwargs.anti {
+                        (loss, win)
                     } else {
+                        (win, loss)
+                    };
+                    if reset {
+                        // reset win probability and start a new cycle
+                        win_p = init_win_p;
+                        last_signal = kwargs.init_pos;
                         open_price = Some(close);
+                        cycle_price = Some(close);
+                    } else if scale {
+                        if last_signal == 0. {
+                            cycle_price = Some(close);
+                        }
+                        scale_up(&mut last_signal, &mut win_p);
+                        open_price = Some(close)
+                    } else {
+                        // just keep position
                     }
-                    last_signal.into_cast::<T>()
                 } else {
-                    last_signal.into_cast::<T>()
+                    open_price = Some(close);
+                    cycle_price = Some(close);
                 }
-                // 是否止盈或止损
+            }
+            side * last_signal
+        };
+    let out = if let Some(filter) = filter {
+        izip!(
+            close_vec.titer(),
+            std_vec.titer(),
+            fac_vec.titer(),
+            filter.titer(),
+        )
+        .map(|(close, std, fac, filter)| logic(close, std, fac, filter).into_cast::<T>())
+        .collect_trusted_vec1()
+    } else {
+        izip!(close_vec.titer(), std_vec.titer(), fac_vec.titer())
+            .map(|(close, std, fac)| {
+                logic(close, std, fac, (None, None, None, None)).into_cast::<T>()
             })
             .collect_trusted_vec1()
     };
     Ok(out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_martingale() -> TResult<()> {
+        let close = vec![10., 11., 10., 11., 10., 9., 8., 7., 8., 9., 10., 11.];
+        let mut kwargs = MartingaleKwargs {
+            n: 2,
+            step: None,
+            init_pos: 0.25,
+            win_p_addup: None,
+            pos_mul: Some(2.),
+            take_profit: 0.5,
+            b: 1.,
+            stop_loss_m: None,
+            direction: Direction::Long,
+            anti: false,
+        };
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let signal: Vec<f64> = martingale(&close, filter.as_ref(), &kwargs)?;
+        let expect = vec![
+            0.25, 0.25, 0.5, 0.25, 0.5, 1., 1., 1., 0.25, 0.25, 0.25, 0.25,
+        ];
+        assert_eq!(signal, expect);
+        // short side is the mirror of long side
+        kwargs.direction = Direction::Short;
+        let signal: Vec<f64> = martingale(&close, filter.as_ref(), &kwargs)?;
+        let expect = vec![
+            -0.25, -0.25, -0.25, -0.5, -0.25, -0.25, -0.25, -0.25, -0.5, -1., -1., -1.,
+        ];
+        assert_eq!(signal, expect);
+        // hard stop loss
+        kwargs.direction = Direction::Long;
+        kwargs.stop_loss_m = Some(2.);
+        let signal: Vec<f64> = martingale(&close, filter.as_ref(), &kwargs)?;
+        let expect = vec![
+            0.25, 0.25, 0.5, 0.25, 0.5, 0., 0.25, 0.5, 0.25, 0.25, 0.25, 0.25,
+        ];
+        assert_eq!(signal, expect);
+        // anti-martingale
+        kwargs.stop_loss_m = None;
+        kwargs.anti = true;
+        let signal: Vec<f64> = martingale(&close, filter.as_ref(), &kwargs)?;
+        let expect = vec![
+            0.25, 0.25, 0.25, 0.5, 0.25, 0.25, 0.25, 0.25, 0.5, 1., 1., 1.,
+        ];
+        assert_eq!(signal, expect);
+        // direction driven by factor
+        kwargs.anti = false;
+        kwargs.direction = Direction::Both;
+        let fac = vec![1., 1., 1., 1., 1., 1., -1., -1., -1., -1., -1., -1.];
+        let signal: Vec<f64> = martingale_with_fac(&close, Some(&fac), filter.as_ref(), &kwargs)?;
+        assert_eq!(&signal[..6], &[0.25, 0.25, 0.5, 0.25, 0.5, 1.]);
+        assert_eq!(&signal[6..], &[-0.25, -0.25, -0.5, -1., -1., -1.]);
+        Ok(())
+    }
+}
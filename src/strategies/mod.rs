@@ -3,16 +3,34 @@ mod boll;
 mod delay_boll;
 
 mod auto_tangqian;
+mod combine;
 mod fix_time;
+mod grid;
+mod hold_control;
 mod martingale;
+mod oscillator_threshold;
+mod pairs;
 mod prob_threshold;
+#[cfg(feature = "time")]
+mod session_filter;
 mod strategy_filter;
+mod vol_target;
 
 pub use auto_boll::{auto_boll, AutoBollKwargs};
 pub use auto_tangqian::{auto_tangqian, AutoTangQiAnKwargs};
 pub use boll::{boll, BollKwargs};
+pub use combine::{combine_signals, CombineKwargs, CombineMethod};
 pub use delay_boll::{delay_boll, DelayBollKwargs};
 pub use fix_time::{fix_time, FixTimeKwargs};
-pub use martingale::{martingale, MartingaleKwargs};
+pub use grid::{grid, GridKwargs, GridSpacing};
+pub use hold_control::HoldControl;
+pub use martingale::{martingale, martingale_with_fac, Direction, MartingaleKwargs};
+pub use oscillator_threshold::{
+    calc_oscillator, oscillator_threshold, Oscillator, OscillatorThresholdKwargs,
+};
+pub use pairs::{calc_hedge_ratio, pairs, HedgeMethod, PairsKwargs};
 pub use prob_threshold::{prob_threshold, ProbThresholdKwargs};
+#[cfg(feature = "time")]
+pub use session_filter::SessionFilter;
 pub use strategy_filter::StrategyFilter;
+pub use vol_target::{vol_target, VolTargetKwargs};
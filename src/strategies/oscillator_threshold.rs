@@ -0,0 +1,475 @@
+use itertools::izip;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tevec::prelude::*;
+
+use super::strategy_filter::FilterElement;
+use crate::StrategyFilter;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Oscillator {
+    Rsi,
+    StochK,
+    StochD,
+    Cci,
+    WilliamsR,
+}
+
+impl Oscillator {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "rsi" => Ok(Oscillator::Rsi),
+            "stoch_k" | "stochk" | "k" => Ok(Oscillator::StochK),
+            "stoch_d" | "stochd" | "d" => Ok(Oscillator::StochD),
+            "cci" => Ok(Oscillator::Cci),
+            "williams_r" | "williamsr" | "wr" => Ok(Oscillator::WilliamsR),
+            _ => tbail!("invalid oscillator type"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Oscillator::Rsi => "rsi",
+            Oscillator::StochK => "stoch_k",
+            Oscillator::StochD => "stoch_d",
+            Oscillator::Cci => "cci",
+            Oscillator::WilliamsR => "williams_r",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Oscillator {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Oscillator::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Oscillator {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OscillatorThresholdKwargs {
+    pub oscillator: Oscillator,
+    /// window of the oscillator
+    pub window: usize,
+    /// smooth window of stochastic %D, default 3
+    pub smooth: Option<usize>,
+    pub min_periods: Option<usize>,
+    // long open thres, long stop thres, short open thres, short stop thres
+    pub thresholds: (f64, f64, f64, f64),
+    /// lookback window of divergence confirmation, None means no confirmation
+    pub divergence: Option<usize>,
+    pub long_signal: f64,
+    pub short_signal: f64,
+    pub close_signal: f64,
+}
+
+impl Default for OscillatorThresholdKwargs {
+    fn default() -> Self {
+        Self {
+            oscillator: Oscillator::Rsi,
+            window: 14,
+            smooth: None,
+            min_periods: None,
+            thresholds: (30., 50., 70., 50.),
+            divergence: None,
+            long_signal: 1.0,
+            short_signal: -1.0,
+            close_signal: 0.0,
+        }
+    }
+}
+
+fn check_kwargs(kwargs: &OscillatorThresholdKwargs) -> TResult<()> {
+    tensure!(
+        kwargs.window >= 1,
+        "window should be greater than or equal to 1"
+    );
+    tensure!(
+        kwargs.thresholds.0 < kwargs.thresholds.1,
+        "long open thres should be less than long stop thres"
+    );
+    tensure!(
+        kwargs.thresholds.2 > kwargs.thresholds.3,
+        "short open thres should be greater than short stop thres"
+    );
+    tensure!(
+        kwargs.thresholds.0 < kwargs.thresholds.2,
+        "long open thres should be less than short open thres"
+    );
+    if let Some(n) = kwargs.divergence {
+        tensure!(
+            n >= 1,
+            "divergence window should be greater than or equal to 1"
+        );
+    }
+    Ok(())
+}
+
+#[inline]
+fn to_f64_vec<T: IsNone, V: Vec1View<T>>(arr: &V) -> Vec<f64>
+where
+    T::Inner: Number,
+{
+    arr.titer()
+        .map(|v| v.to_opt().map(|v| v.f64()).unwrap_or(f64::NAN))
+        .collect_trusted_to_vec()
+}
+
+/// Wilder's moving average (RMA) with `alpha = 1 / window`, the first
+/// `window` valid values are averaged as the seed and invalid values are
+/// skipped.
+fn wilder_mean(arr: &[f64], window: usize, min_periods: usize) -> Vec<f64> {
+    let mut avg = f64::NAN;
+    let mut n = 0;
+    arr.iter()
+        .map(|&v| {
+            if v.not_none() {
+                n += 1;
+                avg = if n == 1 {
+                    v
+                } else {
+                    avg + (v - avg) / n.min(window) as f64
+                };
+            }
+            if n >= min_periods.max(1) {
+                avg
+            } else {
+                f64::NAN
+            }
+        })
+        .collect()
+}
+
+/// calculate the oscillator defined in kwargs, if high and low are not
+/// provided, close is used instead.
+///
+/// RSI smooths gains and losses with Wilder's moving average, CCI uses the
+/// mean absolute deviation of the typical price.
+pub fn calc_oscillator<T, V: Vec1View<T>>(
+    close_arr: &V,
+    high_low: Option<(&V, &V)>,
+    kwargs: &OscillatorThresholdKwargs,
+) -> Vec<f64>
+where
+    T: IsNone,
+    T::Inner: Number,
+{
+    let window = kwargs.window;
+    let min_periods = kwargs.min_periods.unwrap_or(window / 2);
+    let close = to_f64_vec(close_arr);
+    let (high, low) = if let Some((high, low)) = high_low {
+        (to_f64_vec(high), to_f64_vec(low))
+    } else {
+        (close.clone(), close.clone())
+    };
+    match kwargs.oscillator {
+        Oscillator::Rsi => {
+            let mut last_close = f64::NAN;
+            let (gain, loss): (Vec<f64>, Vec<f64>) = close
+                .iter()
+                .map(|&c| {
+                    let diff = c - last_close;
+                    if c.not_none() {
+                        last_close = c;
+                    }
+                    if diff.is_nan() {
+                        (f64::NAN, f64::NAN)
+                    } else {
+                        (diff.max(0.), (-diff).max(0.))
+                    }
+                })
+                .unzip();
+            let gain = wilder_mean(&gain, window, min_periods);
+            let loss = wilder_mean(&loss, window, min_periods);
+            izip!(gain, loss)
+                .map(|(g, l)| {
+                    if g + l > 0. {
+                        100. * g / (g + l)
+                    } else if g + l == 0. {
+                        50.
+                    } else {
+                        f64::NAN
+                    }
+                })
+                .collect()
+        },
+        Oscillator::StochK | Oscillator::StochD | Oscillator::WilliamsR => {
+            let hh: Vec<f64> = high.ts_vmax(window, Some(min_periods));
+            let ll: Vec<f64> = low.ts_vmin(window, Some(min_periods));
+            let is_williams = matches!(kwargs.oscillator, Oscillator::WilliamsR);
+            let k: Vec<f64> = izip!(close.iter(), hh, ll)
+                .map(|(&c, h, l)| {
+                    if h > l {
+                        if is_williams {
+                            -100. * (h - c) / (h - l)
+                        } else {
+                            100. * (c - l) / (h - l)
+                        }
+                    } else {
+                        f64::NAN
+                    }
+                })
+                .collect();
+            if let Oscillator::StochD = kwargs.oscillator {
+                let smooth = kwargs.smooth.unwrap_or(3);
+                k.ts_vmean(smooth, Some(smooth))
+            } else {
+                k
+            }
+        },
+        Oscillator::Cci => {
+            let tp: Vec<f64> = izip!(high, low, close.iter())
+                .map(|(h, l, &c)| (h + l + c) / 3.)
+                .collect();
+            let ma: Vec<f64> = tp.ts_vmean(window, Some(min_periods));
+            let min_periods = min_periods.min(window);
+            tp.iter()
+                .zip(ma.iter())
+                .enumerate()
+                .map(|(i, (&v, &m))| {
+                    if v.is_nan() || m.is_nan() {
+                        return f64::NAN;
+                    }
+                    let start = (i + 1).saturating_sub(window);
+                    let (sum, n) = tp[start..=i]
+                        .iter()
+                        .filter(|v| v.not_none())
+                        .fold((0., 0), |(sum, n), v| (sum + (v - m).abs(), n + 1));
+                    if n < min_periods || n == 0 {
+                        return f64::NAN;
+                    }
+                    let md = sum / n as f64;
+                    if md > 0. {
+                        (v - m) / (0.015 * md)
+                    } else {
+                        0.
+                    }
+                })
+                .collect()
+        },
+    }
+}
+
+/// Oscillator threshold strategy, open long when the oscillator is oversold
+/// and open short when it is overbought, positions are closed when the
+/// oscillator reverts back to the stop threshold.
+///
+/// If `divergence` is set, an open signal also requires the price to make a
+/// new low (high) of the lookback window while the oscillator does not.
+#[allow(clippy::collapsible_else_if, clippy::if_same_then_else)]
+pub fn oscillator_threshold<
+    O: Vec1<T::Cast<f64>>,
+    T,
+    V: Vec1View<T>,
+    VMask: Vec1View<Option<bool>>,
+>(
+    close_arr: &V,
+    high_low: Option<(&V, &V)>,
+    filter: Option<&StrategyFilter<VMask>>,
+    kwargs: &OscillatorThresholdKwargs,
+) -> TResult<O>
+where
+    T: IsNone,
+    T::Inner: Number,
+{
+    check_kwargs(kwargs)?;
+    let osc_arr = calc_oscillator(close_arr, high_low, kwargs);
+    let close = to_f64_vec(close_arr);
+    // the extreme of the previous n bars, used to confirm divergence
+    let div_n = kwargs.divergence.unwrap_or(1);
+    let prev_extreme = |arr: &Vec<f64>, is_max: bool| -> Vec<f64> {
+        let res: Vec<f64> = if is_max {
+            arr.ts_vmax(div_n, Some(div_n))
+        } else {
+            arr.ts_vmin(div_n, Some(div_n))
+        };
+        res.titer().vshift(1, None).collect_trusted_vec1()
+    };
+    let (close_min, close_max, osc_min, osc_max) = if kwargs.divergence.is_some() {
+        (
+            prev_extreme(&close, false),
+            prev_extreme(&close, true),
+            prev_extreme(&osc_arr, false),
+            prev_extreme(&osc_arr, true),
+        )
+    } else {
+        let nan_vec = vec![f64::NAN; close.len()];
+        (nan_vec.clone(), nan_vec.clone(), nan_vec.clone(), nan_vec)
+    };
+    let check_divergence = kwargs.divergence.is_some();
+    let mut last_signal = kwargs.close_signal;
+    let mut logic =
+        |osc: f64,
+         close: f64,
+         (close_min, close_max, osc_min, osc_max): (f64, f64, f64, f64),
+         (long_open, long_stop, short_open, short_stop): FilterElement| {
+            if osc.not_none() {
+                // == open condition
+                let mut open_flag = false;
+                let long_div_cond = !check_divergence || ((close <= close_min) && (osc > osc_min));
+                let short_div_cond = !check_divergence || ((close >= close_max) && (osc < osc_max));
+                if (last_signal != kwargs.long_signal)
+                    && (osc <= kwargs.thresholds.0)
+                    && long_open.unwrap_or(true)
+                    && long_div_cond
+                {
+                    last_signal = kwargs.long_signal;
+                    open_flag = true;
+                } else if (last_signal != kwargs.short_signal)
+                    && (osc >= kwargs.thresholds.2)
+                    && short_open.unwrap_or(true)
+                    && short_div_cond
+                {
+                    last_signal = kwargs.short_signal;
+                    open_flag = true;
+                }
+                // == stop condition
+                if (!open_flag) && (last_signal != kwargs.close_signal) {
+                    // we can skip stop condition if trade is already close or open
+                    if (last_signal == kwargs.long_signal)
+                        && ((osc >= kwargs.thresholds.1) || long_stop.unwrap_or(false))
+                    {
+                        last_signal = kwargs.close_signal;
+                    } else if (last_signal == kwargs.short_signal)
+                        && ((osc <= kwargs.thresholds.3) || short_stop.unwrap_or(false))
+                    {
+                        last_signal = kwargs.close_signal;
+                    }
+                }
+            }
+            last_signal.into_cast::<T>()
+        };
+    let out = if let Some(filter) = filter {
+        izip!(
+            osc_arr.titer(),
+            close.titer(),
+            izip!(close_min, close_max, osc_min, osc_max),
+            filter.titer(),
+        )
+        .map(|(osc, close, extreme, filter)| logic(osc, close, extreme, filter))
+        .collect_trusted_vec1()
+    } else {
+        izip!(
+            osc_arr.titer(),
+            close.titer(),
+            izip!(close_min, close_max, osc_min, osc_max),
+        )
+        .map(|(osc, close, extreme)| logic(osc, close, extreme, (None, None, None, None)))
+        .collect_trusted_vec1()
+    };
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use tevec::core::testing::assert_vec1d_equal_numeric;
+
+    use super::*;
+    #[test]
+    fn test_oscillator_threshold() -> TResult<()> {
+        let close = vec![
+            10., 11., 12., 13., 14., 13., 12., 11., 10., 9., 10., 11., 12., 13., 12., 11.,
+        ];
+        let kwargs = OscillatorThresholdKwargs {
+            oscillator: Oscillator::Rsi,
+            window: 4,
+            thresholds: (30., 50., 70., 50.),
+            ..Default::default()
+        };
+        let rsi = calc_oscillator(&close, None, &kwargs);
+        assert!(rsi[0].is_nan() && rsi[1].is_nan());
+        // wilder smoothing: avg = avg + (x - avg) / window after the seed
+        assert_vec1d_equal_numeric(
+            &rsi[2..10].to_vec(),
+            &[
+                100.,
+                100.,
+                100.,
+                75.,
+                56.25,
+                42.1875,
+                31.640625,
+                23.73046875,
+            ],
+            None,
+        );
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let signal: Vec<f64> = oscillator_threshold(&close, None, filter.as_ref(), &kwargs)?;
+        let expect = vec![
+            0., 0., -1., -1., -1., -1., -1., 0., 0., 1., 1., 0., 0., -1., -1., 0.,
+        ];
+        assert_eq!(signal, expect);
+        Ok(())
+    }
+
+    #[test]
+    fn test_oscillator_values() {
+        let high = vec![11., 12., 13., 12., 14., 15., 13.];
+        let low = vec![9., 10., 10., 9., 11., 12., 10.];
+        let close = vec![10., 11., 12., 10., 13., 14., 11.];
+        let calc = |oscillator| {
+            let kwargs = OscillatorThresholdKwargs {
+                oscillator,
+                window: 3,
+                smooth: Some(2),
+                min_periods: Some(3),
+                ..Default::default()
+            };
+            calc_oscillator(&close, Some((&high, &low)), &kwargs)
+        };
+        let nan = f64::NAN;
+        assert_vec1d_equal_numeric(
+            &calc(Oscillator::StochK),
+            &[nan, nan, 75., 25., 80., 250. / 3., 20.],
+            Some(1e-8),
+        );
+        assert_vec1d_equal_numeric(
+            &calc(Oscillator::StochD),
+            &[nan, nan, nan, 50., 52.5, 245. / 3., 155. / 3.],
+            Some(1e-8),
+        );
+        assert_vec1d_equal_numeric(
+            &calc(Oscillator::WilliamsR),
+            &[nan, nan, -25., -75., -20., -50. / 3., -80.],
+            Some(1e-8),
+        );
+        assert_vec1d_equal_numeric(
+            &calc(Oscillator::Cci),
+            &[nan, nan, 87.5, -100., 1000. / 11., 1300. / 17., -100.],
+            Some(1e-8),
+        );
+    }
+
+    #[test]
+    fn test_oscillator_divergence() -> TResult<()> {
+        let close = vec![10., 9., 8., 7., 6., 7., 6.5, 6., 5.9, 7., 8.];
+        let mut kwargs = OscillatorThresholdKwargs {
+            window: 3,
+            min_periods: Some(3),
+            ..Default::default()
+        };
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let signal: Vec<f64> = oscillator_threshold(&close, None, filter.as_ref(), &kwargs)?;
+        assert_eq!(signal, vec![0., 0., 0., 1., 1., 1., 1., 1., 1., 0., -1.]);
+        // the long is opened only when the close makes a new low of the last
+        // 3 bars while the rsi does not
+        kwargs.divergence = Some(3);
+        let signal: Vec<f64> = oscillator_threshold(&close, None, filter.as_ref(), &kwargs)?;
+        assert_eq!(signal, vec![0., 0., 0., 0., 0., 0., 0., 1., 1., 0., 0.]);
+        Ok(())
+    }
+}
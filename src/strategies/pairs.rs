@@ -0,0 +1,344 @@
+use itertools::izip;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tevec::prelude::*;
+
+use super::strategy_filter::FilterElement;
+use crate::StrategyFilter;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HedgeMethod {
+    Ols,
+    Kalman,
+}
+
+impl HedgeMethod {
+    #[inline]
+    pub fn parse(s: &str) -> TResult<Self> {
+        match s.to_lowercase().as_str() {
+            "ols" | "rolling_ols" => Ok(HedgeMethod::Ols),
+            "kalman" | "kf" => Ok(HedgeMethod::Kalman),
+            _ => tbail!("invalid hedge method"),
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HedgeMethod::Ols => "ols",
+            HedgeMethod::Kalman => "kalman",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for HedgeMethod {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        HedgeMethod::parse(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for HedgeMethod {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PairsKwargs {
+    /// window, open_width, stop_width
+    pub params: (usize, f64, f64),
+    pub min_periods: Option<usize>,
+    pub hedge_method: HedgeMethod,
+    /// window of rolling ols, use params.0 if None
+    pub hedge_window: Option<usize>,
+    /// state noise (delta) and observation noise of the kalman filter
+    pub kalman_params: (f64, f64),
+    pub long_signal: f64,
+    pub short_signal: f64,
+    pub close_signal: f64,
+}
+
+impl Default for PairsKwargs {
+    fn default() -> Self {
+        Self {
+            params: (20, 2., 0.5),
+            min_periods: None,
+            hedge_method: HedgeMethod::Ols,
+            hedge_window: None,
+            kalman_params: (1e-4, 1e-3),
+            long_signal: 1.0,
+            short_signal: -1.0,
+            close_signal: 0.0,
+        }
+    }
+}
+
+/// estimate the hedge ratio of `a` on `b`, so that `a - beta * b` is the spread
+pub fn calc_hedge_ratio<T, V: Vec1View<T>>(a_arr: &V, b_arr: &V, kwargs: &PairsKwargs) -> Vec<f64>
+where
+    T: IsNone,
+    T::Inner: Number,
+{
+    match kwargs.hedge_method {
+        HedgeMethod::Ols => {
+            let window = kwargs.hedge_window.unwrap_or(kwargs.params.0);
+            let min_periods = kwargs.min_periods.unwrap_or(window / 2);
+            a_arr.ts_vregx_beta(b_arr, window, Some(min_periods))
+        },
+        HedgeMethod::Kalman => {
+            // state is (beta, alpha), observation is a = beta * b + alpha
+            let (delta, ve) = kwargs.kalman_params;
+            let vw = delta / (1. - delta);
+            let mut x = [0_f64; 2];
+            let mut p = [[0_f64; 2]; 2];
+            let mut init = false;
+            izip!(a_arr.titer(), b_arr.titer())
+                .map(|(a, b)| {
+                    if a.is_none() || b.is_none() {
+                        return if init { x[0] } else { f64::NAN };
+                    }
+                    let a = a.unwrap().f64();
+                    let h = [b.unwrap().f64(), 1.];
+                    // predict
+                    let r = [[p[0][0] + vw, p[0][1]], [p[1][0], p[1][1] + vw]];
+                    let y_hat = h[0] * x[0] + h[1] * x[1];
+                    let e = a - y_hat;
+                    let rh = [
+                        r[0][0] * h[0] + r[0][1] * h[1],
+                        r[1][0] * h[0] + r[1][1] * h[1],
+                    ];
+                    let q = h[0] * rh[0] + h[1] * rh[1] + ve;
+                    // update
+                    let k = [rh[0] / q, rh[1] / q];
+                    x = [x[0] + k[0] * e, x[1] + k[1] * e];
+                    let hr = [
+                        h[0] * r[0][0] + h[1] * r[1][0],
+                        h[0] * r[0][1] + h[1] * r[1][1],
+                    ];
+                    p = [
+                        [r[0][0] - k[0] * hr[0], r[0][1] - k[0] * hr[1]],
+                        [r[1][0] - k[1] * hr[0], r[1][1] - k[1] * hr[1]],
+                    ];
+                    init = true;
+                    x[0]
+                })
+                .collect_trusted_to_vec()
+        },
+    }
+}
+
+/// Pairs trading strategy on the spread `a - beta * b`.
+///
+/// The spread is normalized by its rolling zscore, short the spread when
+/// zscore is above open width and long it when zscore is below -open width,
+/// the position is closed when zscore reverts back inside the stop width.
+///
+/// Returns the signal of leg a and leg b, the signal of leg b is sized by the
+/// hedge ratio and the price ratio at the time of opening, so that the
+/// notional of leg b is `beta` times the units of leg a.
+///
+/// The signals are notional weights (percent of equity) as expected by
+/// [`calc_pairs_future_ret`](crate::equity::calc_pairs_future_ret), which
+/// divides each leg by its own `multiplier * price`, so the contract
+/// multipliers of the legs need not be known here.
+#[allow(clippy::collapsible_else_if, clippy::if_same_then_else)]
+pub fn pairs<O: Vec1<T::Cast<f64>>, T, V: Vec1View<T>, VMask: Vec1View<Option<bool>>>(
+    a_arr: &V,
+    b_arr: &V,
+    filter: Option<&StrategyFilter<VMask>>,
+    kwargs: &PairsKwargs,
+) -> TResult<(O, O)>
+where
+    T: IsNone,
+    T::Inner: Number,
+{
+    tensure!(
+        a_arr.len() == b_arr.len(),
+        "length of leg a and leg b should be equal"
+    );
+    tensure!(
+        kwargs.params.1 > kwargs.params.2,
+        "open_width should be greater than stop_width"
+    );
+    // use the hedge ratio estimated up to the previous bar, otherwise the
+    // deviation of the current bar would be absorbed by the estimation
+    let beta_arr: Vec<f64> = calc_hedge_ratio(a_arr, b_arr, kwargs)
+        .titer()
+        .vshift(1, None)
+        .collect_trusted_vec1();
+    let spread_arr: Vec<f64> = izip!(a_arr.titer(), b_arr.titer(), beta_arr.titer())
+        .map(|(a, b, beta)| {
+            if a.not_none() && b.not_none() {
+                a.unwrap().f64() - beta * b.unwrap().f64()
+            } else {
+                f64::NAN
+            }
+        })
+        .collect_trusted_to_vec();
+    let min_periods = kwargs.min_periods.unwrap_or(kwargs.params.0 / 2);
+    let middle_arr: Vec<f64> = spread_arr.ts_vmean(kwargs.params.0, Some(min_periods));
+    let std_arr: Vec<f64> = spread_arr.ts_vstd(kwargs.params.0, Some(min_periods));
+    let mut last_signal = kwargs.close_signal;
+    // signal of leg b per unit signal of leg a, fixed when the trade is opened
+    let mut hedge_weight = 0.;
+    let mut logic =
+        |a: T,
+         b: T,
+         beta: f64,
+         spread: f64,
+         middle: f64,
+         std: f64,
+         (long_open, long_stop, short_open, short_stop): FilterElement| {
+            if spread.not_none() && middle.not_none() && std.not_none() && std > 0. {
+                let zscore = (spread - middle) / std;
+                let mut open_flag = false;
+                // == open condition
+                if (last_signal != kwargs.long_signal)
+                    && (zscore <= -kwargs.params.1)
+                    && long_open.unwrap_or(true)
+                {
+                    last_signal = kwargs.long_signal;
+                    open_flag = true;
+                } else if (last_signal != kwargs.short_signal)
+                    && (zscore >= kwargs.params.1)
+                    && short_open.unwrap_or(true)
+                {
+                    last_signal = kwargs.short_signal;
+                    open_flag = true;
+                }
+                if open_flag {
+                    hedge_weight = -beta * b.unwrap().f64() / a.unwrap().f64();
+                }
+                // == stop condition
+                if (!open_flag) && (last_signal != kwargs.close_signal) {
+                    if (last_signal == kwargs.long_signal)
+                        && ((zscore >= -kwargs.params.2) || long_stop.unwrap_or(false))
+                    {
+                        last_signal = kwargs.close_signal;
+                    } else if (last_signal == kwargs.short_signal)
+                        && ((zscore <= kwargs.params.2) || short_stop.unwrap_or(false))
+                    {
+                        last_signal = kwargs.close_signal;
+                    }
+                }
+            }
+            (
+                last_signal.into_cast::<T>(),
+                (last_signal * hedge_weight).into_cast::<T>(),
+            )
+        };
+    let out: Vec<_> = if let Some(filter) = filter {
+        izip!(
+            a_arr.titer(),
+            b_arr.titer(),
+            beta_arr.titer(),
+            izip!(spread_arr.titer(), middle_arr.titer(), std_arr.titer()),
+            filter.titer(),
+        )
+        .map(|(a, b, beta, (spread, middle, std), filter)| {
+            logic(a, b, beta, spread, middle, std, filter)
+        })
+        .collect_trusted_to_vec()
+    } else {
+        izip!(
+            a_arr.titer(),
+            b_arr.titer(),
+            beta_arr.titer(),
+            izip!(spread_arr.titer(), middle_arr.titer(), std_arr.titer()),
+        )
+        .map(|(a, b, beta, (spread, middle, std))| {
+            logic(a, b, beta, spread, middle, std, (None, None, None, None))
+        })
+        .collect_trusted_to_vec()
+    };
+    let leg_a = out.titer().map(|(a, _)| a).collect_trusted_vec1();
+    let leg_b = out.into_iter().map(|(_, b)| b).collect_trusted_vec1();
+    Ok((leg_a, leg_b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equity::{calc_pairs_future_ret_result, CommissionType, PairsFutureRetKwargs};
+    #[test]
+    fn test_pairs() -> TResult<()> {
+        let b: Vec<f64> = (0..20)
+            .map(|i| 10. + 0.5 * i as f64 + [0., 0.2, -0.1, 0.1][i % 4])
+            .collect();
+        let mut noise = vec![0.; 20];
+        noise[10] = 1.;
+        let a: Vec<f64> = b.iter().zip(&noise).map(|(b, n)| 2. * b + n).collect();
+        let kwargs = PairsKwargs {
+            params: (8, 1.5, 0.5),
+            ..Default::default()
+        };
+        let beta = calc_hedge_ratio(&a, &b, &kwargs);
+        assert!((beta[8] - 2.).abs() < 1e-7);
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let (leg_a, leg_b): (Vec<f64>, Vec<f64>) = pairs(&a, &b, filter.as_ref(), &kwargs)?;
+        // spread is too high, short leg a and long leg b
+        assert_eq!(leg_a[10], -1.);
+        assert!(leg_b[10] > 0.);
+        for (sa, sb) in leg_a.iter().zip(&leg_b) {
+            if *sa == 0. {
+                assert_eq!(*sb, 0.);
+            }
+        }
+        // spread is too low, long leg a and short leg b
+        let a: Vec<f64> = b.iter().zip(&noise).map(|(b, n)| 2. * b - n).collect();
+        let (leg_a, leg_b): (Vec<f64>, Vec<f64>) = pairs(&a, &b, filter.as_ref(), &kwargs)?;
+        assert_eq!(leg_a[10], 1.);
+        assert!(leg_b[10] < 0.);
+        // kalman filter should also find the hedge ratio
+        let kwargs = PairsKwargs {
+            hedge_method: HedgeMethod::Kalman,
+            ..kwargs
+        };
+        let beta = calc_hedge_ratio(&a, &b, &kwargs);
+        assert!((beta[19] - 2.).abs() < 0.3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pairs_multiplier() -> TResult<()> {
+        let b: Vec<f64> = (0..20)
+            .map(|i| 10. + 0.5 * i as f64 + [0., 0.2, -0.1, 0.1][i % 4])
+            .collect();
+        let mut noise = vec![0.; 20];
+        noise[10] = 1.;
+        let a: Vec<f64> = b.iter().zip(&noise).map(|(b, n)| 2. * b + n).collect();
+        let kwargs = PairsKwargs {
+            params: (8, 1.5, 0.5),
+            ..Default::default()
+        };
+        let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
+        let (leg_a, leg_b): (Vec<f64>, Vec<f64>) = pairs(&a, &b, filter.as_ref(), &kwargs)?;
+        // the legs have different multipliers, the hedge holds beta units of
+        // leg b per unit of leg a
+        let ret_kwargs = PairsFutureRetKwargs {
+            init_cash: 10_000_000,
+            multiplier: (10., 5.),
+            leverage: 0.5,
+            slippage: (0., 0.),
+            c_rate: 0.,
+            blowup: false,
+            commission_type: CommissionType::Percent,
+        };
+        let (res_a, res_b) =
+            calc_pairs_future_ret_result(&leg_a, &leg_b, &a, &a, &b, &b, &ret_kwargs);
+        let beta = calc_hedge_ratio(&a, &b, &kwargs);
+        assert!(res_a.lots[10] < 0.);
+        let units_a = res_a.lots[10] * 10.;
+        let units_b = res_b.lots[10] * 5.;
+        assert!((units_b / units_a + beta[9]).abs() < 1e-3);
+        Ok(())
+    }
+}
@@ -1,15 +1,19 @@
 use itertools::izip;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tevec::prelude::*;
 
+use super::hold_control::{HoldControl, HoldState};
 use crate::StrategyFilter;
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ProbThresholdKwargs {
     // long open thres, long stop thres, short open thres, short stop thres
     pub thresholds: (f64, f64, f64, f64),
     pub per_hand: f64,
     pub max_hand: f64,
+    /// holding controls, given as a nested `control` table in configs
+    #[serde(default)]
+    pub control: HoldControl,
 }
 
 fn check_kwargs(kwargs: &ProbThresholdKwargs) -> TResult<()> {
@@ -48,10 +52,18 @@ where
     f64: Cast<U>,
 {
     check_kwargs(kwargs)?;
+    if filter.is_none() && kwargs.control.is_active() {
+        // holding controls are applied through the open flags of filter
+        let filter = StrategyFilter::full(fac_arr.len(), None);
+        return prob_threshold(fac_arr, Some(&filter), kwargs);
+    }
+    let mut state = HoldState::new(&kwargs.control, 0.);
     let mut last_signal = 0.;
     let out = if let Some(filter) = filter {
         izip!(fac_arr.titer(), filter.titer(),)
             .map(|(fac, (long_open, long_stop, short_open, short_stop))| {
+                let (long_open, short_open) = state.open_flags(long_open, short_open);
+                let prev_signal = last_signal;
                 if fac.not_none() {
                     let fac = fac.unwrap().f64();
                     // open condition
@@ -95,6 +107,7 @@ where
                         }
                     }
                 }
+                last_signal = state.update(prev_signal, last_signal);
                 last_signal.cast()
             })
             .collect_trusted_vec1()
@@ -158,6 +171,7 @@ mod tests {
             thresholds: (0.6, 0.5, 0.4, 0.5),
             per_hand: 1.,
             max_hand: 2.,
+            control: HoldControl::default(),
         };
         let filter: Option<StrategyFilter<Vec<Option<bool>>>> = None;
         let signal: Vec<f64> = prob_threshold(&prob_vec, filter.as_ref(), &kwargs)?;
@@ -0,0 +1,372 @@
+use serde::{Deserialize, Serialize};
+use tevec::prelude::*;
+
+use crate::StrategyFilter;
+
+const DAY_SECONDS: i64 = 86400;
+
+/// parse `HH:MM` or `HH:MM:SS` to seconds of the day
+fn parse_time(s: &str) -> TResult<i64> {
+    let parts = s
+        .trim()
+        .split(':')
+        .map(|p| p.parse::<i64>().map_err(|_| terr!("invalid time: {}", s)))
+        .collect::<TResult<Vec<_>>>()?;
+    tensure!(parts.len() == 2 || parts.len() == 3, "invalid time: {}", s);
+    let (h, m, sec) = (
+        parts[0],
+        parts[1],
+        if parts.len() == 3 { parts[2] } else { 0 },
+    );
+    tensure!(
+        (0..24).contains(&h) && (0..60).contains(&m) && (0..60).contains(&sec),
+        "invalid time: {}",
+        s
+    );
+    Ok(h * 3600 + m * 60 + sec)
+}
+
+/// days since 1970-01-01 of a civil date
+fn days_from_civil(y: i32, m: usize, d: usize) -> i64 {
+    let y = y as i64 - (m <= 2) as i64;
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = m as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// days since 1970-01-01 of the date of `dt`
+#[inline]
+fn days_of(dt: &DateTime) -> Option<i64> {
+    Some(days_from_civil(dt.year()?, dt.month()?, dt.day()?))
+}
+
+/// skip saturday and sunday, 1970-01-01 is a thursday
+#[inline]
+fn next_weekday(days: i64) -> i64 {
+    match (days + 3).rem_euclid(7) {
+        5 => days + 2,
+        6 => days + 1,
+        _ => days,
+    }
+}
+
+/// A window of the day, `end` earlier than `start` means the window crosses
+/// midnight. Both ends are included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Window {
+    start: i64,
+    end: i64,
+}
+
+impl Window {
+    fn parse((start, end): &(String, String)) -> TResult<Self> {
+        Ok(Self {
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        })
+    }
+
+    #[inline]
+    fn contains(&self, t: i64) -> bool {
+        if self.start <= self.end {
+            t >= self.start && t <= self.end
+        } else {
+            t >= self.start || t <= self.end
+        }
+    }
+
+    #[inline]
+    fn any_contains(windows: &[Window], t: i64) -> bool {
+        Iterator::any(&mut windows.iter(), |w| w.contains(t))
+    }
+
+    /// seconds from `t` to the end of the window
+    #[inline]
+    fn secs_to_end(&self, t: i64) -> i64 {
+        (self.end - t).rem_euclid(DAY_SECONDS)
+    }
+}
+
+/// Build a [`StrategyFilter`] from the time of each bar.
+///
+/// Times are given as `HH:MM` or `HH:MM:SS` pairs of (start, end), a window
+/// whose end is earlier than its start crosses midnight, e.g. a night
+/// session from `21:00` to `02:30`. Blackout dates are given as `%Y-%m-%d`
+/// and are trading days: bars at or after `night_start` belong to the next
+/// trading day, weekends are skipped, so the friday night session belongs
+/// to monday.
+///
+/// Open flags are `false` outside the sessions, outside the open windows,
+/// in the last `no_open_last` minutes before a session close and on
+/// blackout dates. Stop flags are `true` in the force flat windows, in the
+/// last `force_flat_last` minutes before a session close and on blackout
+/// dates. A session close is a session end listed in `session_closes`, so
+/// breaks inside the day (e.g. `10:15` and `11:30`) are not closes.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct SessionFilter {
+    /// trading sessions, the whole day is one session if empty
+    #[serde(default)]
+    pub sessions: Vec<(String, String)>,
+    /// opening is only allowed in these windows, no restriction if empty
+    #[serde(default)]
+    pub open_windows: Vec<(String, String)>,
+    /// force the position to be flat in these windows
+    #[serde(default)]
+    pub force_flat_windows: Vec<(String, String)>,
+    /// do not open in the last n minutes of each session close
+    pub no_open_last: Option<usize>,
+    /// force flat in the last n minutes of each session close
+    pub force_flat_last: Option<usize>,
+    /// session ends at which the position should be flat, e.g. the day close
+    /// and the night close, every session end is a close if empty
+    #[serde(default)]
+    pub session_closes: Vec<String>,
+    /// start of the night session, bars at or after it belong to the next
+    /// trading day
+    pub night_start: Option<String>,
+    /// dates without trading
+    #[serde(default)]
+    pub blackout_dates: Vec<String>,
+}
+
+impl SessionFilter {
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sessions of Chinese futures, `night_end` is the end of the night
+    /// session which depends on the product (e.g. `23:00`, `01:00`, `02:30`),
+    /// no night session if None.
+    pub fn china_futures(night_end: Option<&str>) -> Self {
+        let mut sessions = vec![
+            ("09:00".to_string(), "10:15".to_string()),
+            ("10:30".to_string(), "11:30".to_string()),
+            ("13:30".to_string(), "15:00".to_string()),
+        ];
+        let mut closes = vec!["15:00".to_string()];
+        let mut filter = Self::new();
+        if let Some(night_end) = night_end {
+            sessions.push(("21:00".to_string(), night_end.to_string()));
+            closes.push(night_end.to_string());
+            filter = filter.with_night_start("21:00");
+        }
+        filter.with_sessions(sessions).with_session_closes(closes)
+    }
+
+    /// Sessions of Chinese financial futures (index futures).
+    pub fn china_index_futures() -> Self {
+        Self::new()
+            .with_sessions(vec![
+                ("09:30".to_string(), "11:30".to_string()),
+                ("13:00".to_string(), "15:00".to_string()),
+            ])
+            .with_session_closes(vec!["15:00".to_string()])
+    }
+
+    #[inline]
+    pub fn with_sessions(mut self, sessions: Vec<(String, String)>) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    #[inline]
+    pub fn with_session_closes(mut self, closes: Vec<String>) -> Self {
+        self.session_closes = closes;
+        self
+    }
+
+    #[inline]
+    pub fn with_night_start(mut self, start: &str) -> Self {
+        self.night_start = Some(start.to_string());
+        self
+    }
+
+    #[inline]
+    pub fn with_open_window(mut self, start: &str, end: &str) -> Self {
+        self.open_windows.push((start.to_string(), end.to_string()));
+        self
+    }
+
+    #[inline]
+    pub fn with_force_flat_window(mut self, start: &str, end: &str) -> Self {
+        self.force_flat_windows
+            .push((start.to_string(), end.to_string()));
+        self
+    }
+
+    #[inline]
+    pub fn with_no_open_last(mut self, minutes: usize) -> Self {
+        self.no_open_last = Some(minutes);
+        self
+    }
+
+    #[inline]
+    pub fn with_force_flat_last(mut self, minutes: usize) -> Self {
+        self.force_flat_last = Some(minutes);
+        self
+    }
+
+    #[inline]
+    pub fn with_blackout_dates(mut self, dates: Vec<String>) -> Self {
+        self.blackout_dates.extend(dates);
+        self
+    }
+
+    /// Build the filter, the flags of invalid times are None.
+    pub fn build<V: Vec1View<DateTime>>(
+        &self,
+        time_vec: &V,
+    ) -> TResult<StrategyFilter<Vec<Option<bool>>>> {
+        let parse_windows = |windows: &Vec<(String, String)>| {
+            windows
+                .iter()
+                .map(Window::parse)
+                .collect::<TResult<Vec<_>>>()
+        };
+        let sessions = parse_windows(&self.sessions)?;
+        let open_windows = parse_windows(&self.open_windows)?;
+        let force_flat_windows = parse_windows(&self.force_flat_windows)?;
+        let session_closes = self
+            .session_closes
+            .iter()
+            .map(|s| parse_time(s))
+            .collect::<TResult<Vec<_>>>()?;
+        let night_start = self.night_start.as_deref().map(parse_time).transpose()?;
+        let blackout_dates = self
+            .blackout_dates
+            .iter()
+            .map(|s| {
+                let dt = DateTime::parse(s, None)?;
+                days_of(&dt).ok_or_else(|| terr!("invalid date: {}", s))
+            })
+            .collect::<TResult<Vec<_>>>()?;
+        let no_open_last = self.no_open_last.map(|m| m as i64 * 60);
+        let force_flat_last = self.force_flat_last.map(|m| m as i64 * 60);
+        let (open_vec, stop_vec): (Vec<_>, Vec<_>) = time_vec
+            .titer()
+            .map(|dt| {
+                if dt.is_nat() {
+                    return (None, None);
+                }
+                let (Some(h), Some(m), Some(s)) = (dt.hour(), dt.minute(), dt.second()) else {
+                    return (None, None);
+                };
+                let t = (h * 3600 + m * 60 + s) as i64;
+                let Some(days) = days_of(&dt) else {
+                    return (None, None);
+                };
+                let night = night_start.map(|n| t >= n).unwrap_or(false);
+                let trading_day = next_weekday(days + night as i64);
+                if blackout_dates.contains(&trading_day) {
+                    return (Some(false), Some(true));
+                }
+                let session = if sessions.is_empty() {
+                    Some(Window {
+                        start: 0,
+                        end: DAY_SECONDS - 1,
+                    })
+                } else {
+                    sessions.iter().find(|w| w.contains(t)).copied()
+                };
+                let Some(session) = session else {
+                    // not in trading time
+                    return (Some(false), Some(false));
+                };
+                // seconds to the close, None if the session does not end at a close
+                let to_close = (session_closes.is_empty() || session_closes.contains(&session.end))
+                    .then(|| session.secs_to_end(t));
+                let open = (open_windows.is_empty() || Window::any_contains(&open_windows, t))
+                    && !matches!((no_open_last, to_close), (Some(n), Some(c)) if c <= n);
+                let stop = Window::any_contains(&force_flat_windows, t)
+                    || matches!((force_flat_last, to_close), (Some(n), Some(c)) if c <= n);
+                (Some(open), Some(stop))
+            })
+            .unzip();
+        Ok(StrategyFilter {
+            long_open: open_vec.clone(),
+            long_stop: stop_vec.clone(),
+            short_open: open_vec,
+            short_stop: stop_vec,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_filter() -> TResult<()> {
+        let time_vec: Vec<DateTime> = [
+            "2024-01-02 09:01:00",
+            "2024-01-02 10:14:00",
+            "2024-01-02 10:20:00",
+            "2024-01-02 14:40:00",
+            "2024-01-02 14:50:00",
+            "2024-01-02 14:58:00",
+            "2024-01-02 21:30:00",
+            "2024-01-03 00:30:00",
+            "2024-01-03 00:58:00",
+            "2024-01-04 09:30:00",
+        ]
+        .into_iter()
+        .map(|s| DateTime::parse(s, None).unwrap())
+        .collect();
+        let filter = SessionFilter::china_futures(Some("01:00"))
+            .with_no_open_last(15)
+            .with_force_flat_last(5)
+            .with_blackout_dates(vec!["2024-01-04".to_string()])
+            .build(&time_vec)?;
+        let open: Vec<bool> = filter.long_open.iter().map(|v| v.unwrap()).collect();
+        let stop: Vec<bool> = filter.short_stop.iter().map(|v| v.unwrap()).collect();
+        // 10:14 is a break of the day session, not a close
+        assert_eq!(
+            open,
+            vec![true, true, false, true, false, false, true, true, false, false]
+        );
+        assert_eq!(
+            stop,
+            vec![false, false, false, false, false, true, false, false, true, true]
+        );
+        // open window and force flat window
+        let filter = SessionFilter::new()
+            .with_open_window("09:00", "10:00")
+            .with_force_flat_window("14:55", "15:00")
+            .build(&time_vec)?;
+        assert_eq!(filter.long_open[0], Some(true));
+        assert_eq!(filter.long_open[3], Some(false));
+        assert_eq!(filter.long_stop[5], Some(true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_filter_night_blackout() -> TResult<()> {
+        let time_vec: Vec<DateTime> = [
+            "2024-01-04 14:30:00",
+            "2024-01-04 21:30:00", // night session of friday
+            "2024-01-05 00:30:00",
+            "2024-01-05 09:30:00",
+            "2024-01-05 21:30:00", // night session of monday
+            "2024-01-06 00:30:00",
+            "2024-01-08 09:30:00",
+        ]
+        .into_iter()
+        .map(|s| DateTime::parse(s, None).unwrap())
+        .collect();
+        let filter = SessionFilter::china_futures(Some("01:00"))
+            .with_blackout_dates(vec!["2024-01-05".to_string()])
+            .build(&time_vec)?;
+        let stop: Vec<bool> = filter.long_stop.iter().map(|v| v.unwrap()).collect();
+        assert_eq!(stop, vec![false, true, true, true, false, false, false]);
+        let filter = SessionFilter::china_futures(Some("01:00"))
+            .with_blackout_dates(vec!["2024-01-08".to_string()])
+            .build(&time_vec)?;
+        let stop: Vec<bool> = filter.long_stop.iter().map(|v| v.unwrap()).collect();
+        assert_eq!(stop, vec![false, false, false, false, true, true, true]);
+        Ok(())
+    }
+}
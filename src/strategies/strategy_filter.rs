@@ -13,6 +13,26 @@ pub struct StrategyFilter<T: Vec1View<Option<bool>>> {
 
 pub type FilterElement = (Option<bool>, Option<bool>, Option<bool>, Option<bool>);
 
+/// and of two flags, a None flag is taken as `none` (true for an open flag
+/// and false for a stop flag), the result is None only if both are None
+#[inline]
+fn flag_and(a: Option<bool>, b: Option<bool>, none: bool) -> Option<bool> {
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(none) && b.unwrap_or(none)),
+    }
+}
+
+/// or of two flags, a None flag is taken as `none` (true for an open flag
+/// and false for a stop flag), the result is None only if both are None
+#[inline]
+fn flag_or(a: Option<bool>, b: Option<bool>, none: bool) -> Option<bool> {
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(none) || b.unwrap_or(none)),
+    }
+}
+
 impl<T: Vec1View<Option<bool>>> StrategyFilter<T> {
     pub fn titer(&self) -> TrustIter<impl Iterator<Item = FilterElement> + '_> {
         let iter = izip!(
@@ -23,30 +43,277 @@ impl<T: Vec1View<Option<bool>>> StrategyFilter<T> {
         );
         TrustIter::new(iter, self.long_open.len())
     }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.long_open.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.long_open.is_empty()
+    }
+
+    fn check_len(&self) -> TResult<()> {
+        let len = self.len();
+        tensure!(
+            self.long_stop.len() == len
+                && self.short_open.len() == len
+                && self.short_stop.len() == len,
+            "length of filter columns should be equal"
+        );
+        Ok(())
+    }
+
+    fn zip_with<T2: Vec1View<Option<bool>>>(
+        &self,
+        other: &StrategyFilter<T2>,
+        open_op: fn(Option<bool>, Option<bool>, bool) -> Option<bool>,
+        stop_op: fn(Option<bool>, Option<bool>, bool) -> Option<bool>,
+    ) -> TResult<StrategyFilter<Vec<Option<bool>>>> {
+        self.check_len()?;
+        other.check_len()?;
+        tensure!(
+            self.len() == other.len(),
+            "length of filters should be equal"
+        );
+        let mut out = StrategyFilter::with_capacity(self.len());
+        // a None open flag allows opening and a None stop flag does not stop
+        izip!(self.titer(), other.titer()).for_each(|(a, b)| {
+            out.push((
+                open_op(a.0, b.0, true),
+                stop_op(a.1, b.1, false),
+                open_op(a.2, b.2, true),
+                stop_op(a.3, b.3, false),
+            ))
+        });
+        Ok(out)
+    }
+
+    /// Both filters must allow opening, and the position is stopped if
+    /// either filter stops it. A None flag puts no constraint.
+    #[inline]
+    pub fn and<T2: Vec1View<Option<bool>>>(
+        &self,
+        other: &StrategyFilter<T2>,
+    ) -> TResult<StrategyFilter<Vec<Option<bool>>>> {
+        self.zip_with(other, flag_and, flag_or)
+    }
+
+    /// Either filter allows opening, and the position is stopped only if
+    /// both filters stop it. A None flag puts no constraint.
+    #[inline]
+    pub fn or<T2: Vec1View<Option<bool>>>(
+        &self,
+        other: &StrategyFilter<T2>,
+    ) -> TResult<StrategyFilter<Vec<Option<bool>>>> {
+        self.zip_with(other, flag_or, flag_and)
+    }
+
+    /// Invert the open flags of the filter, the stop flags and None flags
+    /// are kept, so that the inverted filter does not stop a position that
+    /// is held.
+    pub fn not(&self) -> TResult<StrategyFilter<Vec<Option<bool>>>> {
+        self.check_len()?;
+        let mut out = StrategyFilter::with_capacity(self.len());
+        self.titer()
+            .for_each(|(lo, ls, so, ss)| out.push((lo.map(|v| !v), ls, so.map(|v| !v), ss)));
+        Ok(out)
+    }
+
+    /// Combine multiple filters by [`StrategyFilter::and`].
+    pub fn combine(filters: &[&Self]) -> TResult<StrategyFilter<Vec<Option<bool>>>> {
+        tensure!(!filters.is_empty(), "filters should not be empty");
+        let mut out = filters[0].and(&StrategyFilter::<Vec<Option<bool>>>::full(
+            filters[0].len(),
+            None,
+        ))?;
+        for filter in &filters[1..] {
+            out = out.and(*filter)?;
+        }
+        Ok(out)
+    }
 }
 
-#[cfg(feature = "polars")]
-impl From<DataFrame> for StrategyFilter<BooleanChunked> {
-    fn from(df: DataFrame) -> Self {
-        assert_eq!(df.width(), 4);
+impl StrategyFilter<Vec<Option<bool>>> {
+    /// a filter whose flags are all `value`
+    #[inline]
+    pub fn full(len: usize, value: Option<bool>) -> Self {
         Self {
-            long_open: df.select_at_idx(0).unwrap().bool().unwrap().clone(),
-            long_stop: df.select_at_idx(1).unwrap().bool().unwrap().clone(),
-            short_open: df.select_at_idx(2).unwrap().bool().unwrap().clone(),
-            short_stop: df.select_at_idx(3).unwrap().bool().unwrap().clone(),
+            long_open: vec![value; len],
+            long_stop: vec![value; len],
+            short_open: vec![value; len],
+            short_stop: vec![value; len],
         }
     }
-}
 
-#[cfg(feature = "polars")]
-impl<'a> From<&'a DataFrame> for StrategyFilter<&'a BooleanChunked> {
-    fn from(df: &'a DataFrame) -> Self {
-        assert_eq!(df.width(), 4);
+    #[inline]
+    fn with_capacity(len: usize) -> Self {
         Self {
-            long_open: df.select_at_idx(0).unwrap().bool().unwrap(),
-            long_stop: df.select_at_idx(1).unwrap().bool().unwrap(),
-            short_open: df.select_at_idx(2).unwrap().bool().unwrap(),
-            short_stop: df.select_at_idx(3).unwrap().bool().unwrap(),
+            long_open: Vec::with_capacity(len),
+            long_stop: Vec::with_capacity(len),
+            short_open: Vec::with_capacity(len),
+            short_stop: Vec::with_capacity(len),
         }
     }
+
+    #[inline]
+    fn push(&mut self, (lo, ls, so, ss): FilterElement) {
+        self.long_open.push(lo);
+        self.long_stop.push(ls);
+        self.short_open.push(so);
+        self.short_stop.push(ss);
+    }
+}
+
+#[cfg(feature = "polars")]
+impl<'a> StrategyFilter<&'a BooleanChunked> {
+    /// Build the filter from named boolean columns, the names are in the
+    /// order of long open, long stop, short open and short stop.
+    pub fn from_df(df: &'a DataFrame, names: [&str; 4]) -> TResult<Self> {
+        let get = |name: &str| -> TResult<&'a BooleanChunked> {
+            df.column(name)
+                .and_then(|s| s.bool())
+                .map_err(|e| terr!("invalid filter column {}: {}", name, e))
+        };
+        Ok(Self {
+            long_open: get(names[0])?,
+            long_stop: get(names[1])?,
+            short_open: get(names[2])?,
+            short_stop: get(names[3])?,
+        })
+    }
+}
+
+#[cfg(feature = "polars")]
+impl TryFrom<DataFrame> for StrategyFilter<BooleanChunked> {
+    type Error = TError;
+
+    /// build the filter from the first four columns of the dataframe
+    fn try_from(df: DataFrame) -> TResult<Self> {
+        let filter = StrategyFilter::try_from(&df)?;
+        Ok(Self {
+            long_open: filter.long_open.clone(),
+            long_stop: filter.long_stop.clone(),
+            short_open: filter.short_open.clone(),
+            short_stop: filter.short_stop.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "polars")]
+impl<'a> TryFrom<&'a DataFrame> for StrategyFilter<&'a BooleanChunked> {
+    type Error = TError;
+
+    /// build the filter from the first four columns of the dataframe
+    fn try_from(df: &'a DataFrame) -> TResult<Self> {
+        tensure!(
+            df.width() == 4,
+            "dataframe of strategy filter should have 4 columns, got {}",
+            df.width()
+        );
+        let get = |idx: usize| -> TResult<&'a BooleanChunked> {
+            df.select_at_idx(idx)
+                .unwrap()
+                .bool()
+                .map_err(|e| terr!("invalid filter column {}: {}", idx, e))
+        };
+        Ok(Self {
+            long_open: get(0)?,
+            long_stop: get(1)?,
+            short_open: get(2)?,
+            short_stop: get(3)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_logic() -> TResult<()> {
+        let trend = StrategyFilter {
+            long_open: vec![Some(true), Some(true), Some(false), None],
+            long_stop: vec![Some(false), Some(true), Some(false), None],
+            short_open: vec![Some(false), Some(false), Some(true), None],
+            short_stop: vec![Some(true), Some(false), Some(false), None],
+        };
+        let session = StrategyFilter {
+            long_open: vec![Some(true), Some(false), Some(true), Some(false)],
+            long_stop: vec![Some(false), Some(false), Some(true), Some(true)],
+            short_open: vec![Some(true), Some(false), Some(true), Some(false)],
+            short_stop: vec![Some(false), Some(false), Some(true), Some(true)],
+        };
+        let filter = trend.and(&session)?;
+        assert_eq!(
+            filter.long_open,
+            vec![Some(true), Some(false), Some(false), Some(false)]
+        );
+        assert_eq!(
+            filter.long_stop,
+            vec![Some(false), Some(true), Some(true), Some(true)]
+        );
+        let filter = trend.or(&session)?;
+        // a None open flag of trend allows opening whatever the session is,
+        // and its None stop flag never stops
+        assert_eq!(
+            filter.short_open,
+            vec![Some(true), Some(false), Some(true), Some(true)]
+        );
+        assert_eq!(
+            filter.short_stop,
+            vec![Some(false), Some(false), Some(false), Some(false)]
+        );
+        let filter = trend.not()?;
+        assert_eq!(
+            filter.long_open,
+            vec![Some(false), Some(false), Some(true), None]
+        );
+        assert_eq!(filter.long_stop, trend.long_stop);
+        assert_eq!(filter.short_stop, trend.short_stop);
+        let filter = StrategyFilter::combine(&[&trend, &session, &trend])?;
+        assert_eq!(filter.long_open, trend.and(&session)?.long_open);
+        let short = StrategyFilter::<Vec<Option<bool>>>::full(3, None);
+        assert!(trend.and(&short).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "polars")]
+    #[test]
+    fn test_filter_from_df() -> TResult<()> {
+        use tevec::export::polars::prelude::*;
+
+        let df = df!(
+            "lo" => [Some(true), None],
+            "ls" => [Some(false), Some(true)],
+            "so" => [Some(false), Some(true)],
+            "ss" => [None, Some(false)],
+        )
+        .unwrap();
+        let filter = StrategyFilter::from_df(&df, ["lo", "ls", "so", "ss"])?;
+        assert_eq!(
+            filter.titer().collect::<Vec<_>>(),
+            vec![
+                (Some(true), Some(false), Some(false), None),
+                (None, Some(true), Some(true), Some(false))
+            ]
+        );
+        let err = StrategyFilter::from_df(&df, ["lo", "ls", "so", "missing"]).err();
+        assert!(err.unwrap().to_string().contains("missing"));
+        let owned = StrategyFilter::try_from(df.clone())?;
+        assert_eq!(
+            owned.titer().collect::<Vec<_>>(),
+            filter.titer().collect::<Vec<_>>()
+        );
+        // a missing column, and a column that is not boolean
+        let df3 = df.select(["lo", "ls", "so"]).unwrap();
+        assert!(StrategyFilter::try_from(&df3).is_err());
+        let mut df_f64 = df.clone();
+        df_f64
+            .with_column(Series::new("ss".into(), [1.0, 2.0]))
+            .unwrap();
+        assert!(StrategyFilter::try_from(&df_f64).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,154 @@
+use itertools::izip;
+use serde::{Deserialize, Serialize};
+use tevec::prelude::*;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct VolTargetKwargs {
+    /// rolling window of realized volatility
+    pub window: usize,
+    pub min_periods: Option<usize>,
+    /// target annualized volatility
+    pub target_vol: f64,
+    /// number of periods in a year, used to annualize the realized volatility
+    pub annualize: f64,
+    /// max absolute position after scaling
+    pub max_leverage: f64,
+    /// do not rebalance if the relative change of position is within the band
+    pub rebalance_band: f64,
+}
+
+impl Default for VolTargetKwargs {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            min_periods: None,
+            target_vol: 0.15,
+            annualize: 252.,
+            max_leverage: 2.,
+            rebalance_band: 0.,
+        }
+    }
+}
+
+/// Scale a signal vector to a target annualized volatility.
+///
+/// The realized volatility is the rolling std of the percent returns of
+/// close, the position is `signal * target_vol / realized_vol` and is capped
+/// by `max_leverage`. Before the realized volatility is available the
+/// position is zero. If the signal is unchanged, the position is only
+/// rebalanced when it moves out of the rebalance band.
+pub fn vol_target<O: Vec1<T::Cast<f64>>, T, V: Vec1View<T>>(
+    signal_vec: &V,
+    close_vec: &V,
+    kwargs: &VolTargetKwargs,
+) -> TResult<O>
+where
+    T: IsNone,
+    T::Inner: Number,
+{
+    tensure!(
+        signal_vec.len() == close_vec.len(),
+        "length of signal and close should be equal"
+    );
+    tensure!(
+        kwargs.target_vol > 0.,
+        "target vol should be greater than 0"
+    );
+    tensure!(
+        kwargs.max_leverage > 0.,
+        "max leverage should be greater than 0"
+    );
+    tensure!(
+        kwargs.rebalance_band >= 0.,
+        "rebalance band should be greater than or equal to 0"
+    );
+    let mut last_close = f64::NAN;
+    let ret_vec: Vec<f64> = close_vec
+        .titer()
+        .map(|close| {
+            if close.not_none() {
+                let close = close.unwrap().f64();
+                let ret = close / last_close - 1.;
+                last_close = close;
+                ret
+            } else {
+                f64::NAN
+            }
+        })
+        .collect_trusted_to_vec();
+    let min_periods = kwargs.min_periods.unwrap_or(kwargs.window / 2);
+    let vol_vec: Vec<f64> = ret_vec.ts_vstd(kwargs.window, Some(min_periods));
+    let ann = kwargs.annualize.sqrt();
+    let mut last_signal = f64::NAN;
+    let mut last_pos = 0_f64;
+    let out = izip!(signal_vec.titer(), vol_vec.titer())
+        .map(|(signal, vol)| {
+            if signal.is_none() {
+                return last_pos.into_cast::<T>();
+            }
+            let signal = signal.unwrap().f64();
+            let vol = vol * ann;
+            let pos = if vol.not_none() && vol > 0. {
+                (signal * kwargs.target_vol / vol).clamp(-kwargs.max_leverage, kwargs.max_leverage)
+            } else {
+                0.
+            };
+            let in_band = (signal == last_signal)
+                && (pos.signum() == last_pos.signum())
+                && ((pos - last_pos).abs() <= kwargs.rebalance_band * last_pos.abs());
+            if !in_band {
+                last_pos = pos;
+            }
+            last_signal = signal;
+            last_pos.into_cast::<T>()
+        })
+        .collect_trusted_vec1();
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use tevec::core::testing::assert_vec1d_equal_numeric;
+
+    use super::*;
+    #[test]
+    fn test_vol_target() -> TResult<()> {
+        let ret = vec![
+            0., 0.01, -0.01, 0.01, -0.01, 0.01, -0.01, 0.012, -0.012, 0.01,
+        ];
+        let mut close = 100.;
+        let close_vec: Vec<f64> = ret
+            .iter()
+            .map(|r| {
+                close *= 1. + r;
+                close
+            })
+            .collect();
+        let signal = vec![1., 1., 1., 1., 1., 1., -1., -1., -1., -1.];
+        let mut kwargs = VolTargetKwargs {
+            window: 4,
+            min_periods: Some(4),
+            target_vol: 0.01,
+            annualize: 1.,
+            max_leverage: 2.,
+            rebalance_band: 0.,
+        };
+        let std = (0.0004_f64 / 3.).sqrt();
+        let pos: Vec<f64> = vol_target(&signal, &close_vec, &kwargs)?;
+        assert_vec1d_equal_numeric(
+            &pos[..7].to_vec(),
+            &vec![0., 0., 0., 0., 0.01 / std, 0.01 / std, -0.01 / std],
+            Some(1e-7),
+        );
+        assert!(pos[8].abs() < pos[6].abs());
+        // the change caused by volatility is within the band
+        kwargs.rebalance_band = 0.5;
+        let pos: Vec<f64> = vol_target(&signal, &close_vec, &kwargs)?;
+        assert_eq!(pos[8], pos[6]);
+        // leverage is capped
+        kwargs.target_vol = 1.;
+        let pos: Vec<f64> = vol_target(&signal, &close_vec, &kwargs)?;
+        assert_eq!(pos[5], 2.);
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use std::{fmt::Debug, str::FromStr};
 
 use derive_more::From;
 use itertools::izip;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tevec::prelude::*;
 
 #[derive(Copy, Clone, PartialEq)]
@@ -47,11 +48,36 @@ impl FromStr for TradeSide {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+impl Serialize for TradeSide {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TradeSide {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A trade, the time is serialized as nanoseconds since the unix epoch.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Trade {
+    #[serde(with = "crate::serde_utils::datetime_as_ns")]
     pub time: DateTime,
     pub side: TradeSide,
+    #[serde(with = "crate::serde_utils::nan_as_null")]
     pub price: f64,
+    #[serde(with = "crate::serde_utils::nan_as_null")]
     pub num: f64,
 }
 
@@ -212,10 +238,34 @@ mod tests {
             time.titer(),
         );
         let expect = vec![
-            Trade::new(time[2].clone(), TradeSide::Buy, price[2].clone(), 0.5),
-            Trade::new(time[4].clone(), TradeSide::Buy, price[4].clone(), 0.5),
-            Trade::new(time[5].clone(), TradeSide::Sell, price[5].clone(), 0.8),
+            Trade::new(time[2], TradeSide::Buy, price[2], 0.5),
+            Trade::new(time[4], TradeSide::Buy, price[4], 0.5),
+            Trade::new(time[5], TradeSide::Sell, price[5], 0.8),
         ];
         assert_eq!(trades, expect)
     }
+
+    #[test]
+    fn test_trade_serde() {
+        let time = DateTime::<unit::Nanosecond>::parse("2021-01-01 00:01:00", None).unwrap();
+        let trades = vec![
+            Trade::new(time, TradeSide::Buy, 10., 0.5),
+            Trade::new(time, TradeSide::Sell, f64::NAN, 0.5),
+        ];
+        let json = serde_json::to_string(&trades).unwrap();
+        let de: Vec<Trade> = serde_json::from_str(&json).unwrap();
+        assert_eq!(de[0], trades[0]);
+        assert_eq!(de[1].side, TradeSide::Sell);
+        assert!(de[1].price.is_nan());
+        assert_eq!(
+            serde_json::to_value(Trade::new(DateTime::nat(), TradeSide::Buy, 1., 1.)).unwrap()
+                ["time"],
+            serde_json::Value::Null
+        );
+        let bytes = bincode::serialize(&trades).unwrap();
+        let de: Vec<Trade> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(de[0], trades[0]);
+        assert_eq!(de[1].time, time);
+        assert!(de[1].price.is_nan());
+    }
 }
@@ -0,0 +1,47 @@
+"""Tests of the polars expression plugins, run by `make test-py`."""
+from pathlib import Path
+
+import polars as pl
+import pytest
+
+import tea_strategy
+
+BOLL_KWARGS = {
+    "params": [4, 1.0, 0.0, None],
+    "min_periods": None,
+    "delay_open": False,
+    "zscore": True,
+    "long_signal": 1.0,
+    "short_signal": -1.0,
+    "close_signal": 0.0,
+}
+
+FAC = [10, 11, 11.9, 10, 11, 12, 10, 11, 12, 13, 14, 10, 7, 5, 4, 3, 4, 4, 3, 2]
+
+
+def plugin(name: str, fac: pl.Expr, kwargs: dict) -> pl.Expr:
+    return pl.plugins.register_plugin_function(
+        plugin_path=Path(tea_strategy.__file__),
+        function_name=name,
+        args=fac,
+        kwargs=kwargs,
+    )
+
+
+def test_boll_over_symbol():
+    n = len(FAC)
+    df = pl.DataFrame({"symbol": ["a"] * n + ["b"] * n, "fac": FAC + FAC[::-1]})
+    out = df.with_columns(
+        plugin("boll", pl.col("fac"), BOLL_KWARGS).over("symbol").alias("signal")
+    )
+    for symbol, fac in [("a", FAC), ("b", FAC[::-1])]:
+        expect = tea_strategy.boll(fac, BOLL_KWARGS)
+        signal = out.filter(pl.col("symbol") == symbol)["signal"]
+        assert signal.to_list() == expect.tolist()
+
+
+def test_strategy_error():
+    kwargs = {"thresholds": [0.4, 0.5, 0.6, 0.5], "per_hand": 1.0, "max_hand": 1.0}
+    df = pl.DataFrame({"fac": FAC})
+    with pytest.raises(pl.exceptions.ComputeError, match="long open thres"):
+        df.select(plugin("prob_threshold", pl.col("fac"), kwargs))